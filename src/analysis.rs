@@ -0,0 +1,349 @@
+/*
+  Analyses a file of inputs, as loaded by the REPL's `:load` command, without evaluating it:  the errors and
+  warnings the lexer, parser, variable resolution and lints find, the `let` definitions, and what each
+  variable refers to.  Used by the language server.
+
+  Variables are resolved as a session would resolve them:  against the latest earlier definition of the name,
+  then the globals.  `_` refers to the latest earlier expression.  Unlike a session, an input with errors still
+  defines its name, so that one mistake is not reported again at every use of the name.  A definition whose
+  value no input of the file uses is reported once the file has been analysed, where a session can only report
+  it when the name is defined again.
+
+  For highlighting, `classify_tokens` walks the tokens of the file and classifies each by what the analysis
+  found it to be.  The language has no strings or functions, so there are no classes for them, and the only
+  definitions are `let`s.
+*/
+
+use diagnostic::*;
+use env::*;
+use error::*;
+use ast::*;
+use lexer::*;
+use passes::*;
+use passes::lint::*;
+use passes::resolve_variables::similar_names;
+use repl::*;
+use source::*;
+
+/// A `let` definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub name_span: Span,
+    /// The span of the whole definition, from `let` to the end of the expression.
+    pub span: Span
+}
+
+/// What a variable refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    /// The global of the same name.
+    Global,
+    /// The definition with this index in `Analysis::definitions`.
+    Let(usize),
+    /// `_`, the value of the expression with this span.
+    LastResult(Span),
+    /// Nothing;  an error has been reported.
+    Undefined
+}
+
+/// A use of a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub binding: Binding
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// The errors and warnings, in the order they were found.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The definitions, in source order.  These are the symbols of the file, as every definition is at the top
+    /// level.
+    pub definitions: Vec<Definition>,
+    /// The variables, in source order.
+    pub references: Vec<Reference>
+}
+
+/// Returns true if `span` contains the byte offset `offset`, or if `adjacent` is set, ends just before it.
+fn span_at(span: &Span, offset: usize, adjacent: bool) -> bool {
+    span.start.offset <= offset && (offset <= span.end.offset || (adjacent && offset == span.end.offset + 1))
+}
+
+impl Analysis {
+    /// The reference at the byte offset `offset`.  A reference which ends just before `offset` is found when no
+    /// reference contains it, so that the name before the cursor is found.
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        self.references.iter().find(|r| span_at(&r.span, offset, false))
+            .or_else(|| self.references.iter().find(|r| span_at(&r.span, offset, true)))
+    }
+
+    /// The definition whose name is at the byte offset `offset`, found like `reference_at`.
+    pub fn definition_at(&self, offset: usize) -> Option<usize> {
+        self.definitions.iter().position(|d| span_at(&d.name_span, offset, false))
+            .or_else(|| self.definitions.iter().position(|d| span_at(&d.name_span, offset, true)))
+    }
+
+    /// The definitions which end before the byte offset `offset`.  A name defined more than once is only
+    /// included for its latest definition.
+    pub fn definitions_before(&self, offset: usize) -> Vec<&Definition> {
+        let before: Vec<&Definition> = self.definitions.iter().filter(|d| d.span.end.offset < offset).collect();
+        before.iter().enumerate()
+            .filter(|&(i, d)| !before[i + 1..].iter().any(|later| later.name == d.name))
+            .map(|(_, d)| *d)
+            .collect()
+    }
+}
+
+/// Analyses `source`, the text of `file`, whose inputs are evaluated with `globals`, checking the lints enabled
+/// by `config`.  See `split_inputs`.
+pub fn analyze(source: &str, file: FileId, globals: &EnvDef, config: &LintConfig) -> Analysis {
+    let mut analyzer = Analyzer {
+        globals,
+        last_result: None,
+        analysis: Analysis { diagnostics: Vec::new(), definitions: Vec::new(), references: Vec::new() }
+    };
+    for (start, end) in split_inputs(source) {
+        let input = match parse_input(source, file, start, end) {
+            Ok(input) => input,
+            Err(error) => {
+                analyzer.analysis.diagnostics.push(Diagnostic::from_error(&error));
+                continue;
+            }
+        };
+        analyzer.analysis.diagnostics.extend(input.errors.iter().map(Diagnostic::from_error));
+        analyzer.resolve(&input.expr);
+        if input.errors.is_empty() {
+            analyzer.analysis.diagnostics.extend(lint(&input.expr, config).iter().map(Diagnostic::from_error));
+        }
+        //The name is defined after its expression has been resolved, as the expression cannot refer to it.
+        match input.header {
+            Some(header) => {
+                if config.is_enabled(Lint::ShadowedGlobal) && globals.find(&header.name).is_some() {
+                    let warning = LintWarning::new_with_span(Lint::ShadowedGlobal, header.name_span);
+                    analyzer.analysis.diagnostics.push(Diagnostic::from_error(&warning));
+                }
+                analyzer.analysis.definitions.push(Definition {
+                    name: header.name,
+                    name_span: header.name_span,
+                    span: header.keyword_span.to(input.expr.span)
+                })
+            },
+            None => analyzer.last_result = Some(input.expr.span)
+        }
+    }
+    if config.is_enabled(Lint::UnusedLet) {
+        for (i, definition) in analyzer.analysis.definitions.iter().enumerate() {
+            if !analyzer.analysis.references.iter().any(|r| r.binding == Binding::Let(i)) {
+                let warning = LintWarning::new_with_span(Lint::UnusedLet, definition.name_span);
+                analyzer.analysis.diagnostics.push(Diagnostic::from_error(&warning));
+            }
+        }
+    }
+    analyzer.analysis
+}
+
+/// The class of a token, for highlighting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenClass {
+    Keyword,
+    Number,
+    Operator,
+    GlobalVariable,
+    /// A variable bound by a `let`, or `_`.
+    LocalVariable
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassifiedToken {
+    pub class: TokenClass,
+    pub span: Span,
+    /// Set for the name of a definition.
+    pub declaration: bool
+}
+
+/// Classifies the tokens of `source`, the text of `file` whose analysis is `analysis`, in source order.
+/// Parentheses, undefined variables and invalid characters are not classified.  The `=` of a definition is an
+/// operator.
+pub fn classify_tokens(source: &str, file: FileId, analysis: &Analysis) -> Vec<ClassifiedToken> {
+    let mut tokens = Vec::new();
+    let mut lexer = Lexer::new_with_file(source.chars(), file);
+    loop {
+        let token = match lexer.next() {
+            LexResult::Ok(token) => token,
+            LexResult::Err(_) => continue,
+            LexResult::EndOfInput(_) => return tokens
+        };
+        let offset = token.span.start.offset;
+        let is_name = analysis.definitions.binary_search_by_key(&offset, |d| d.name_span.start.offset).is_ok();
+        let class = match token.kind {
+            TokenKind::LiteralInt32(_) => Some(TokenClass::Number),
+            TokenKind::BinaryOperator(_) => Some(TokenClass::Operator),
+            TokenKind::OpenParen | TokenKind::CloseParen => None,
+            TokenKind::Identifier(_) if is_name => Some(TokenClass::LocalVariable),
+            TokenKind::Identifier(ref name) => match analysis.references.binary_search_by_key(&offset, |r| r.span.start.offset) {
+                Ok(i) => match analysis.references[i].binding {
+                    Binding::Global => Some(TokenClass::GlobalVariable),
+                    Binding::Let(_) | Binding::LastResult(_) => Some(TokenClass::LocalVariable),
+                    Binding::Undefined => None
+                },
+                //An identifier which is neither a name nor a variable starts a definition.
+                Err(_) if name == "let" => Some(TokenClass::Keyword),
+                Err(_) => None
+            }
+        };
+        if let Some(class) = class {
+            tokens.push(ClassifiedToken { class, span: token.span, declaration: is_name });
+        }
+        //The name of a definition is followed by `=`, which is not a token of the language.
+        let equals = if is_name { lexer.next_char('=') } else { None };
+        if let Some(span) = equals {
+            tokens.push(ClassifiedToken { class: TokenClass::Operator, span, declaration: false });
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    globals: &'a EnvDef,
+    last_result: Option<Span>,
+    analysis: Analysis
+}
+
+impl <'a> Analyzer<'a> {
+    fn resolve(&mut self, expr: &Expr) {
+        match expr.kind {
+            ExprKind::VariableRef { ref name } => {
+                let binding = self.lookup(name);
+                if binding == Binding::Undefined {
+                    let error = SourceError::new_with_span(
+                        PassErrorKind::VariableDoesNotExist { variable_name: name.clone(), suggestions: similar_names(name, self.names().into_iter()) },
+                        expr.span);
+                    self.analysis.diagnostics.push(Diagnostic::from_error(&error));
+                }
+                self.analysis.references.push(Reference { name: name.clone(), span: expr.span, binding });
+            },
+            ExprKind::Binary { ref left, ref right, .. } => {
+                self.resolve(left);
+                self.resolve(right);
+            },
+            ExprKind::CompoundExpr { ref exprs } => {
+                for expr in exprs {
+                    self.resolve(expr);
+                }
+            },
+            ExprKind::Literal { .. } | ExprKind::VariableIndex { .. } | ExprKind::Error => {}
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Binding {
+        let definition = self.analysis.definitions.iter().rposition(|d| d.name == name);
+        match self.last_result {
+            Some(span) if name == LAST_RESULT && definition.is_none_or(|i| self.analysis.definitions[i].span.start.offset < span.start.offset) =>
+                Binding::LastResult(span),
+            _ => match definition {
+                Some(i) => Binding::Let(i),
+                None if self.globals.find(name).is_some() => Binding::Global,
+                None => Binding::Undefined
+            }
+        }
+    }
+
+    /// The names which are defined, for suggesting instead of a misspelt name.
+    fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.globals.fields().iter().map(|f| &f.name[..])
+            .chain(self.analysis.definitions.iter().map(|d| &d.name[..]))
+            .collect();
+        if self.last_result.is_some() {
+            names.push(LAST_RESULT);
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use value::*;
+
+    fn globals() -> EnvDef {
+        EnvDefBuilder::new().with_item("total", Value::Int32(5)).build()
+    }
+
+    fn codes(analysis: &Analysis) -> Vec<&str> {
+        analysis.diagnostics.iter().map(|d| d.code.unwrap()).collect()
+    }
+
+    #[test]
+    fn resolves_definitions_and_globals() {
+        let analysis = analyze("let a = total\nlet total = a + 1\ntotal * a\n_ + b\n", FileId::NONE, &globals(), &LintConfig::new());
+        assert_eq!(vec!["a", "total"], analysis.definitions.iter().map(|d| &d.name[..]).collect::<Vec<&str>>());
+        assert_eq!(Span::new(FileId::NONE, Location::new(1, 5, 4), Location::new(1, 5, 4)), analysis.definitions[0].name_span);
+        assert_eq!(
+            vec![Binding::Global, Binding::Let(0), Binding::Let(1), Binding::Let(0), Binding::LastResult(analysis.references[2].span.to(analysis.references[3].span)), Binding::Undefined],
+            analysis.references.iter().map(|r| r.binding.clone()).collect::<Vec<Binding>>());
+        assert_eq!(vec!["W0004", "E0301"], codes(&analysis));
+        assert_eq!("Variable 'b' does not exist; did you mean '_' or 'a'?", analysis.diagnostics[1].message);
+    }
+
+    #[test]
+    fn reports_unused_and_shadowing_definitions() {
+        let source = "let a = 1
+let a = a + 1
+let b = 2
+let b = 3
+let total = b
+";
+        let analysis = analyze(source, FileId::NONE, &globals(), &LintConfig::new());
+        assert_eq!(vec!["W0004", "W0003", "W0003", "W0003"], codes(&analysis));
+        assert_eq!(vec![14, 28, 48], analysis.diagnostics[1..].iter().map(|d| d.span.start.offset).collect::<Vec<usize>>());
+        let config = LintConfig::new().disable(Lint::UnusedLet).disable(Lint::ShadowedGlobal);
+        assert!(analyze(source, FileId::NONE, &globals(), &config).diagnostics.is_empty());
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let analysis = analyze("let = 1\n1 /\n 0\nlet b = c + )\nb\n", FileId::NONE, &globals(), &LintConfig::new());
+        assert_eq!(vec!["E0901", "W0002", "E0202", "E0301"], codes(&analysis));
+        //A definition with errors still defines its name.
+        assert_eq!(Binding::Let(0), analysis.references[1].binding);
+    }
+
+    #[test]
+    fn find_at_offsets() {
+        let analysis = analyze("let ab = 1\nab + ab\nlet c = 2\nlet ab = 3", FileId::NONE, &globals(), &LintConfig::new());
+        assert_eq!(Some(11), analysis.reference_at(12).map(|r| r.span.start.offset));
+        assert_eq!(Some(16), analysis.reference_at(16).map(|r| r.span.start.offset));
+        assert_eq!(Some(16), analysis.reference_at(18).map(|r| r.span.start.offset));
+        assert_eq!(None, analysis.reference_at(15));
+        assert_eq!(Some(0), analysis.definition_at(6));
+        assert_eq!(None, analysis.definition_at(3));
+        assert_eq!(vec!["ab"], analysis.definitions_before(19).iter().map(|d| &d.name[..]).collect::<Vec<&str>>());
+        assert_eq!(vec!["c", "ab"], analysis.definitions_before(40).iter().map(|d| &d.name[..]).collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn classify() {
+        let source = "let a = total // sum\n(a * 2) + _ - b $\nlet = 1";
+        let analysis = analyze(source, FileId::NONE, &globals(), &LintConfig::new());
+        //No expression precedes `_`, so it is undefined like `b`.
+        let classes: Vec<(TokenClass, usize, bool)> = classify_tokens(source, FileId::NONE, &analysis).into_iter()
+            .map(|t| (t.class, t.span.start.offset, t.declaration))
+            .collect();
+        assert_eq!(vec![
+            (TokenClass::Keyword, 0, false),
+            (TokenClass::LocalVariable, 4, true),
+            (TokenClass::Operator, 6, false),
+            (TokenClass::GlobalVariable, 8, false),
+            (TokenClass::LocalVariable, 22, false),
+            (TokenClass::Operator, 24, false),
+            (TokenClass::Number, 26, false),
+            (TokenClass::Operator, 29, false),
+            (TokenClass::Operator, 33, false),
+            (TokenClass::Keyword, 39, false),
+            (TokenClass::Number, 45, false)
+        ], classes);
+    }
+}
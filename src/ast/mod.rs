@@ -5,13 +5,17 @@ use common::*;
 
 use std::vec::Vec;
 
+pub mod printer;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum ExprKind {
     Literal{ value: Value },
     Binary{ op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
     VariableRef { name: String },
     VariableIndex { index: u32 },
-    CompoundExpr { exprs: Vec<Box<Expr>>}
+    CompoundExpr { exprs: Vec<Box<Expr>>},
+    /// Takes the place of an expression which could not be parsed.
+    Error
 }
 
 #[derive(Debug, Clone)]
@@ -67,6 +71,30 @@ impl Expr {
         Expr::new_with_span(ExprKind::CompoundExpr {exprs }, span)
     }
 
+    pub fn new_error_with_span(span: Span) -> Expr {
+        Expr::new_with_span(ExprKind::Error, span)
+    }
+
+    /// Returns true if `Error` nodes are found anywhere within the expression.
+    pub fn has_errors(&self) -> bool {
+        match self.kind {
+            ExprKind::Error => true,
+            ExprKind::Binary { ref left, ref right, .. } => left.has_errors() || right.has_errors(),
+            ExprKind::CompoundExpr { ref exprs } => exprs.iter().any(|e| e.has_errors()),
+            ExprKind::Literal { .. } | ExprKind::VariableRef { .. } | ExprKind::VariableIndex { .. } => false
+        }
+    }
+
+    /// Returns true if the variable `name` is referred to anywhere within the expression.
+    pub fn uses_variable(&self, name: &str) -> bool {
+        match self.kind {
+            ExprKind::VariableRef { name: ref variable } => variable == name,
+            ExprKind::Binary { ref left, ref right, .. } => left.uses_variable(name) || right.uses_variable(name),
+            ExprKind::CompoundExpr { ref exprs } => exprs.iter().any(|e| e.uses_variable(name)),
+            ExprKind::Literal { .. } | ExprKind::VariableIndex { .. } | ExprKind::Error => false
+        }
+    }
+
     fn new_with_span(kind: ExprKind, span: Span) -> Expr {
         Expr { kind, span }
     }
@@ -0,0 +1,119 @@
+/*
+  Renders an `Expr` back to canonical source text.
+
+  Binary operators are surrounded by single spaces and the expressions of a `CompoundExpr` are separated
+  by single spaces.  Parentheses are only inserted where the precedence and associativity used by the
+  parser (see `parser::get_binary_op_precedence`) require them, so for any `Expr` produced by the parser
+  `parse(&print(&expr)) == expr`.
+
+  There is no source syntax for negative literals or for `VariableIndex`, which only exists after
+  `resolve_variables`, or for the `Error` nodes of a partial parse.  Negative literals are printed as-is,
+  indexes are printed as `#<index>` and errors as `<error>`; none of these will parse.
+*/
+
+use std::fmt::Write;
+use ast::*;
+use value::Value;
+use parser::get_binary_op_precedence;
+
+pub fn print(expr: &Expr) -> String {
+    let mut out = String::new();
+    match expr.kind {
+        //A compound expression only needs parentheses when it is nested.
+        ExprKind::CompoundExpr { ref exprs } => write_sequence(exprs, &mut out),
+        _ => write_expr(expr, 0, &mut out)
+    }
+    out
+}
+
+pub fn op_text(op: &BinaryOp) -> &'static str {
+    match *op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+    }
+}
+
+fn write_sequence(exprs: &[Box<Expr>], out: &mut String) {
+    for (i, e) in exprs.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write_expr(e, 0, out);
+    }
+}
+
+/// Writes `expr` in a position where only binary operators with a precedence of at least
+/// `min_precedence` may appear without parentheses.
+fn write_expr(expr: &Expr, min_precedence: u32, out: &mut String) {
+    match expr.kind {
+        ExprKind::Literal { value: Value::Int32(v) } => write!(out, "{}", v).unwrap(),
+        ExprKind::VariableRef { ref name } => out.push_str(name),
+        ExprKind::VariableIndex { index } => write!(out, "#{}", index).unwrap(),
+        ExprKind::Error => out.push_str("<error>"),
+        ExprKind::Binary { ref op, ref left, ref right } => {
+            let precedence = get_binary_op_precedence(op);
+            let parens = precedence < min_precedence;
+            if parens {
+                out.push('(');
+            }
+            //Operators are left associative, so a right operand of the same precedence needs parentheses.
+            write_expr(left, precedence, out);
+            write!(out, " {} ", op_text(op)).unwrap();
+            write_expr(right, precedence + 1, out);
+            if parens {
+                out.push(')');
+            }
+        },
+        ExprKind::CompoundExpr { ref exprs } => {
+            out.push('(');
+            write_sequence(exprs, out);
+            out.push(')');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(v: i32) -> Expr {
+        Expr::new_literal(Value::Int32(v))
+    }
+
+    fn var(name: &str) -> Expr {
+        Expr::new_variable_ref(String::from(name))
+    }
+
+    #[test]
+    fn print_omits_unneeded_parens() {
+        let expr = Expr::new_binary(BinaryOp::Add, lit(1), Expr::new_binary(BinaryOp::Mul, var("a"), lit(3)));
+        assert_eq!("1 + a * 3", print(&expr));
+
+        let expr = Expr::new_binary(BinaryOp::Sub, Expr::new_binary(BinaryOp::Sub, lit(1), lit(2)), lit(3));
+        assert_eq!("1 - 2 - 3", print(&expr));
+    }
+
+    #[test]
+    fn print_inserts_needed_parens() {
+        let expr = Expr::new_binary(BinaryOp::Mul, Expr::new_binary(BinaryOp::Add, lit(1), lit(2)), lit(3));
+        assert_eq!("(1 + 2) * 3", print(&expr));
+
+        let expr = Expr::new_binary(BinaryOp::Sub, lit(1), Expr::new_binary(BinaryOp::Add, lit(2), lit(3)));
+        assert_eq!("1 - (2 + 3)", print(&expr));
+
+        let expr = Expr::new_binary(BinaryOp::Div, lit(8), Expr::new_binary(BinaryOp::Mod, lit(5), lit(3)));
+        assert_eq!("8 / (5 % 3)", print(&expr));
+    }
+
+    #[test]
+    fn print_compound() {
+        let inner = Expr::new_compound_expr(vec![Box::new(lit(1)), Box::new(var("b"))]);
+        let expr = Expr::new_compound_expr(vec![
+            Box::new(Expr::new_binary(BinaryOp::Add, lit(2), inner.clone())),
+            Box::new(inner)]);
+        assert_eq!("2 + (1 b) (1 b)", print(&expr));
+    }
+}
@@ -1,49 +1,402 @@
-
-use std::io::Write;
+use std::io::{IsTerminal, Read, Write};
+use std::time::Instant;
+use std::path::PathBuf;
 
 extern crate compiler_in_rust_lib;
 use compiler_in_rust_lib::*;
+use env::*;
 use error::*;
+use source::FileId;
+use source_map::*;
+use diagnostic::*;
+use lexer::Lexer;
+use value::*;
+use passes::lint::*;
+use globals::*;
+use repl::*;
+use line_editor::*;
 
-fn main() {
-    println!("Hello, enter an expression and I will evaluate it for you.  To view ast, type '?' as the first character of your expression.  To exit, type 'exit'.");
+/// The exit codes when evaluating a file, inline expression or standard input.
+const EXIT_PARSE_ERROR: i32 = 1;
+const EXIT_RESOLVE_ERROR: i32 = 2;
+const EXIT_EVALUATE_ERROR: i32 = 3;
 
-    while {
-        print!(">");
-        std::io::stdout().flush().unwrap();
-        let mut input = String::new();
-        match std::io::stdin().read_line(&mut input) {
-            Ok(bytes_read) => {
-                if bytes_read == 1 { // Only read newline
-                    true
-                } else {
-                    if input.starts_with("?") {
-
-                        match parse(&input[1..]) {
-                            Ok(expr) => println!("{:#?}", expr),
-                            Err(e) => println!("Error: {}", e.kind.message())
-                        }
-                          true
-                    } else {
-                        match input[..].trim() {
-                            "exit" => false,
-                            input => {
-                                let result = execute(&input);
-                                match result {
-                                    Err(e) => println!("Error: {}", e.kind.message()),
-                                    Ok(v) => println!("Result: {:?}", v)
-                                }
-                                true
-                            }
-                        }
-                    }
+/// The name of the REPL's history file, in the home directory.
+const HISTORY_FILE_NAME: &str = ".compiler_in_rust_history";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    Human,
+    /// One JSON object per line, see `diagnostic::to_json`.
+    Json
+}
+
+/// Prints diagnostics to standard output, or to standard error when `stderr` is set.
+struct Reporter {
+    format: ErrorFormat,
+    stderr: bool
+}
+
+impl Reporter {
+    fn report<TErrorKind: ErrorKind>(&self, error: &SourceError<TErrorKind>, source_map: &SourceMap) {
+        self.report_diagnostic(&Diagnostic::from_error(error), source_map);
+    }
+
+    fn report_diagnostic(&self, diagnostic: &Diagnostic, source_map: &SourceMap) {
+        let text = match self.format {
+            ErrorFormat::Human => {
+                let colour = if self.stderr { std::io::stderr().is_terminal() } else { std::io::stdout().is_terminal() };
+                render(diagnostic, source_map, &RenderOptions { colour })
+            },
+            ErrorFormat::Json => format!("{}\n", to_json(diagnostic, source_map))
+        };
+        if self.stderr {
+            eprint!("{}", text);
+        } else {
+            print!("{}", text);
+        }
+    }
+
+    fn report_all<TErrorKind: ErrorKind>(&self, errors: &[SourceError<TErrorKind>], source_map: &SourceMap) {
+        for error in errors {
+            self.report(error, source_map);
+        }
+    }
+}
+
+/// What to print for the input, rather than its value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    Tokens,
+    Ast,
+    Dot
+}
+
+/// What to evaluate.
+enum Input {
+    Repl,
+    File(String),
+    Expr(String),
+    Stdin
+}
+
+fn usage() -> ! {
+    eprintln!("usage: compiler_in_rust [--error-format=human|json] [--define <name>=<value>]... [--globals <file>]...");
+    eprintln!("                        [--emit=tokens|ast|dot] [<file> | -e <expr> | -]");
+    eprintln!("       compiler_in_rust --explain <code>");
+    eprintln!();
+    eprintln!("Evaluates <file>, the expression <expr> or standard input (-) and prints the result.  Without");
+    eprintln!("an input, starts an interactive session.  Exits with {} for syntax errors, {} for undefined",
+              EXIT_PARSE_ERROR, EXIT_RESOLVE_ERROR);
+    eprintln!("variables and {} for evaluation errors.", EXIT_EVALUATE_ERROR);
+    eprintln!();
+    eprintln!("Globals are defined with --define, or by a --globals file which is either a JSON object or has");
+    eprintln!("a <name>=<value> definition on each line.  Later definitions replace earlier ones.");
+    eprintln!();
+    eprintln!("--emit prints the tokens, the syntax tree or a Graphviz graph of the syntax tree of the input");
+    eprintln!("instead of evaluating it.");
+    eprintln!();
+    eprintln!("In a terminal, the interactive session keeps its history in ~/{}.", HISTORY_FILE_NAME);
+    std::process::exit(EXIT_USAGE);
+}
+
+fn explain_and_exit(code: &str) -> ! {
+    match explain::explanation(&code.to_uppercase()) {
+        Some(text) => {
+            print!("{}", text);
+            std::process::exit(0);
+        },
+        None => {
+            eprintln!("error: '{}' is not a known error code", code);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+fn exit_code(kind: &ExecuteErrorKind) -> i32 {
+    match *kind {
+        ExecuteErrorKind::Parse(_) => EXIT_PARSE_ERROR,
+        ExecuteErrorKind::Pass(_) => EXIT_RESOLVE_ERROR,
+        ExecuteErrorKind::Evaluate(_) => EXIT_EVALUATE_ERROR
+    }
+}
+
+/// Prints `emit` for `file`.  On failure returns the exit code for the error.
+fn emit(file: FileId, source_map: &SourceMap, emit: Emit, reporter: &Reporter) -> Result<String, i32> {
+    let source = &source_map.file(file).unwrap().source;
+    if emit == Emit::Tokens {
+        return match Lexer::new_with_file(source.chars(), file).tokens() {
+            Ok(tokens) => Ok(dump::tokens(&tokens)),
+            Err(error) => {
+                reporter.report(&error, source_map);
+                Err(EXIT_PARSE_ERROR)
+            }
+        };
+    }
+    let (expr, errors) = parse_recovering_with_file(source, file);
+    if !errors.is_empty() {
+        reporter.report_all(&errors, source_map);
+        return Err(EXIT_PARSE_ERROR);
+    }
+    Ok(match emit {
+        Emit::Ast => dump::ast(&expr),
+        _ => dump::dot(&expr)
+    })
+}
+
+/// Parses, lints and executes `file`, reporting every diagnostic.  On failure returns the exit code for the error.
+fn run(file: FileId, source_map: &SourceMap, env_def: &EnvDef, reporter: &Reporter) -> Result<Value, i32> {
+    let source = &source_map.file(file).unwrap().source;
+
+    //Every syntax error is reported, rather than only the first.
+    let (expr, errors) = parse_recovering_with_file(source, file);
+    if !errors.is_empty() {
+        reporter.report_all(&errors, source_map);
+        return Err(EXIT_PARSE_ERROR);
+    }
+    reporter.report_all(&lint(&expr, &LintConfig::new()), source_map);
+
+    execute_expr(expr, env_def).map_err(|e| {
+        reporter.report(&e, source_map);
+        exit_code(&e.kind)
+    })
+}
+
+fn main() {
+    let mut error_format = ErrorFormat::Human;
+    let mut input = Input::Repl;
+    let mut emit_mode = None;
+    let mut env_def_builder = EnvDefBuilder::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match globals_from_arg(&arg, &mut args) {
+            Some(Ok(globals)) => {
+                for (name, value) in globals {
+                    env_def_builder = env_def_builder.with_item(&name, value);
                 }
+                continue;
+            },
+            Some(Err(LoadGlobalsError::MissingValue(_))) => usage(),
+            Some(Err(err)) => {
+                eprintln!("error: {}", err);
+                std::process::exit(err.exit_code());
+            },
+            None => {}
+        }
+
+        let next_input = match &arg[..] {
+            "--error-format=human" => {
+                error_format = ErrorFormat::Human;
+                continue;
             },
+            "--error-format=json" => {
+                error_format = ErrorFormat::Json;
+                continue;
+            },
+            "--emit=tokens" | "--emit=ast" | "--emit=dot" => {
+                emit_mode = Some(match &arg[7..] {
+                    "tokens" => Emit::Tokens,
+                    "ast" => Emit::Ast,
+                    _ => Emit::Dot
+                });
+                continue;
+            },
+            "--explain" => explain_and_exit(&args.next().unwrap_or_else(|| usage())),
+            "-e" => Input::Expr(args.next().unwrap_or_else(|| usage())),
+            "-" => Input::Stdin,
+            _ if arg.starts_with('-') => usage(),
+            _ => Input::File(arg.clone())
+        };
+        //Only one input may be given.
+        if let Input::Repl = input {
+            input = next_input;
+        } else {
+            usage();
+        }
+    }
+
+    let mut source_map = SourceMap::new();
+    let env_def = env_def_builder.build();
+
+    let file = match input {
+        Input::Repl if emit_mode.is_some() => usage(),
+        Input::Repl => return repl(&env_def, error_format),
+        Input::File(path) => match source_map.load(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("error: cannot read '{}': {}", path, err);
+                std::process::exit(EXIT_IO_ERROR);
+            }
+        },
+        Input::Expr(source) => source_map.add("<expr>", source),
+        Input::Stdin => {
+            let mut source = String::new();
+            if let Err(err) = std::io::stdin().read_to_string(&mut source) {
+                eprintln!("error: cannot read standard input: {}", err);
+                std::process::exit(EXIT_IO_ERROR);
+            }
+            source_map.add("<stdin>", source)
+        }
+    };
+
+    let reporter = Reporter { format: error_format, stderr: true };
+    if let Some(emit_mode) = emit_mode {
+        match emit(file, &source_map, emit_mode, &reporter) {
+            Ok(text) => print!("{}", text),
+            Err(code) => std::process::exit(code)
+        }
+        return;
+    }
+    match run(file, &source_map, &env_def, &reporter) {
+        Ok(value) => println!("{}", value),
+        Err(code) => std::process::exit(code)
+    }
+}
+
+/// Where the REPL reads lines from.
+enum Lines {
+    /// Standard input and output are a terminal.
+    Editor(LineEditor),
+    Plain
+}
+
+impl Lines {
+    fn read(&mut self, prompt: &str, session: &Session) -> ReadLine {
+        if let Lines::Editor(ref mut editor) = *self {
+            match editor.read_line(prompt, &|line, cursor| session.complete(line, cursor)) {
+                Ok(ReadLine::Line(line)) => {
+                    editor.add_history(&line);
+                    return ReadLine::Line(line);
+                },
+                Ok(result) => return result,
+                //The terminal does not support raw mode, so lines are read without editing from now on.
+                Err(_) => *self = Lines::Plain
+            }
+        }
+
+        print!("{}", prompt);
+        std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => ReadLine::EndOfInput,
+            Ok(_) => ReadLine::Line(String::from(line.trim_end_matches(['\n', '\r']))),
             Err(err) => {
                 println!("error reading from standard input: {}", err);
-                false
+                ReadLine::EndOfInput
             }
         }
+    }
+}
+
+/// Reads an input, which continues over further lines while it is incomplete.  A blank line ends an incomplete
+/// input, so that its errors are reported.  Returns `None` at the end of standard input.
+fn read_input(lines: &mut Lines, session: &Session) -> Option<String> {
+    let mut input = String::new();
+    loop {
+        let line = match lines.read(if input.is_empty() { ">" } else { "." }, session) {
+            ReadLine::Line(line) => line,
+            //Ctrl-C abandons the input.
+            ReadLine::Interrupted => return Some(String::new()),
+            ReadLine::EndOfInput => return if input.is_empty() { None } else { Some(input) }
+        };
+        input.push_str(&line);
+        input.push('\n');
+        if line.trim().is_empty() || !is_incomplete(&input) {
+            return Some(input);
+        }
+    }
+}
+
+fn print_response(response: &Response, session: &Session, reporter: &Reporter) {
+    for diagnostic in &response.diagnostics {
+        reporter.report_diagnostic(diagnostic, session.source_map());
+    }
+    match response.outcome {
+        Outcome::Value(ref v) => println!("Result: {:?}", v),
+        Outcome::Defined(ref name, ref v) => println!("{} = {}", name, v),
+        Outcome::Failed => {}
+    }
+}
+
+/// Runs `command`, returning false if the REPL should exit.
+fn run_command(command: Command, session: &mut Session, reporter: &Reporter) -> bool {
+    let report = |diagnostics: Vec<Diagnostic>, session: &Session| for diagnostic in &diagnostics {
+        reporter.report_diagnostic(diagnostic, session.source_map());
+    };
+    match command {
+        Command::Ast(input) => match session.parse(&input) {
+            Ok(expr) => println!("{}", ast::printer::print(&expr)),
+            Err(diagnostics) => report(diagnostics, session)
+        },
+        Command::Tokens(input) => match session.tokens(&input) {
+            Ok(tokens) => for token in tokens {
+                println!("{:<8} {}", format!("{}:{}", token.span.start.line, token.span.start.col_offset), token.kind);
+            },
+            Err(diagnostics) => report(diagnostics, session)
+        },
+        Command::Type(input) => match session.type_of(&input) {
+            Ok(type_name) => println!("{}", type_name),
+            Err(diagnostics) => report(diagnostics, session)
+        },
+        Command::Bytecode(input) => match session.resolve(&input) {
+            Ok(expr) => print!("{}", bytecode::disassemble::disassemble(&bytecode::compile::compile(&expr))),
+            Err(diagnostics) => report(diagnostics, session)
+        },
+        Command::Time(input) => {
+            let start = Instant::now();
+            let response = session.eval(&input);
+            let elapsed = start.elapsed();
+            print_response(&response, session, reporter);
+            println!("Time: {:?}", elapsed);
+        },
+        Command::Load(path) => match std::fs::read_to_string(&path) {
+            Ok(source) => for response in session.load(&path, source) {
+                print_response(&response, session, reporter);
+            },
+            Err(err) => println!("error: cannot read '{}': {}", path, err)
+        },
+        Command::Env => for (name, value) in session.bindings() {
+            println!("{} = {}", name, value);
+        },
+        Command::Reset => {
+            session.reset();
+            println!("Definitions cleared.");
+        },
+        Command::Help => for &(name, argument, description) in COMMANDS {
+            println!("{:<18} {}", format!("{} {}", name, argument), description);
+        },
+        Command::Quit => return false
+    }
+    true
+}
+
+fn repl(env_def: &EnvDef, error_format: ErrorFormat) {
+    let reporter = Reporter { format: error_format, stderr: false };
+    let mut session = Session::new(env_def);
+    let mut lines = if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        let history_file = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE_NAME));
+        Lines::Editor(LineEditor::new(history_file))
+    } else {
+        Lines::Plain
+    };
 
-    } { }
-} 
+    println!("Hello, enter an expression and I will evaluate it for you.  Define variables with 'let <name> = <expression>'; the last result is '_'.  Type ':help' for a list of commands, or 'exit' to exit.");
+
+    while let Some(input) = read_input(&mut lines, &session) {
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+        if input == "exit" {
+            break;
+        }
+        match Command::parse(input) {
+            None => print_response(&session.eval(input), &session, &reporter),
+            Some(Err(message)) => println!("error: {}", message),
+            Some(Ok(command)) => if !run_command(command, &mut session, &reporter) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{self, BufReader};
+
+extern crate compiler_in_rust_lib;
+use compiler_in_rust_lib::*;
+use env::*;
+use globals::*;
+use lsp::*;
+
+fn usage() -> ! {
+    eprintln!("usage: lsp [--define <name>=<value>]... [--globals <file>]... [--stdio]");
+    eprintln!();
+    eprintln!("Runs a language server for files of inputs, speaking the Language Server Protocol on standard");
+    eprintln!("input and output.  Variables are resolved against the globals defined with --define and by");
+    eprintln!("--globals files, as for compiler_in_rust, and those of the `globals` initialization option.");
+    eprintln!("Exits with 0 if the client shut the server down before it exited, otherwise 1.");
+    std::process::exit(EXIT_USAGE);
+}
+
+fn main() {
+    let mut env_def_builder = EnvDefBuilder::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match globals_from_arg(&arg, &mut args) {
+            Some(Ok(globals)) => {
+                for (name, value) in globals {
+                    env_def_builder = env_def_builder.with_item(&name, value);
+                }
+            },
+            Some(Err(LoadGlobalsError::MissingValue(_))) => usage(),
+            Some(Err(err)) => {
+                eprintln!("error: {}", err);
+                std::process::exit(err.exit_code());
+            },
+            //Clients pass `--stdio` to servers which support several transports;  it is the only one.
+            None if arg == "--stdio" => {},
+            None => usage()
+        }
+    }
+
+    let mut server = Server::new(env_def_builder.build());
+    let stdin = io::stdin();
+    if let Err(err) = serve(BufReader::new(stdin.lock()), io::stdout().lock(), &mut server) {
+        eprintln!("error: {}", err);
+    }
+    std::process::exit(server.exit_code());
+}
@@ -0,0 +1,91 @@
+
+use ast::*;
+use common::*;
+use super::*;
+
+/// Compiles `expr` into a `Chunk`.  `expr` must have already been through the `resolve_variables` pass.
+pub fn compile(expr: &Expr) -> Chunk {
+    let mut chunk = Chunk::new();
+    compile_expr(expr, &mut chunk);
+    chunk
+}
+
+fn compile_expr(expr: &Expr, chunk: &mut Chunk) {
+    match expr.kind {
+        ExprKind::Literal { ref value } => {
+            let index = chunk.add_constant(value.clone());
+            chunk.emit(OpCode::Constant(index), expr.span);
+        },
+        ExprKind::VariableIndex { index } => chunk.emit(OpCode::LoadGlobal(index), expr.span),
+        //This case indicates that the `resolve_variables` pass was not executed against `expr`
+        //This would be a bug.
+        ExprKind::VariableRef { ref name } => panic!("Unresolved variable reference: {:?}", name),
+        //Only expressions which parsed without errors can be compiled.
+        ExprKind::Error => panic!("Syntax error at {:?}", expr.span),
+        ExprKind::Binary { ref op, ref left, ref right } => {
+            compile_expr(left, chunk);
+            compile_expr(right, chunk);
+            chunk.emit(binary_op_code(op), expr.span);
+        },
+        ExprKind::CompoundExpr { ref exprs } => {
+            //The result of every expression but the last is discarded.
+            for (i, e) in exprs.iter().enumerate() {
+                compile_expr(e, chunk);
+                if i < exprs.len() - 1 {
+                    chunk.emit(OpCode::Pop, e.span);
+                }
+            }
+        }
+    }
+}
+
+fn binary_op_code(op: &BinaryOp) -> OpCode {
+    match *op {
+        BinaryOp::Add => OpCode::Add,
+        BinaryOp::Sub => OpCode::Sub,
+        BinaryOp::Mul => OpCode::Mul,
+        BinaryOp::Div => OpCode::Div,
+        BinaryOp::Mod => OpCode::Mod,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use value::Value;
+
+    fn ops(chunk: &Chunk) -> Vec<OpCode> {
+        chunk.code.iter().map(|i| i.op).collect()
+    }
+
+    #[test]
+    fn compile_binary() {
+        let chunk = compile(&Expr::new_binary(
+            BinaryOp::Add,
+            Expr::new_literal(Value::Int32(1)),
+            Expr::new_variable_index(3)));
+
+        assert_eq!(vec![Value::Int32(1)], chunk.constants);
+        assert_eq!(vec![OpCode::Constant(0), OpCode::LoadGlobal(3), OpCode::Add], ops(&chunk));
+    }
+
+    #[test]
+    fn compile_reuses_constants() {
+        let chunk = compile(&Expr::new_binary(
+            BinaryOp::Mul,
+            Expr::new_literal(Value::Int32(2)),
+            Expr::new_literal(Value::Int32(2))));
+
+        assert_eq!(vec![Value::Int32(2)], chunk.constants);
+        assert_eq!(vec![OpCode::Constant(0), OpCode::Constant(0), OpCode::Mul], ops(&chunk));
+    }
+
+    #[test]
+    fn compile_compound_expr() {
+        let chunk = compile(&Expr::new_compound_expr(vec![
+            Box::new(Expr::new_literal(Value::Int32(1))),
+            Box::new(Expr::new_literal(Value::Int32(2)))]));
+
+        assert_eq!(vec![OpCode::Constant(0), OpCode::Pop, OpCode::Constant(1)], ops(&chunk));
+    }
+}
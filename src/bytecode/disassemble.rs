@@ -0,0 +1,54 @@
+
+use std::fmt::Write;
+use super::*;
+
+/// Renders `chunk` as human readable text:  the constant pool followed by one instruction per line,
+/// each prefixed with its index and the line & column of the source it was compiled from.
+pub fn disassemble(chunk: &Chunk) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "constants:").unwrap();
+    for (i, constant) in chunk.constants.iter().enumerate() {
+        writeln!(out, "    #{:<4} {:?}", i, constant).unwrap();
+    }
+
+    writeln!(out, "code:").unwrap();
+    for (i, instruction) in chunk.code.iter().enumerate() {
+        let loc = format!("{}:{}", instruction.span.start.line, instruction.span.start.col_offset);
+        let operand = match instruction.op {
+            OpCode::Constant(index) => match chunk.constants.get(index as usize) {
+                Some(value) => format!("#{} ({:?})", index, value),
+                None => format!("#{} (invalid)", index)
+            },
+            OpCode::LoadGlobal(index) => format!("{}", index),
+            _ => String::new()
+        };
+        writeln!(out, "    {:04}  {:<8} {:<12} {}", i, loc, instruction.op.mnemonic(), operand).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::*;
+
+    #[test]
+    fn disassemble_chunk() {
+        let mut chunk = Chunk::new();
+        let index = chunk.add_constant(Value::Int32(42));
+        chunk.emit(OpCode::Constant(index), Span::from_locations(Location::new(1, 1, 0), Location::new(1, 2, 1)));
+        chunk.emit(OpCode::LoadGlobal(0), Span::from_locations(Location::new(1, 6, 5), Location::new(1, 8, 7)));
+        chunk.emit(OpCode::Add, Span::from_locations(Location::new(1, 1, 0), Location::new(1, 8, 7)));
+
+        let lines: Vec<String> = disassemble(&chunk).lines().map(|l| l.trim_end().to_string()).collect();
+        assert_eq!(vec![
+            "constants:",
+            "    #0    Int32(42)",
+            "code:",
+            "    0000  1:1      CONSTANT     #0 (Int32(42))",
+            "    0001  1:6      LOAD_GLOBAL  0",
+            "    0002  1:1      ADD",
+        ], lines);
+    }
+}
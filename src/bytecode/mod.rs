@@ -0,0 +1,103 @@
+
+/*
+  A compact, stack based representation of a resolved `Expr`.  A `Chunk` can be executed by the VM,
+  written to disk with `serialize` and loaded back again with `load`.
+*/
+
+pub mod compile;
+pub mod vm;
+pub mod serialize;
+pub mod disassemble;
+
+use source::Span;
+use value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    /// Pushes the constant at the specified index of the constant pool.
+    Constant(u32),
+    /// Pushes the value of the global variable with the specified ordinal.
+    LoadGlobal(u32),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    /// Discards the value on top of the stack.
+    Pop,
+}
+
+impl OpCode {
+    /// The name of the op code as displayed by the disassembler.
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            OpCode::Constant(_) => "CONSTANT",
+            OpCode::LoadGlobal(_) => "LOAD_GLOBAL",
+            OpCode::Add => "ADD",
+            OpCode::Sub => "SUB",
+            OpCode::Mul => "MUL",
+            OpCode::Div => "DIV",
+            OpCode::Mod => "MOD",
+            OpCode::Pop => "POP",
+        }
+    }
+
+    /// The net change to the depth of the stack after executing the op code.
+    pub fn stack_effect(&self) -> i32 {
+        match *self {
+            OpCode::Constant(_) | OpCode::LoadGlobal(_) => 1,
+            OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Div | OpCode::Mod | OpCode::Pop => -1,
+        }
+    }
+
+    /// The number of values the op code pops from the stack.
+    pub fn stack_inputs(&self) -> u32 {
+        match *self {
+            OpCode::Constant(_) | OpCode::LoadGlobal(_) => 0,
+            OpCode::Pop => 1,
+            OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Div | OpCode::Mod => 2,
+        }
+    }
+}
+
+/// A single instruction along with the span of the source code it was compiled from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub op: OpCode,
+    pub span: Span
+}
+
+impl Instruction {
+    pub fn new(op: OpCode, span: Span) -> Instruction {
+        Instruction { op, span }
+    }
+}
+
+/// The output of the compiler:  a constant pool and a sequence of instructions which, when executed,
+/// leaves exactly one value on the stack.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Chunk {
+    pub constants: Vec<Value>,
+    pub code: Vec<Instruction>
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk { constants: Vec::new(), code: Vec::new() }
+    }
+
+    /// Adds `value` to the constant pool if it isn't already there and returns its index.
+    pub fn add_constant(&mut self, value: Value) -> u32 {
+        match self.constants.iter().position(|c| *c == value) {
+            Some(index) => index as u32,
+            None => {
+                self.constants.push(value);
+                (self.constants.len() - 1) as u32
+            }
+        }
+    }
+
+    pub fn emit(&mut self, op: OpCode, span: Span) {
+        self.code.push(Instruction::new(op, span));
+    }
+}
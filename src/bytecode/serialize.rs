@@ -0,0 +1,341 @@
+
+/*
+  The binary format of a serialized `Chunk`.  All integers are little endian.
+
+      magic           4 bytes, "CIRB"
+      version         u16
+      constant count  u32
+      constants       tag: u8 followed by the value (0x01 = Int32, an i32)
+      code length     u32
+      instructions    op code: u8, an u32 operand (CONSTANT and LOAD_GLOBAL only),
+                      followed by the span:  the line, column and byte offset of the start and then
+                      of the end (6 x u32).  The `FileId` is not stored, loaded spans have `FileId::NONE`.
+*/
+
+use std::fmt;
+use std::error::Error as StdError;
+use error::*;
+use source::*;
+use value::Value;
+use super::*;
+
+pub const MAGIC: &[u8; 4] = b"CIRB";
+pub const FORMAT_VERSION: u16 = 2;
+
+const TAG_INT32: u8 = 0x01;
+
+const OP_CONSTANT: u8 = 0x01;
+const OP_LOAD_GLOBAL: u8 = 0x02;
+const OP_ADD: u8 = 0x10;
+const OP_SUB: u8 = 0x11;
+const OP_MUL: u8 = 0x12;
+const OP_DIV: u8 = 0x13;
+const OP_MOD: u8 = 0x14;
+const OP_POP: u8 = 0x20;
+
+pub fn serialize(chunk: &Chunk) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    write_u16(&mut out, FORMAT_VERSION);
+
+    write_u32(&mut out, chunk.constants.len() as u32);
+    for constant in &chunk.constants {
+        match *constant {
+            Value::Int32(v) => {
+                out.push(TAG_INT32);
+                write_u32(&mut out, v as u32);
+            }
+        }
+    }
+
+    write_u32(&mut out, chunk.code.len() as u32);
+    for instruction in &chunk.code {
+        match instruction.op {
+            OpCode::Constant(index) => { out.push(OP_CONSTANT); write_u32(&mut out, index); },
+            OpCode::LoadGlobal(index) => { out.push(OP_LOAD_GLOBAL); write_u32(&mut out, index); },
+            OpCode::Add => out.push(OP_ADD),
+            OpCode::Sub => out.push(OP_SUB),
+            OpCode::Mul => out.push(OP_MUL),
+            OpCode::Div => out.push(OP_DIV),
+            OpCode::Mod => out.push(OP_MOD),
+            OpCode::Pop => out.push(OP_POP),
+        }
+        write_location(&mut out, &instruction.span.start);
+        write_location(&mut out, &instruction.span.end);
+    }
+    out
+}
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_location(out: &mut Vec<u8>, loc: &Location) {
+    write_u32(out, loc.line);
+    write_u32(out, loc.col_offset);
+    write_u32(out, loc.offset as u32);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadErrorKind {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEndOfData,
+    InvalidConstantTag(u8),
+    InvalidOpCode(u8),
+    ConstantIndexOutOfRange(u32),
+    StackUnderflow { instruction: usize },
+    InvalidFinalStackDepth(i32),
+    TrailingData(usize),
+}
+
+impl ErrorKind for LoadErrorKind {
+    fn message(&self) -> String {
+        match *self {
+            LoadErrorKind::InvalidMagic => String::from("Not a compiled bytecode file (invalid magic number)"),
+            LoadErrorKind::UnsupportedVersion(v) => format!("Unsupported bytecode version {} (expected {})", v, FORMAT_VERSION),
+            LoadErrorKind::UnexpectedEndOfData => String::from("Unexpected end of data"),
+            LoadErrorKind::InvalidConstantTag(tag) => format!("Invalid constant tag 0x{:02x}", tag),
+            LoadErrorKind::InvalidOpCode(op) => format!("Invalid op code 0x{:02x}", op),
+            LoadErrorKind::ConstantIndexOutOfRange(index) => format!("Constant index {} is out of range", index),
+            LoadErrorKind::StackUnderflow { instruction } => format!("Instruction {} would underflow the stack", instruction),
+            LoadErrorKind::InvalidFinalStackDepth(depth) => format!("Code leaves {} values on the stack instead of 1", depth),
+            LoadErrorKind::TrailingData(len) => format!("{} unexpected bytes after the end of the code", len),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            LoadErrorKind::InvalidMagic => "E0601",
+            LoadErrorKind::UnsupportedVersion(_) => "E0602",
+            LoadErrorKind::UnexpectedEndOfData => "E0603",
+            LoadErrorKind::InvalidConstantTag(_) => "E0604",
+            LoadErrorKind::InvalidOpCode(_) => "E0605",
+            LoadErrorKind::ConstantIndexOutOfRange(_) => "E0606",
+            LoadErrorKind::StackUnderflow { .. } => "E0607",
+            LoadErrorKind::InvalidFinalStackDepth(_) => "E0608",
+            LoadErrorKind::TrailingData(_) => "E0609",
+        }
+    }
+}
+
+impl fmt::Display for LoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ::std::error::Error for LoadErrorKind {}
+
+/// An error encountered while loading a serialized chunk.  `offset` is the position within the
+/// input at which the problem was detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+    pub kind: LoadErrorKind,
+    pub offset: usize
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte offset {}", self.kind.message(), self.offset)
+    }
+}
+
+impl StdError for LoadError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.kind)
+    }
+}
+
+pub type LoadResult = Result<Chunk, LoadError>;
+
+/// Loads a chunk previously written by `serialize`, verifying that it is well formed:
+/// every constant index is valid and the code never underflows the stack and leaves exactly
+/// one value on it, so a loaded chunk is always safe to hand to `vm::run`.
+pub fn load(bytes: &[u8]) -> LoadResult {
+    let mut reader = ByteReader { bytes, pos: 0 };
+
+    if reader.read_bytes(MAGIC.len())? != MAGIC {
+        return Err(LoadError { kind: LoadErrorKind::InvalidMagic, offset: 0 });
+    }
+    let version = reader.read_u16()?;
+    if version != FORMAT_VERSION {
+        return Err(reader.error_at(LoadErrorKind::UnsupportedVersion(version), reader.pos - 2));
+    }
+
+    let mut chunk = Chunk::new();
+    let constant_count = reader.read_u32()?;
+    for _ in 0..constant_count {
+        let tag = reader.read_u8()?;
+        match tag {
+            TAG_INT32 => chunk.constants.push(Value::Int32(reader.read_u32()? as i32)),
+            _ => return Err(reader.error_at(LoadErrorKind::InvalidConstantTag(tag), reader.pos - 1))
+        }
+    }
+
+    let code_len = reader.read_u32()?;
+    let mut depth: i32 = 0;
+    for i in 0..code_len as usize {
+        let op_offset = reader.pos;
+        let op = match reader.read_u8()? {
+            OP_CONSTANT => {
+                let index = reader.read_u32()?;
+                if index as usize >= chunk.constants.len() {
+                    return Err(reader.error_at(LoadErrorKind::ConstantIndexOutOfRange(index), op_offset));
+                }
+                OpCode::Constant(index)
+            },
+            OP_LOAD_GLOBAL => OpCode::LoadGlobal(reader.read_u32()?),
+            OP_ADD => OpCode::Add,
+            OP_SUB => OpCode::Sub,
+            OP_MUL => OpCode::Mul,
+            OP_DIV => OpCode::Div,
+            OP_MOD => OpCode::Mod,
+            OP_POP => OpCode::Pop,
+            other => return Err(reader.error_at(LoadErrorKind::InvalidOpCode(other), op_offset))
+        };
+        if depth < op.stack_inputs() as i32 {
+            return Err(reader.error_at(LoadErrorKind::StackUnderflow { instruction: i }, op_offset));
+        }
+        depth += op.stack_effect();
+
+        let start = reader.read_location()?;
+        let end = reader.read_location()?;
+        chunk.emit(op, Span::from_locations(start, end));
+    }
+
+    if depth != 1 {
+        return Err(reader.error_at(LoadErrorKind::InvalidFinalStackDepth(depth), reader.pos));
+    }
+    if reader.pos != bytes.len() {
+        return Err(reader.error_at(LoadErrorKind::TrailingData(bytes.len() - reader.pos), reader.pos));
+    }
+    Ok(chunk)
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl <'a> ByteReader<'a> {
+    fn error_at(&self, kind: LoadErrorKind, offset: usize) -> LoadError {
+        LoadError { kind, offset }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(self.error_at(LoadErrorKind::UnexpectedEndOfData, self.bytes.len()));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, LoadError> {
+        let b = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, LoadError> {
+        let b = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_location(&mut self) -> Result<Location, LoadError> {
+        let line = self.read_u32()?;
+        let col_offset = self.read_u32()?;
+        let offset = self.read_u32()? as usize;
+        Ok(Location { line, col_offset, offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytecode::compile::compile;
+    use ast::*;
+    use common::*;
+
+    fn sample_chunk() -> Chunk {
+        let mut chunk = compile(&Expr::new_compound_expr(vec![
+            Box::new(Expr::new_literal(Value::Int32(-5))),
+            Box::new(Expr::new_binary(BinaryOp::Mod, Expr::new_variable_index(1), Expr::new_literal(Value::Int32(3))))]));
+        chunk.code[0].span = Span::from_locations(Location::new(1, 1, 0), Location::new(1, 2, 1));
+        chunk
+    }
+
+    fn load_err(bytes: &[u8]) -> LoadErrorKind {
+        load(bytes).unwrap_err().kind
+    }
+
+    #[test]
+    fn round_trip() {
+        let chunk = sample_chunk();
+        assert_eq!(Ok(chunk.clone()), load(&serialize(&chunk)));
+    }
+
+    #[test]
+    fn header() {
+        let bytes = serialize(&sample_chunk());
+        assert_eq!(b"CIRB", &bytes[0..4]);
+        assert_eq!(&FORMAT_VERSION.to_le_bytes(), &bytes[4..6]);
+    }
+
+    #[test]
+    fn load_invalid_header() {
+        let mut bytes = serialize(&sample_chunk());
+        bytes[0] = b'X';
+        assert_eq!(LoadErrorKind::InvalidMagic, load_err(&bytes));
+
+        let mut bytes = serialize(&sample_chunk());
+        bytes[4] = 99;
+        assert_eq!(LoadError { kind: LoadErrorKind::UnsupportedVersion(99), offset: 4 }, load(&bytes).unwrap_err());
+
+        assert_eq!(LoadErrorKind::UnexpectedEndOfData, load_err(b"CI"));
+    }
+
+    #[test]
+    fn load_truncated() {
+        let bytes = serialize(&sample_chunk());
+        for len in 0..bytes.len() {
+            assert!(load(&bytes[..len]).is_err(), "Truncated to {} bytes should fail to load", len);
+        }
+    }
+
+    #[test]
+    fn load_trailing_data() {
+        let mut bytes = serialize(&sample_chunk());
+        bytes.push(0);
+        assert_eq!(LoadErrorKind::TrailingData(1), load_err(&bytes));
+    }
+
+    #[test]
+    fn load_invalid_code() {
+        let mut chunk = Chunk::new();
+        chunk.emit(OpCode::Constant(0), Span::unknown());
+        assert_eq!(LoadErrorKind::ConstantIndexOutOfRange(0), load_err(&serialize(&chunk)));
+
+        let mut chunk = Chunk::new();
+        chunk.emit(OpCode::Add, Span::unknown());
+        assert_eq!(LoadErrorKind::StackUnderflow { instruction: 0 }, load_err(&serialize(&chunk)));
+
+        let mut chunk = Chunk::new();
+        chunk.emit(OpCode::LoadGlobal(0), Span::unknown());
+        chunk.emit(OpCode::LoadGlobal(0), Span::unknown());
+        assert_eq!(LoadErrorKind::InvalidFinalStackDepth(2), load_err(&serialize(&chunk)));
+
+        // Constant pool of one Int32 with an invalid tag
+        let mut bytes = serialize(&sample_chunk());
+        bytes[10] = 0x7f;
+        assert_eq!(LoadError { kind: LoadErrorKind::InvalidConstantTag(0x7f), offset: 10 }, load(&bytes).unwrap_err());
+    }
+}
@@ -0,0 +1,80 @@
+
+use env::Env;
+use common::BinaryOp;
+use passes::evaluate::*;
+use super::*;
+
+/// Executes `chunk` against `env`, producing the same result (or error) as `passes::evaluate`
+/// would for the expression `chunk` was compiled from.
+pub fn run(chunk: &Chunk, env: &Env) -> EvaluateResult {
+    let mut stack: Vec<Value> = Vec::new();
+
+    for instruction in &chunk.code {
+        match instruction.op {
+            OpCode::Constant(index) => stack.push(chunk.constants[index as usize].clone()),
+            OpCode::LoadGlobal(index) => match env.get_by_index(index) {
+                Some(value) => stack.push(value.clone()),
+                None => return Err(EvaluateError::new_with_span(EvaluateErrorKind::IndexOutOfRange(index), instruction.span))
+            },
+            OpCode::Pop => { stack.pop(); },
+            OpCode::Add => binary(&mut stack, &BinaryOp::Add, instruction)?,
+            OpCode::Sub => binary(&mut stack, &BinaryOp::Sub, instruction)?,
+            OpCode::Mul => binary(&mut stack, &BinaryOp::Mul, instruction)?,
+            OpCode::Div => binary(&mut stack, &BinaryOp::Div, instruction)?,
+            OpCode::Mod => binary(&mut stack, &BinaryOp::Mod, instruction)?,
+        }
+    }
+
+    //Note: chunks produced by the compiler or accepted by the loader always leave exactly one value on the stack.
+    Ok(stack.pop().expect("Stack was empty after executing chunk"))
+}
+
+fn binary(stack: &mut Vec<Value>, op: &BinaryOp, instruction: &Instruction) -> Result<(), EvaluateError> {
+    let right = stack.pop().expect("Stack underflow");
+    let left = stack.pop().expect("Stack underflow");
+    let value = apply_binary_op(op, left, right)
+        .map_err(|kind| EvaluateError::new_with_span(kind, instruction.span))?;
+    stack.push(value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytecode::compile::compile;
+    use ast::*;
+    use env::*;
+
+    fn run_expr(expr: &Expr) -> EvaluateResult {
+        let env_def = EnvDefBuilder::new().with_item("foo", Value::Int32(7)).build();
+        let env = env_def.create_with_default_values();
+        run(&compile(expr), &env)
+    }
+
+    #[test]
+    fn run_binary() {
+        let expr = Expr::new_binary(
+            BinaryOp::Sub,
+            Expr::new_variable_index(0),
+            Expr::new_binary(BinaryOp::Mul, Expr::new_literal(Value::Int32(2)), Expr::new_literal(Value::Int32(3))));
+
+        assert_eq!(Ok(Value::Int32(1)), run_expr(&expr));
+    }
+
+    #[test]
+    fn run_compound_expr() {
+        let expr = Expr::new_compound_expr(vec![
+            Box::new(Expr::new_literal(Value::Int32(1))),
+            Box::new(Expr::new_variable_index(0))]);
+
+        assert_eq!(Ok(Value::Int32(7)), run_expr(&expr));
+    }
+
+    #[test]
+    fn run_errors() {
+        let div = Expr::new_binary(BinaryOp::Div, Expr::new_literal(Value::Int32(1)), Expr::new_literal(Value::Int32(0)));
+        assert_eq!(EvaluateErrorKind::DivisionByZero, run_expr(&div).unwrap_err().kind);
+
+        assert_eq!(EvaluateErrorKind::IndexOutOfRange(5), run_expr(&Expr::new_variable_index(5)).unwrap_err().kind);
+    }
+}
@@ -0,0 +1,206 @@
+
+/*
+  Translates a resolved `Expr` into a standalone C function.  For `COptions::function_name` "rule" the
+  generated code declares:
+
+      typedef struct { int32_t <global>; ... } rule_globals;
+      int rule(const rule_globals *globals, int32_t *result);
+
+  The members of `rule_globals` are in order of ordinal and named after the globals in the `EnvDef`.
+  `rule` returns `codegen::STATUS_OK` and stores the value of the expression in `*result`, or returns
+  the `codegen::status_code` of the error `passes::evaluate` would have reported.  All arithmetic is
+  explicitly checked so the generated code never invokes undefined behaviour.
+*/
+
+use std::fmt::Write;
+use ast::*;
+use common::*;
+use env::*;
+use value::Value;
+use passes::evaluate::EvaluateErrorKind;
+use codegen::*;
+
+#[derive(Debug, Clone)]
+pub struct COptions {
+    /// The name of the generated function.  Also used to prefix every other generated identifier.
+    pub function_name: String
+}
+
+impl Default for COptions {
+    fn default() -> COptions {
+        COptions { function_name: String::from("evaluate") }
+    }
+}
+
+const C_KEYWORDS: [&str; 44] = [
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+    "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "restrict", "return",
+    "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void",
+    "volatile", "while", "_Alignas", "_Alignof", "_Atomic", "_Bool", "_Complex", "_Generic",
+    "_Imaginary", "_Noreturn", "_Static_assert", "_Thread_local"];
+
+/// Returns the name of the struct member used for the global `name`.  Names which are C keywords
+/// have an underscore appended.
+pub fn member_name(name: &str) -> String {
+    if C_KEYWORDS.contains(&name) { format!("{}_", name) } else { String::from(name) }
+}
+
+/// Generates a C translation unit containing the function described in the module documentation.
+/// `expr` must have been resolved against `env_def`.
+pub fn compile(expr: &Expr, env_def: &EnvDef, options: &COptions) -> Result<String, CodegenError> {
+    let fields = env_def.fields();
+    let mut emitter = Emitter { fields: &fields, prefix: &options.function_name, body: String::new(), temp_count: 0, used_ops: Vec::new() };
+    let result = emitter.emit_expr(expr)?;
+    let name = emitter.prefix;
+
+    let mut out = String::new();
+    writeln!(out, "/* Generated by compiler_in_rust */").unwrap();
+    writeln!(out, "#include <stdint.h>").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "typedef struct {{").unwrap();
+    for field in &fields {
+        match field.default_value {
+            Value::Int32(_) => writeln!(out, "    int32_t {};", member_name(&field.name)).unwrap()
+        }
+    }
+    if fields.is_empty() {
+        //Empty structs are not allowed by ISO C.
+        writeln!(out, "    char unused;").unwrap();
+    }
+    writeln!(out, "}} {}_globals;", name).unwrap();
+    writeln!(out).unwrap();
+
+    for op in &emitter.used_ops {
+        emit_helper(op, name, &mut out);
+    }
+
+    writeln!(out, "int {}(const {}_globals *globals, int32_t *result) {{", name, name).unwrap();
+    if emitter.temp_count > 0 {
+        writeln!(out, "    int status;").unwrap();
+    }
+    out.push_str(&emitter.body);
+    writeln!(out, "    (void)globals;").unwrap();
+    writeln!(out, "    *result = {};", result).unwrap();
+    writeln!(out, "    return {};", STATUS_OK).unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+fn op_name(op: &BinaryOp) -> &'static str {
+    match *op {
+        BinaryOp::Add => "add",
+        BinaryOp::Sub => "sub",
+        BinaryOp::Mul => "mul",
+        BinaryOp::Div => "div",
+        BinaryOp::Mod => "mod",
+    }
+}
+
+fn int32_literal(value: i32) -> String {
+    //-2147483648 is the negation of a constant which does not fit in an int.
+    if value == i32::MIN { String::from("(-2147483647 - 1)") } else { format!("{}", value) }
+}
+
+/// Emits a `static` function which applies `op` to two `int32_t`s, returning a status code.
+fn emit_helper(op: &BinaryOp, prefix: &str, out: &mut String) {
+    let overflow = status_code(&EvaluateErrorKind::IntegerOverflow(op.clone()));
+    writeln!(out, "static int {}_{}(int32_t l, int32_t r, int32_t *out) {{", prefix, op_name(op)).unwrap();
+    match *op {
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => {
+            let c_op = match *op { BinaryOp::Add => "+", BinaryOp::Sub => "-", _ => "*" };
+            writeln!(out, "    int64_t wide = (int64_t)l {} (int64_t)r;", c_op).unwrap();
+            writeln!(out, "    if (wide < INT32_MIN || wide > INT32_MAX) return {};", overflow).unwrap();
+            writeln!(out, "    *out = (int32_t)wide;").unwrap();
+        },
+        BinaryOp::Div | BinaryOp::Mod => {
+            let c_op = if *op == BinaryOp::Div { "/" } else { "%" };
+            writeln!(out, "    if (r == 0) return {};", status_code(&EvaluateErrorKind::DivisionByZero)).unwrap();
+            writeln!(out, "    if (l == INT32_MIN && r == -1) return {};", overflow).unwrap();
+            writeln!(out, "    *out = l {} r;", c_op).unwrap();
+        }
+    }
+    writeln!(out, "    return {};", STATUS_OK).unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+}
+
+struct Emitter<'a> {
+    fields: &'a [&'a EnvField],
+    prefix: &'a str,
+    body: String,
+    temp_count: u32,
+    /// The operators for which a helper function must be emitted.
+    used_ops: Vec<BinaryOp>
+}
+
+impl <'a> Emitter<'a> {
+    /// Emits the statements which evaluate `expr` and returns the C expression holding its value.
+    fn emit_expr(&mut self, expr: &Expr) -> Result<String, CodegenError> {
+        match expr.kind {
+            ExprKind::Literal { value: Value::Int32(v) } => Ok(int32_literal(v)),
+            ExprKind::VariableIndex { index } => match self.fields.get(index as usize) {
+                Some(field) => Ok(format!("globals->{}", member_name(&field.name))),
+                None => Err(CodegenError::new_with_span(CodegenErrorKind::InvalidGlobalIndex(index), expr.span))
+            },
+            ExprKind::VariableRef { ref name } =>
+                Err(CodegenError::new_with_span(CodegenErrorKind::UnresolvedVariable(name.clone()), expr.span)),
+            ExprKind::Error => Err(CodegenError::new_with_span(CodegenErrorKind::SyntaxError, expr.span)),
+            ExprKind::Binary { ref op, ref left, ref right } => {
+                let l = self.emit_expr(left)?;
+                let r = self.emit_expr(right)?;
+                if !self.used_ops.contains(op) {
+                    self.used_ops.push(op.clone());
+                }
+                let temp = format!("t{}", self.temp_count);
+                self.temp_count += 1;
+                writeln!(self.body, "    int32_t {};", temp).unwrap();
+                writeln!(self.body, "    status = {}_{}({}, {}, &{});", self.prefix, op_name(op), l, r, temp).unwrap();
+                writeln!(self.body, "    if (status != {}) return status;", STATUS_OK).unwrap();
+                Ok(temp)
+            },
+            ExprKind::CompoundExpr { ref exprs } => {
+                //Every expression is evaluated for its errors but only the value of the last is kept.
+                let mut last = String::new();
+                for e in exprs {
+                    last = self.emit_expr(e)?;
+                }
+                Ok(last)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_binary() {
+        let env_def = EnvDefBuilder::new().with_item("foo", Value::Int32(1)).with_item("int", Value::Int32(2)).build();
+        let expr = Expr::new_binary(BinaryOp::Add, Expr::new_variable_index(0), Expr::new_variable_index(1));
+        let options = COptions { function_name: String::from("rule") };
+        let source = compile(&expr, &env_def, &options).unwrap();
+
+        assert!(source.contains("    int32_t foo;\n    int32_t int_;\n} rule_globals;"));
+        assert!(source.contains("int rule(const rule_globals *globals, int32_t *result) {"));
+        assert!(source.contains("    status = rule_add(globals->foo, globals->int_, &t0);\n"));
+        assert!(source.contains("    *result = t0;\n"));
+    }
+
+    #[test]
+    fn compile_literal() {
+        let env_def = EnvDefBuilder::new().build();
+        let source = compile(&Expr::new_literal(Value::Int32(i32::MIN)), &env_def, &COptions::default()).unwrap();
+
+        assert!(source.contains("    char unused;\n} evaluate_globals;"));
+        assert!(source.contains("    *result = (-2147483647 - 1);\n"));
+    }
+
+    #[test]
+    fn compile_errors() {
+        let env_def = EnvDefBuilder::new().build();
+        let result = compile(&Expr::new_variable_index(0), &env_def, &COptions::default());
+        assert_eq!(CodegenErrorKind::InvalidGlobalIndex(0), result.unwrap_err().kind);
+    }
+}
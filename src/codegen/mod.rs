@@ -0,0 +1,113 @@
+
+/*
+  Backends which translate a resolved `Expr` into something other than an in-memory value.
+
+  Compiled code cannot return a `SourceError`, so every backend reports evaluation errors as an
+  integer status code instead.  The codes are shared by all backends so that callers can map them
+  back to the `EvaluateErrorKind` that `passes::evaluate` would have produced.
+*/
+
+pub mod wasm;
+pub mod c;
+pub mod x86_64;
+
+use std::fmt;
+use common::*;
+use error::*;
+use passes::evaluate::EvaluateErrorKind;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodegenErrorKind {
+    /// The `resolve_variables` pass was not executed against the expression.
+    UnresolvedVariable(String),
+    /// The expression refers to a global that does not exist in the `EnvDef` given to the backend.
+    InvalidGlobalIndex(u32),
+    /// The expression contains an `ExprKind::Error` node left by the parser.
+    SyntaxError,
+}
+
+impl ErrorKind for CodegenErrorKind {
+    fn message(&self) -> String {
+        match *self {
+            CodegenErrorKind::UnresolvedVariable(ref name) => format!("Unresolved variable reference '{}'", name),
+            CodegenErrorKind::InvalidGlobalIndex(index) => format!("Global variable index {} does not exist", index),
+            CodegenErrorKind::SyntaxError => String::from("Cannot generate code for an expression with syntax errors"),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            CodegenErrorKind::UnresolvedVariable(_) => "E0501",
+            CodegenErrorKind::InvalidGlobalIndex(_) => "E0502",
+            CodegenErrorKind::SyntaxError => "E0503",
+        }
+    }
+}
+
+impl fmt::Display for CodegenErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ::std::error::Error for CodegenErrorKind {}
+
+pub type CodegenError = SourceError<CodegenErrorKind>;
+
+/// The status code indicating that evaluation succeeded.
+pub const STATUS_OK: i32 = 0;
+
+/// Returns the status code compiled code uses to report `kind`.
+pub fn status_code(kind: &EvaluateErrorKind) -> i32 {
+    match *kind {
+        EvaluateErrorKind::IndexOutOfRange(_) => 1,
+        EvaluateErrorKind::DivisionByZero => 2,
+        EvaluateErrorKind::IntegerOverflow(ref op) => 3 + binary_op_index(op),
+    }
+}
+
+/// The inverse of `status_code`.  Returns `None` for `STATUS_OK` and unknown codes.
+/// Note that the index of an `IndexOutOfRange` error is not preserved.
+pub fn status_error(code: i32) -> Option<EvaluateErrorKind> {
+    match code {
+        1 => Some(EvaluateErrorKind::IndexOutOfRange(0)),
+        2 => Some(EvaluateErrorKind::DivisionByZero),
+        3 => Some(EvaluateErrorKind::IntegerOverflow(BinaryOp::Add)),
+        4 => Some(EvaluateErrorKind::IntegerOverflow(BinaryOp::Sub)),
+        5 => Some(EvaluateErrorKind::IntegerOverflow(BinaryOp::Mul)),
+        6 => Some(EvaluateErrorKind::IntegerOverflow(BinaryOp::Div)),
+        7 => Some(EvaluateErrorKind::IntegerOverflow(BinaryOp::Mod)),
+        _ => None
+    }
+}
+
+fn binary_op_index(op: &BinaryOp) -> i32 {
+    match *op {
+        BinaryOp::Add => 0,
+        BinaryOp::Sub => 1,
+        BinaryOp::Mul => 2,
+        BinaryOp::Div => 3,
+        BinaryOp::Mod => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_codes_round_trip() {
+        let kinds = vec![
+            EvaluateErrorKind::DivisionByZero,
+            EvaluateErrorKind::IntegerOverflow(BinaryOp::Add),
+            EvaluateErrorKind::IntegerOverflow(BinaryOp::Sub),
+            EvaluateErrorKind::IntegerOverflow(BinaryOp::Mul),
+            EvaluateErrorKind::IntegerOverflow(BinaryOp::Div),
+            EvaluateErrorKind::IntegerOverflow(BinaryOp::Mod)];
+
+        for kind in kinds {
+            assert_eq!(Some(kind.clone()), status_error(status_code(&kind)));
+        }
+        assert_eq!(None, status_error(STATUS_OK));
+    }
+}
@@ -0,0 +1,452 @@
+
+use std::fmt;
+use std::error::Error as StdError;
+use error::*;
+use super::module::*;
+use super::module::opcodes::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeErrorKind {
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    UnexpectedEndOfData,
+    InvalidLeb128,
+    InvalidUtf8,
+    UnsupportedSection(u8),
+    SectionOutOfOrder(u8),
+    SectionSizeMismatch(u8),
+    InvalidValueType(u8),
+    InvalidFunctionType(u8),
+    UnsupportedImportKind(u8),
+    UnsupportedExportKind(u8),
+    UnsupportedBlockType(u8),
+    InvalidMutability(u8),
+    UnsupportedOpCode(u8),
+    FunctionCountMismatch { functions: u32, bodies: u32 },
+    TooManyLocals,
+}
+
+impl ErrorKind for DecodeErrorKind {
+    fn message(&self) -> String {
+        match *self {
+            DecodeErrorKind::InvalidMagic => String::from("Not a WebAssembly module (invalid magic number)"),
+            DecodeErrorKind::UnsupportedVersion(v) => format!("Unsupported WebAssembly version {}", v),
+            DecodeErrorKind::UnexpectedEndOfData => String::from("Unexpected end of data"),
+            DecodeErrorKind::InvalidLeb128 => String::from("Invalid LEB128 integer"),
+            DecodeErrorKind::InvalidUtf8 => String::from("Name is not valid UTF-8"),
+            DecodeErrorKind::UnsupportedSection(id) => format!("Unsupported section id {}", id),
+            DecodeErrorKind::SectionOutOfOrder(id) => format!("Section id {} is out of order", id),
+            DecodeErrorKind::SectionSizeMismatch(id) => format!("Contents of section id {} do not match its declared size", id),
+            DecodeErrorKind::InvalidValueType(t) => format!("Invalid value type 0x{:02x}", t),
+            DecodeErrorKind::InvalidFunctionType(t) => format!("Invalid function type 0x{:02x}", t),
+            DecodeErrorKind::UnsupportedImportKind(k) => format!("Unsupported import kind 0x{:02x}", k),
+            DecodeErrorKind::UnsupportedExportKind(k) => format!("Unsupported export kind 0x{:02x}", k),
+            DecodeErrorKind::UnsupportedBlockType(t) => format!("Unsupported block type 0x{:02x}", t),
+            DecodeErrorKind::InvalidMutability(m) => format!("Invalid global mutability 0x{:02x}", m),
+            DecodeErrorKind::UnsupportedOpCode(op) => format!("Unsupported op code 0x{:02x}", op),
+            DecodeErrorKind::FunctionCountMismatch { functions, bodies } =>
+                format!("{} functions were declared but {} bodies were found", functions, bodies),
+            DecodeErrorKind::TooManyLocals => String::from("Too many locals"),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            DecodeErrorKind::InvalidMagic => "E0701",
+            DecodeErrorKind::UnsupportedVersion(_) => "E0702",
+            DecodeErrorKind::UnexpectedEndOfData => "E0703",
+            DecodeErrorKind::InvalidLeb128 => "E0704",
+            DecodeErrorKind::InvalidUtf8 => "E0705",
+            DecodeErrorKind::UnsupportedSection(_) => "E0706",
+            DecodeErrorKind::SectionOutOfOrder(_) => "E0707",
+            DecodeErrorKind::SectionSizeMismatch(_) => "E0708",
+            DecodeErrorKind::InvalidValueType(_) => "E0709",
+            DecodeErrorKind::InvalidFunctionType(_) => "E0710",
+            DecodeErrorKind::UnsupportedImportKind(_) => "E0711",
+            DecodeErrorKind::UnsupportedExportKind(_) => "E0712",
+            DecodeErrorKind::UnsupportedBlockType(_) => "E0713",
+            DecodeErrorKind::InvalidMutability(_) => "E0714",
+            DecodeErrorKind::UnsupportedOpCode(_) => "E0715",
+            DecodeErrorKind::FunctionCountMismatch { .. } => "E0716",
+            DecodeErrorKind::TooManyLocals => "E0717",
+        }
+    }
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ::std::error::Error for DecodeErrorKind {}
+
+/// An error encountered while decoding a module.  `offset` is the position within the input at which
+/// the problem was detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    pub kind: DecodeErrorKind,
+    pub offset: usize
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte offset {}", self.kind.message(), self.offset)
+    }
+}
+
+impl StdError for DecodeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.kind)
+    }
+}
+
+pub type DecodeResult<T> = Result<T, DecodeError>;
+
+/// Decodes a module in the subset of the WebAssembly binary format produced by `encode`.
+/// Custom sections are skipped.
+pub fn decode(bytes: &[u8]) -> DecodeResult<Module> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.read_bytes(4)? != MAGIC {
+        return Err(DecodeError { kind: DecodeErrorKind::InvalidMagic, offset: 0 });
+    }
+    let version = reader.read_fixed_u32()?;
+    if version != VERSION {
+        return Err(reader.error_at(DecodeErrorKind::UnsupportedVersion(version), 4));
+    }
+
+    let mut module = Module::new();
+    let mut function_types: Vec<u32> = Vec::new();
+    let mut last_section = 0;
+
+    while reader.pos < bytes.len() {
+        let id_offset = reader.pos;
+        let id = reader.read_u8()?;
+        let size = reader.read_u32()? as usize;
+        let end = reader.pos.checked_add(size).filter(|end| *end <= bytes.len())
+            .ok_or_else(|| reader.error_at(DecodeErrorKind::UnexpectedEndOfData, bytes.len()))?;
+
+        if id == SECTION_CUSTOM {
+            reader.pos = end;
+            continue;
+        }
+        if id <= last_section {
+            return Err(reader.error_at(DecodeErrorKind::SectionOutOfOrder(id), id_offset));
+        }
+        last_section = id;
+
+        match id {
+            SECTION_TYPE => module.types = reader.read_vec(|r| r.read_func_type())?,
+            SECTION_IMPORT => module.imports = reader.read_vec(|r| r.read_import())?,
+            SECTION_FUNCTION => function_types = reader.read_vec(|r| r.read_u32())?,
+            SECTION_GLOBAL => module.globals = reader.read_vec(|r| r.read_global())?,
+            SECTION_EXPORT => module.exports = reader.read_vec(|r| r.read_export())?,
+            SECTION_CODE => {
+                let bodies_offset = reader.pos;
+                let bodies = reader.read_vec(|r| r.read_code())?;
+                if bodies.len() != function_types.len() {
+                    return Err(reader.error_at(
+                        DecodeErrorKind::FunctionCountMismatch { functions: function_types.len() as u32, bodies: bodies.len() as u32 },
+                        bodies_offset));
+                }
+                module.functions = function_types.iter().zip(bodies)
+                    .map(|(type_index, (locals, body))| Function { type_index: *type_index, locals, body })
+                    .collect();
+            }
+            _ => return Err(reader.error_at(DecodeErrorKind::UnsupportedSection(id), id_offset))
+        }
+
+        if reader.pos != end {
+            return Err(reader.error_at(DecodeErrorKind::SectionSizeMismatch(id), reader.pos));
+        }
+    }
+
+    if !function_types.is_empty() && module.functions.is_empty() {
+        return Err(reader.error_at(
+            DecodeErrorKind::FunctionCountMismatch { functions: function_types.len() as u32, bodies: 0 },
+            bytes.len()));
+    }
+    Ok(module)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl <'a> Reader<'a> {
+    fn error_at(&self, kind: DecodeErrorKind, offset: usize) -> DecodeError {
+        DecodeError { kind, offset }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> DecodeResult<&'a [u8]> {
+        if self.bytes.len() - self.pos < len {
+            return Err(self.error_at(DecodeErrorKind::UnexpectedEndOfData, self.bytes.len()));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> DecodeResult<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_fixed_u32(&mut self) -> DecodeResult<u32> {
+        let b = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Unsigned LEB128
+    fn read_u32(&mut self) -> DecodeResult<u32> {
+        let start = self.pos;
+        let mut result: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            result |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift >= 35 {
+                return Err(self.error_at(DecodeErrorKind::InvalidLeb128, start));
+            }
+        }
+        if result > u32::MAX as u64 {
+            return Err(self.error_at(DecodeErrorKind::InvalidLeb128, start));
+        }
+        Ok(result as u32)
+    }
+
+    /// Signed LEB128 of at most `bits` bits.
+    fn read_signed(&mut self, bits: u32) -> DecodeResult<i64> {
+        let start = self.pos;
+        let mut result: i64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift < 64 {
+                result |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1i64 << shift;
+                }
+                break;
+            }
+            if shift >= bits + 7 {
+                return Err(self.error_at(DecodeErrorKind::InvalidLeb128, start));
+            }
+        }
+        if bits < 64 && (result < -(1i64 << (bits - 1)) || result >= 1i64 << (bits - 1)) {
+            return Err(self.error_at(DecodeErrorKind::InvalidLeb128, start));
+        }
+        Ok(result)
+    }
+
+    fn read_vec<T, F: Fn(&mut Reader<'a>) -> DecodeResult<T>>(&mut self, read_item: F) -> DecodeResult<Vec<T>> {
+        let count = self.read_u32()?;
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(read_item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn read_name(&mut self) -> DecodeResult<String> {
+        let start = self.pos;
+        let len = self.read_u32()? as usize;
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.error_at(DecodeErrorKind::InvalidUtf8, start))
+    }
+
+    fn read_val_type(&mut self) -> DecodeResult<ValType> {
+        match self.read_u8()? {
+            TYPE_I32 => Ok(ValType::I32),
+            TYPE_I64 => Ok(ValType::I64),
+            other => Err(self.error_at(DecodeErrorKind::InvalidValueType(other), self.pos - 1))
+        }
+    }
+
+    fn read_func_type(&mut self) -> DecodeResult<FuncType> {
+        let form = self.read_u8()?;
+        if form != TYPE_FUNC {
+            return Err(self.error_at(DecodeErrorKind::InvalidFunctionType(form), self.pos - 1));
+        }
+        let params = self.read_vec(|r| r.read_val_type())?;
+        let results = self.read_vec(|r| r.read_val_type())?;
+        Ok(FuncType { params, results })
+    }
+
+    fn read_global_type(&mut self) -> DecodeResult<GlobalType> {
+        let val_type = self.read_val_type()?;
+        let mutable = match self.read_u8()? {
+            0 => false,
+            1 => true,
+            other => return Err(self.error_at(DecodeErrorKind::InvalidMutability(other), self.pos - 1))
+        };
+        Ok(GlobalType { val_type, mutable })
+    }
+
+    fn read_import(&mut self) -> DecodeResult<Import> {
+        let module = self.read_name()?;
+        let name = self.read_name()?;
+        let kind = self.read_u8()?;
+        if kind != IMPORT_GLOBAL {
+            return Err(self.error_at(DecodeErrorKind::UnsupportedImportKind(kind), self.pos - 1));
+        }
+        let global_type = self.read_global_type()?;
+        Ok(Import { module, name, global_type })
+    }
+
+    fn read_global(&mut self) -> DecodeResult<Global> {
+        let global_type = self.read_global_type()?;
+        let mut init = self.read_instrs()?;
+        //Remove the terminating `end` to match the representation in `Global`.
+        init.pop();
+        Ok(Global { global_type, init })
+    }
+
+    fn read_export(&mut self) -> DecodeResult<Export> {
+        let name = self.read_name()?;
+        let kind = self.read_u8()?;
+        let index = self.read_u32()?;
+        let desc = match kind {
+            EXPORT_FUNC => ExportDesc::Func(index),
+            EXPORT_GLOBAL => ExportDesc::Global(index),
+            other => return Err(self.error_at(DecodeErrorKind::UnsupportedExportKind(other), self.pos - 1))
+        };
+        Ok(Export { name, desc })
+    }
+
+    fn read_code(&mut self) -> DecodeResult<(Vec<ValType>, Vec<Instr>)> {
+        let size = self.read_u32()? as usize;
+        let end = self.pos + size;
+
+        let mut locals = Vec::new();
+        let runs = self.read_u32()?;
+        for _ in 0..runs {
+            let start = self.pos;
+            let count = self.read_u32()?;
+            let t = self.read_val_type()?;
+            if locals.len() + count as usize > 50000 {
+                return Err(self.error_at(DecodeErrorKind::TooManyLocals, start));
+            }
+            locals.extend((0..count).map(|_| t));
+        }
+        let body = self.read_instrs()?;
+        if self.pos != end {
+            return Err(self.error_at(DecodeErrorKind::SectionSizeMismatch(SECTION_CODE), self.pos));
+        }
+        Ok((locals, body))
+    }
+
+    /// Reads instructions up to and including the `end` which terminates the current expression.
+    fn read_instrs(&mut self) -> DecodeResult<Vec<Instr>> {
+        let mut instrs = Vec::new();
+        let mut depth = 0;
+        loop {
+            let op_offset = self.pos;
+            let instr = match self.read_u8()? {
+                UNREACHABLE => Instr::Unreachable,
+                IF => {
+                    let block_type = self.read_u8()?;
+                    if block_type != BLOCK_TYPE_EMPTY {
+                        return Err(self.error_at(DecodeErrorKind::UnsupportedBlockType(block_type), self.pos - 1));
+                    }
+                    depth += 1;
+                    Instr::If
+                },
+                END => Instr::End,
+                RETURN => Instr::Return,
+                CALL => Instr::Call(self.read_u32()?),
+                DROP => Instr::Drop,
+                LOCAL_GET => Instr::LocalGet(self.read_u32()?),
+                LOCAL_SET => Instr::LocalSet(self.read_u32()?),
+                GLOBAL_GET => Instr::GlobalGet(self.read_u32()?),
+                GLOBAL_SET => Instr::GlobalSet(self.read_u32()?),
+                I32_CONST => Instr::I32Const(self.read_signed(32)? as i32),
+                I64_CONST => Instr::I64Const(self.read_signed(64)?),
+                I32_EQZ => Instr::I32Eqz,
+                I32_EQ => Instr::I32Eq,
+                I64_NE => Instr::I64Ne,
+                I32_ADD => Instr::I32Add,
+                I32_SUB => Instr::I32Sub,
+                I32_MUL => Instr::I32Mul,
+                I32_DIV_S => Instr::I32DivS,
+                I32_REM_S => Instr::I32RemS,
+                I32_AND => Instr::I32And,
+                I64_ADD => Instr::I64Add,
+                I64_SUB => Instr::I64Sub,
+                I64_MUL => Instr::I64Mul,
+                I32_WRAP_I64 => Instr::I32WrapI64,
+                I64_EXTEND_I32_S => Instr::I64ExtendI32S,
+                other => return Err(self.error_at(DecodeErrorKind::UnsupportedOpCode(other), op_offset))
+            };
+            instrs.push(instr);
+            if instr == Instr::End {
+                if depth == 0 {
+                    return Ok(instrs);
+                }
+                depth -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codegen::wasm::encode::*;
+
+    #[test]
+    fn decode_header_errors() {
+        assert_eq!(DecodeErrorKind::InvalidMagic, decode(b"\0asn\x01\0\0\0").unwrap_err().kind);
+        assert_eq!(DecodeErrorKind::UnsupportedVersion(2), decode(b"\0asm\x02\0\0\0").unwrap_err().kind);
+        assert_eq!(DecodeErrorKind::UnexpectedEndOfData, decode(b"\0as").unwrap_err().kind);
+    }
+
+    #[test]
+    fn decode_signed_leb128() {
+        for value in vec![0, 1, -1, 63, 64, -64, -65, i32::MAX as i64, i32::MIN as i64, i64::MAX, i64::MIN] {
+            let mut bytes = Vec::new();
+            write_i64(&mut bytes, value);
+            let mut reader = Reader { bytes: &bytes, pos: 0 };
+            assert_eq!(value, reader.read_signed(64).unwrap());
+        }
+
+        let mut bytes = Vec::new();
+        write_i64(&mut bytes, i32::MAX as i64 + 1);
+        let mut reader = Reader { bytes: &bytes, pos: 0 };
+        assert_eq!(DecodeErrorKind::InvalidLeb128, reader.read_signed(32).unwrap_err().kind);
+    }
+
+    #[test]
+    fn decode_skips_custom_sections() {
+        let mut bytes = encode(&Module::new());
+        bytes.extend_from_slice(&[SECTION_CUSTOM, 3, 1, b'x', 0xff]);
+        assert_eq!(Ok(Module::new()), decode(&bytes));
+    }
+
+    #[test]
+    fn decode_section_errors() {
+        let mut bytes = encode(&Module::new());
+        bytes.extend_from_slice(&[SECTION_TYPE, 1, 0, SECTION_TYPE, 1, 0]);
+        assert_eq!(DecodeErrorKind::SectionOutOfOrder(SECTION_TYPE), decode(&bytes).unwrap_err().kind);
+
+        let mut bytes = encode(&Module::new());
+        bytes.extend_from_slice(&[SECTION_TYPE, 2, 0, 0]);
+        assert_eq!(DecodeErrorKind::SectionSizeMismatch(SECTION_TYPE), decode(&bytes).unwrap_err().kind);
+
+        let mut bytes = encode(&Module::new());
+        bytes.extend_from_slice(&[SECTION_FUNCTION, 2, 1, 0]);
+        assert_eq!(DecodeErrorKind::FunctionCountMismatch { functions: 1, bodies: 0 }, decode(&bytes).unwrap_err().kind);
+
+        let mut bytes = encode(&Module::new());
+        bytes.extend_from_slice(&[12, 1, 0]);
+        assert_eq!(DecodeErrorKind::UnsupportedSection(12), decode(&bytes).unwrap_err().kind);
+    }
+}
@@ -0,0 +1,211 @@
+
+use super::module::*;
+use super::module::opcodes::*;
+
+/// Encodes `module` in the WebAssembly binary format.
+pub fn encode(module: &Module) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+
+    if !module.types.is_empty() {
+        write_section(&mut out, SECTION_TYPE, module.types.len(), |buf| {
+            for t in &module.types {
+                buf.push(TYPE_FUNC);
+                write_val_types(buf, &t.params);
+                write_val_types(buf, &t.results);
+            }
+        });
+    }
+    if !module.imports.is_empty() {
+        write_section(&mut out, SECTION_IMPORT, module.imports.len(), |buf| {
+            for import in &module.imports {
+                write_name(buf, &import.module);
+                write_name(buf, &import.name);
+                buf.push(IMPORT_GLOBAL);
+                write_global_type(buf, &import.global_type);
+            }
+        });
+    }
+    if !module.functions.is_empty() {
+        write_section(&mut out, SECTION_FUNCTION, module.functions.len(), |buf| {
+            for f in &module.functions {
+                write_u32(buf, f.type_index);
+            }
+        });
+    }
+    if !module.globals.is_empty() {
+        write_section(&mut out, SECTION_GLOBAL, module.globals.len(), |buf| {
+            for g in &module.globals {
+                write_global_type(buf, &g.global_type);
+                write_instrs(buf, &g.init);
+                buf.push(END);
+            }
+        });
+    }
+    if !module.exports.is_empty() {
+        write_section(&mut out, SECTION_EXPORT, module.exports.len(), |buf| {
+            for e in &module.exports {
+                write_name(buf, &e.name);
+                match e.desc {
+                    ExportDesc::Func(index) => { buf.push(EXPORT_FUNC); write_u32(buf, index); },
+                    ExportDesc::Global(index) => { buf.push(EXPORT_GLOBAL); write_u32(buf, index); },
+                }
+            }
+        });
+    }
+    if !module.functions.is_empty() {
+        write_section(&mut out, SECTION_CODE, module.functions.len(), |buf| {
+            for f in &module.functions {
+                let mut body = Vec::new();
+                write_locals(&mut body, &f.locals);
+                write_instrs(&mut body, &f.body);
+                write_u32(buf, body.len() as u32);
+                buf.extend_from_slice(&body);
+            }
+        });
+    }
+    out
+}
+
+/// Writes a section consisting of a vector of `count` items written by `write_items`.
+fn write_section<F: FnOnce(&mut Vec<u8>)>(out: &mut Vec<u8>, id: u8, count: usize, write_items: F) {
+    let mut content = Vec::new();
+    write_u32(&mut content, count as u32);
+    write_items(&mut content);
+
+    out.push(id);
+    write_u32(out, content.len() as u32);
+    out.extend_from_slice(&content);
+}
+
+/// Locals are encoded as runs of (count, type).
+fn write_locals(out: &mut Vec<u8>, locals: &[ValType]) {
+    let mut runs: Vec<(u32, ValType)> = Vec::new();
+    for local in locals {
+        match runs.last_mut() {
+            Some(&mut (ref mut count, ref t)) if t == local => *count += 1,
+            _ => runs.push((1, *local))
+        }
+    }
+    write_u32(out, runs.len() as u32);
+    for (count, t) in runs {
+        write_u32(out, count);
+        write_val_type(out, t);
+    }
+}
+
+fn write_instrs(out: &mut Vec<u8>, instrs: &[Instr]) {
+    for instr in instrs {
+        match *instr {
+            Instr::Unreachable => out.push(UNREACHABLE),
+            Instr::If => { out.push(IF); out.push(BLOCK_TYPE_EMPTY); },
+            Instr::End => out.push(END),
+            Instr::Return => out.push(RETURN),
+            Instr::Call(index) => { out.push(CALL); write_u32(out, index); },
+            Instr::Drop => out.push(DROP),
+            Instr::LocalGet(index) => { out.push(LOCAL_GET); write_u32(out, index); },
+            Instr::LocalSet(index) => { out.push(LOCAL_SET); write_u32(out, index); },
+            Instr::GlobalGet(index) => { out.push(GLOBAL_GET); write_u32(out, index); },
+            Instr::GlobalSet(index) => { out.push(GLOBAL_SET); write_u32(out, index); },
+            Instr::I32Const(value) => { out.push(I32_CONST); write_i64(out, value as i64); },
+            Instr::I64Const(value) => { out.push(I64_CONST); write_i64(out, value); },
+            Instr::I32Eqz => out.push(I32_EQZ),
+            Instr::I32Eq => out.push(I32_EQ),
+            Instr::I64Ne => out.push(I64_NE),
+            Instr::I32Add => out.push(I32_ADD),
+            Instr::I32Sub => out.push(I32_SUB),
+            Instr::I32Mul => out.push(I32_MUL),
+            Instr::I32DivS => out.push(I32_DIV_S),
+            Instr::I32RemS => out.push(I32_REM_S),
+            Instr::I32And => out.push(I32_AND),
+            Instr::I64Add => out.push(I64_ADD),
+            Instr::I64Sub => out.push(I64_SUB),
+            Instr::I64Mul => out.push(I64_MUL),
+            Instr::I32WrapI64 => out.push(I32_WRAP_I64),
+            Instr::I64ExtendI32S => out.push(I64_EXTEND_I32_S),
+        }
+    }
+}
+
+fn write_val_types(out: &mut Vec<u8>, types: &[ValType]) {
+    write_u32(out, types.len() as u32);
+    for t in types {
+        write_val_type(out, *t);
+    }
+}
+
+fn write_val_type(out: &mut Vec<u8>, t: ValType) {
+    out.push(match t {
+        ValType::I32 => TYPE_I32,
+        ValType::I64 => TYPE_I64,
+    });
+}
+
+fn write_global_type(out: &mut Vec<u8>, t: &GlobalType) {
+    write_val_type(out, t.val_type);
+    out.push(if t.mutable { 1 } else { 0 });
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_u32(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+/// Unsigned LEB128
+pub fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Signed LEB128
+pub fn write_i64(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_bytes(value: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_u32(&mut out, value);
+        out
+    }
+
+    fn i64_bytes(value: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_i64(&mut out, value);
+        out
+    }
+
+    #[test]
+    fn leb128() {
+        assert_eq!(vec![0x00], u32_bytes(0));
+        assert_eq!(vec![0xe5, 0x8e, 0x26], u32_bytes(624485));
+        assert_eq!(vec![0x7f], i64_bytes(-1));
+        assert_eq!(vec![0xc0, 0xbb, 0x78], i64_bytes(-123456));
+        assert_eq!(vec![0x80, 0x01], i64_bytes(128));
+    }
+
+    #[test]
+    fn encode_empty_module() {
+        assert_eq!(vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00], encode(&Module::new()));
+    }
+}
@@ -0,0 +1,229 @@
+
+/*
+  A tiny interpreter for validated modules.  It only understands the instructions in `Instr`
+  and exists so that generated modules can be executed in tests without an external runtime.
+*/
+
+use super::module::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WasmValue {
+    I32(i32),
+    I64(i64),
+}
+
+impl WasmValue {
+    fn default_of(t: ValType) -> WasmValue {
+        match t {
+            ValType::I32 => WasmValue::I32(0),
+            ValType::I64 => WasmValue::I64(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trap {
+    Unreachable,
+    IntegerDivideByZero,
+    IntegerOverflow,
+    UnknownExport(String),
+    /// The wrong number or type of arguments or imports were supplied.
+    ArgumentMismatch,
+}
+
+pub struct Instance<'a> {
+    module: &'a Module,
+    globals: Vec<WasmValue>
+}
+
+impl <'a> Instance<'a> {
+    /// Instantiates `module`, which must have passed validation.  `imports` are the values of the
+    /// imported globals, in the order they are imported.
+    pub fn new(module: &'a Module, imports: &[WasmValue]) -> Result<Instance<'a>, Trap> {
+        if imports.len() != module.imports.len() {
+            return Err(Trap::ArgumentMismatch);
+        }
+        let mut globals = imports.to_vec();
+        for global in &module.globals {
+            let value = match global.init[0] {
+                Instr::I32Const(v) => WasmValue::I32(v),
+                Instr::I64Const(v) => WasmValue::I64(v),
+                Instr::GlobalGet(index) => globals[index as usize],
+                _ => return Err(Trap::Unreachable)
+            };
+            globals.push(value);
+        }
+        Ok(Instance { module, globals })
+    }
+
+    /// The current value of the exported global `name`.
+    pub fn exported_global(&self, name: &str) -> Option<WasmValue> {
+        match self.module.find_export(name) {
+            Some(ExportDesc::Global(index)) => self.globals.get(index as usize).cloned(),
+            _ => None
+        }
+    }
+
+    pub fn invoke(&mut self, name: &str, args: &[WasmValue]) -> Result<Vec<WasmValue>, Trap> {
+        match self.module.find_export(name) {
+            Some(ExportDesc::Func(index)) => self.call(index, args.to_vec()),
+            _ => Err(Trap::UnknownExport(String::from(name)))
+        }
+    }
+
+    fn call(&mut self, index: u32, args: Vec<WasmValue>) -> Result<Vec<WasmValue>, Trap> {
+        let module = self.module;
+        let function = &module.functions[index as usize];
+        let func_type = &module.types[function.type_index as usize];
+        if args.len() != func_type.params.len() {
+            return Err(Trap::ArgumentMismatch);
+        }
+
+        let mut locals = args;
+        locals.extend(function.locals.iter().map(|t| WasmValue::default_of(*t)));
+        let mut stack: Vec<WasmValue> = Vec::new();
+
+        let mut pc = 0;
+        while pc < function.body.len() {
+            match function.body[pc] {
+                Instr::Unreachable => return Err(Trap::Unreachable),
+                Instr::If => {
+                    if pop_i32(&mut stack) == 0 {
+                        pc = matching_end(&function.body, pc);
+                    }
+                },
+                Instr::End => {},
+                Instr::Return => break,
+                Instr::Call(callee) => {
+                    let param_count = module.types[module.functions[callee as usize].type_index as usize].params.len();
+                    let callee_args = stack.split_off(stack.len() - param_count);
+                    let results = self.call(callee, callee_args)?;
+                    stack.extend(results);
+                },
+                Instr::Drop => { stack.pop(); },
+                Instr::LocalGet(i) => stack.push(locals[i as usize]),
+                Instr::LocalSet(i) => locals[i as usize] = stack.pop().unwrap(),
+                Instr::GlobalGet(i) => stack.push(self.globals[i as usize]),
+                Instr::GlobalSet(i) => self.globals[i as usize] = stack.pop().unwrap(),
+                Instr::I32Const(v) => stack.push(WasmValue::I32(v)),
+                Instr::I64Const(v) => stack.push(WasmValue::I64(v)),
+                Instr::I32Eqz => {
+                    let v = pop_i32(&mut stack);
+                    stack.push(WasmValue::I32((v == 0) as i32));
+                },
+                Instr::I32Eq => i32_binary(&mut stack, |l, r| Ok((l == r) as i32))?,
+                Instr::I32And => i32_binary(&mut stack, |l, r| Ok(l & r))?,
+                Instr::I32Add => i32_binary(&mut stack, |l, r| Ok(l.wrapping_add(r)))?,
+                Instr::I32Sub => i32_binary(&mut stack, |l, r| Ok(l.wrapping_sub(r)))?,
+                Instr::I32Mul => i32_binary(&mut stack, |l, r| Ok(l.wrapping_mul(r)))?,
+                Instr::I32DivS => i32_binary(&mut stack, |l, r| match r {
+                    0 => Err(Trap::IntegerDivideByZero),
+                    _ => l.checked_div(r).ok_or(Trap::IntegerOverflow)
+                })?,
+                Instr::I32RemS => i32_binary(&mut stack, |l, r| match r {
+                    0 => Err(Trap::IntegerDivideByZero),
+                    _ => Ok(l.wrapping_rem(r))
+                })?,
+                Instr::I64Ne => {
+                    let r = pop_i64(&mut stack);
+                    let l = pop_i64(&mut stack);
+                    stack.push(WasmValue::I32((l != r) as i32));
+                },
+                Instr::I64Add => i64_binary(&mut stack, |l, r| l.wrapping_add(r)),
+                Instr::I64Sub => i64_binary(&mut stack, |l, r| l.wrapping_sub(r)),
+                Instr::I64Mul => i64_binary(&mut stack, |l, r| l.wrapping_mul(r)),
+                Instr::I32WrapI64 => {
+                    let v = pop_i64(&mut stack);
+                    stack.push(WasmValue::I32(v as i32));
+                },
+                Instr::I64ExtendI32S => {
+                    let v = pop_i32(&mut stack);
+                    stack.push(WasmValue::I64(v as i64));
+                },
+            }
+            pc += 1;
+        }
+
+        let result_count = func_type.results.len();
+        Ok(stack.split_off(stack.len() - result_count))
+    }
+}
+
+/// Returns the index of the `End` matching the `If` at `if_index`.
+fn matching_end(body: &[Instr], if_index: usize) -> usize {
+    let mut depth = 0;
+    for (i, instr) in body.iter().enumerate().skip(if_index) {
+        match *instr {
+            Instr::If => depth += 1,
+            Instr::End => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            },
+            _ => {}
+        }
+    }
+    panic!("Unbalanced if at instruction {} (the module should have been validated)", if_index)
+}
+
+fn pop_i32(stack: &mut Vec<WasmValue>) -> i32 {
+    match stack.pop() {
+        Some(WasmValue::I32(v)) => v,
+        other => panic!("Expected i32 on the stack but found {:?} (the module should have been validated)", other)
+    }
+}
+
+fn pop_i64(stack: &mut Vec<WasmValue>) -> i64 {
+    match stack.pop() {
+        Some(WasmValue::I64(v)) => v,
+        other => panic!("Expected i64 on the stack but found {:?} (the module should have been validated)", other)
+    }
+}
+
+fn i32_binary<F: Fn(i32, i32) -> Result<i32, Trap>>(stack: &mut Vec<WasmValue>, op: F) -> Result<(), Trap> {
+    let r = pop_i32(stack);
+    let l = pop_i32(stack);
+    stack.push(WasmValue::I32(op(l, r)?));
+    Ok(())
+}
+
+fn i64_binary<F: Fn(i64, i64) -> i64>(stack: &mut Vec<WasmValue>, op: F) {
+    let r = pop_i64(stack);
+    let l = pop_i64(stack);
+    stack.push(WasmValue::I64(op(l, r)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_function_module(body: Vec<Instr>) -> Module {
+        let mut module = Module::new();
+        let type_index = module.add_type(FuncType { params: vec![ValType::I32], results: vec![ValType::I32] });
+        module.functions.push(Function { type_index, locals: vec![], body });
+        module.exports.push(Export { name: String::from("f"), desc: ExportDesc::Func(0) });
+        module
+    }
+
+    #[test]
+    fn invoke_with_if() {
+        // if (x == 0) return 10;  return x * 2
+        let module = single_function_module(vec![
+            Instr::LocalGet(0), Instr::I32Eqz, Instr::If, Instr::I32Const(10), Instr::Return, Instr::End,
+            Instr::LocalGet(0), Instr::I32Const(2), Instr::I32Mul, Instr::End]);
+        let mut instance = Instance::new(&module, &[]).unwrap();
+
+        assert_eq!(Ok(vec![WasmValue::I32(10)]), instance.invoke("f", &[WasmValue::I32(0)]));
+        assert_eq!(Ok(vec![WasmValue::I32(6)]), instance.invoke("f", &[WasmValue::I32(3)]));
+    }
+
+    #[test]
+    fn invoke_traps() {
+        let module = single_function_module(vec![Instr::I32Const(1), Instr::LocalGet(0), Instr::I32DivS, Instr::End]);
+        let mut instance = Instance::new(&module, &[]).unwrap();
+
+        assert_eq!(Err(Trap::IntegerDivideByZero), instance.invoke("f", &[WasmValue::I32(0)]));
+        assert_eq!(Err(Trap::UnknownExport(String::from("g"))), instance.invoke("g", &[]));
+    }
+}
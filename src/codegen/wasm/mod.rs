@@ -0,0 +1,267 @@
+
+/*
+  Lowers a resolved `Expr` to a WebAssembly module.
+
+  The module exports a function named `evaluate` which returns the value of the expression as an `i32`
+  and a mutable `i32` global named `status`.  Arithmetic is checked to match the semantics of
+  `passes::evaluate`:  when an operation overflows or divides by zero the generated code stores the
+  corresponding `codegen::status_code` in `status` and traps with `unreachable`.
+*/
+
+pub mod module;
+pub mod encode;
+pub mod decode;
+pub mod validate;
+pub mod interp;
+
+use ast::*;
+use common::*;
+use env::*;
+use value::Value;
+use passes::evaluate::EvaluateErrorKind;
+use codegen::*;
+use self::module::*;
+
+pub const IMPORT_MODULE: &str = "env";
+pub const EVALUATE_EXPORT: &str = "evaluate";
+pub const STATUS_EXPORT: &str = "status";
+
+/// How the globals of the `EnvDef` are made available to the generated module.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlobalMode {
+    /// Each global is an immutable `i32` imported from the `env` module under its own name.
+    Imports,
+    /// Each global is a parameter of the `evaluate` function, in order of ordinal.
+    Parameters,
+}
+
+#[derive(Debug, Clone)]
+pub struct WasmOptions {
+    pub globals: GlobalMode
+}
+
+impl Default for WasmOptions {
+    fn default() -> WasmOptions {
+        WasmOptions { globals: GlobalMode::Imports }
+    }
+}
+
+/// The function indices of the checked arithmetic helpers.  `evaluate` is function 0.
+const HELPER_OPS: [BinaryOp; 5] = [BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div, BinaryOp::Mod];
+
+fn helper_index(op: &BinaryOp) -> u32 {
+    1 + HELPER_OPS.iter().position(|o| o == op).unwrap() as u32
+}
+
+/// Compiles `expr`, which must have been resolved against `env_def`, to a module.
+/// Use `encode::encode` to obtain the binary `.wasm` representation.
+pub fn compile(expr: &Expr, env_def: &EnvDef, options: &WasmOptions) -> Result<Module, CodegenError> {
+    let fields = env_def.fields();
+    let mut module = Module::new();
+
+    let params = match options.globals {
+        GlobalMode::Imports => {
+            for field in &fields {
+                module.imports.push(Import {
+                    module: String::from(IMPORT_MODULE),
+                    name: field.name.clone(),
+                    global_type: GlobalType { val_type: val_type_of(&field.default_value), mutable: false }
+                });
+            }
+            vec![]
+        },
+        GlobalMode::Parameters => fields.iter().map(|f| val_type_of(&f.default_value)).collect()
+    };
+
+    let status_global = module.imports.len() as u32;
+    module.globals.push(Global {
+        global_type: GlobalType { val_type: ValType::I32, mutable: true },
+        init: vec![Instr::I32Const(STATUS_OK)]
+    });
+
+    let mut body = Vec::new();
+    lower_expr(expr, fields.len() as u32, options.globals, &mut body)?;
+    body.push(Instr::End);
+
+    let type_index = module.add_type(FuncType { params, results: vec![ValType::I32] });
+    module.functions.push(Function { type_index, locals: vec![], body });
+
+    let helper_type = module.add_type(FuncType { params: vec![ValType::I32, ValType::I32], results: vec![ValType::I32] });
+    for op in HELPER_OPS.iter() {
+        module.functions.push(helper_function(op, helper_type, status_global));
+    }
+
+    module.exports.push(Export { name: String::from(EVALUATE_EXPORT), desc: ExportDesc::Func(0) });
+    module.exports.push(Export { name: String::from(STATUS_EXPORT), desc: ExportDesc::Global(status_global) });
+    Ok(module)
+}
+
+fn val_type_of(value: &Value) -> ValType {
+    match *value {
+        Value::Int32(_) => ValType::I32,
+    }
+}
+
+fn lower_expr(expr: &Expr, global_count: u32, mode: GlobalMode, out: &mut Vec<Instr>) -> Result<(), CodegenError> {
+    match expr.kind {
+        ExprKind::Literal { value: Value::Int32(v) } => out.push(Instr::I32Const(v)),
+        ExprKind::VariableIndex { index } => {
+            if index >= global_count {
+                return Err(CodegenError::new_with_span(CodegenErrorKind::InvalidGlobalIndex(index), expr.span));
+            }
+            out.push(match mode {
+                GlobalMode::Imports => Instr::GlobalGet(index),
+                GlobalMode::Parameters => Instr::LocalGet(index),
+            });
+        },
+        ExprKind::VariableRef { ref name } =>
+            return Err(CodegenError::new_with_span(CodegenErrorKind::UnresolvedVariable(name.clone()), expr.span)),
+        ExprKind::Error => return Err(CodegenError::new_with_span(CodegenErrorKind::SyntaxError, expr.span)),
+        ExprKind::Binary { ref op, ref left, ref right } => {
+            lower_expr(left, global_count, mode, out)?;
+            lower_expr(right, global_count, mode, out)?;
+            out.push(Instr::Call(helper_index(op)));
+        },
+        ExprKind::CompoundExpr { ref exprs } => {
+            for (i, e) in exprs.iter().enumerate() {
+                lower_expr(e, global_count, mode, out)?;
+                if i < exprs.len() - 1 {
+                    out.push(Instr::Drop);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Emits the instructions which store the status code of `kind` and trap.
+fn fail(kind: EvaluateErrorKind, status_global: u32) -> Vec<Instr> {
+    vec![Instr::If, Instr::I32Const(status_code(&kind)), Instr::GlobalSet(status_global), Instr::Unreachable, Instr::End]
+}
+
+/// Builds a function of two `i32` parameters which applies `op` and checks for errors.
+fn helper_function(op: &BinaryOp, type_index: u32, status_global: u32) -> Function {
+    let mut body = Vec::new();
+    let overflow = EvaluateErrorKind::IntegerOverflow(op.clone());
+    let locals = match *op {
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => {
+            // Compute the result with 64 bits of precision and fail if it does not fit in 32.
+            let wide_op = match *op {
+                BinaryOp::Add => Instr::I64Add,
+                BinaryOp::Sub => Instr::I64Sub,
+                _ => Instr::I64Mul,
+            };
+            body.extend(vec![
+                Instr::LocalGet(0), Instr::I64ExtendI32S, Instr::LocalGet(1), Instr::I64ExtendI32S, wide_op, Instr::LocalSet(2),
+                Instr::LocalGet(2), Instr::LocalGet(2), Instr::I32WrapI64, Instr::I64ExtendI32S, Instr::I64Ne]);
+            body.extend(fail(overflow, status_global));
+            body.extend(vec![Instr::LocalGet(2), Instr::I32WrapI64]);
+            vec![ValType::I64]
+        },
+        BinaryOp::Div | BinaryOp::Mod => {
+            body.extend(vec![Instr::LocalGet(1), Instr::I32Eqz]);
+            body.extend(fail(EvaluateErrorKind::DivisionByZero, status_global));
+            body.extend(vec![
+                Instr::LocalGet(0), Instr::I32Const(i32::MIN), Instr::I32Eq,
+                Instr::LocalGet(1), Instr::I32Const(-1), Instr::I32Eq, Instr::I32And]);
+            body.extend(fail(overflow, status_global));
+            body.extend(vec![
+                Instr::LocalGet(0), Instr::LocalGet(1),
+                if *op == BinaryOp::Div { Instr::I32DivS } else { Instr::I32RemS }]);
+            vec![]
+        }
+    };
+    body.push(Instr::End);
+    Function { type_index, locals, body }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::encode::encode;
+    use super::decode::decode;
+    use super::validate::validate;
+    use super::interp::*;
+    use passes::resolve_variables::*;
+    use passes::evaluate::*;
+
+    fn env_def() -> EnvDef {
+        EnvDefBuilder::new()
+            .with_item("foo", Value::Int32(100))
+            .with_item("bar", Value::Int32(-7))
+            .with_item("big", Value::Int32(i32::MAX))
+            .build()
+    }
+
+    /// Compiles `source`, round trips the module through the binary format, validates it and then runs it.
+    fn run(source: &str, mode: GlobalMode) -> Result<Value, EvaluateErrorKind> {
+        let env_def = env_def();
+        let expr = resolve_variables(::parse(source).unwrap(), &env_def).unwrap();
+        let module = compile(&expr, &env_def, &WasmOptions { globals: mode }).unwrap();
+
+        let bytes = encode(&module);
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(module, decoded);
+        assert_eq!(bytes, encode(&decoded));
+        validate(&decoded).unwrap();
+
+        let values: Vec<WasmValue> = env_def.fields().iter().map(|f| match f.default_value {
+            Value::Int32(v) => WasmValue::I32(v)
+        }).collect();
+        let (imports, args) = match mode {
+            GlobalMode::Imports => (values, vec![]),
+            GlobalMode::Parameters => (vec![], values),
+        };
+        let mut instance = Instance::new(&decoded, &imports).unwrap();
+        match instance.invoke(EVALUATE_EXPORT, &args) {
+            Ok(results) => match results[..] {
+                [WasmValue::I32(v)] => Ok(Value::Int32(v)),
+                _ => panic!("Unexpected results {:?}", results)
+            },
+            Err(Trap::Unreachable) => match instance.exported_global(STATUS_EXPORT) {
+                Some(WasmValue::I32(code)) => Err(status_error(code).expect("Unknown status code")),
+                other => panic!("Unexpected status {:?}", other)
+            },
+            Err(trap) => panic!("Unexpected trap {:?}", trap)
+        }
+    }
+
+    fn interpret(source: &str) -> Result<Value, EvaluateErrorKind> {
+        let env_def = env_def();
+        let expr = resolve_variables(::parse(source).unwrap(), &env_def).unwrap();
+        evaluate(&expr, &env_def.create_with_default_values()).map_err(|e| e.kind)
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        let sources = vec![
+            "1", "foo", "1 + 2 * 3", "foo - bar * 2", "foo / bar", "bar / 2", "foo 2 bar",
+            "big + 1", "big * 2", "0 - big - 2", "foo / 0", "1 foo / 0 2", "bar * big"];
+
+        for source in sources {
+            let expected = interpret(source);
+            assert_eq!(expected, run(source, GlobalMode::Imports), "Imports: {}", source);
+            assert_eq!(expected, run(source, GlobalMode::Parameters), "Parameters: {}", source);
+        }
+    }
+
+    #[test]
+    fn imports_are_named_after_globals() {
+        let env_def = env_def();
+        let module = compile(&Expr::new_variable_index(1), &env_def, &WasmOptions::default()).unwrap();
+
+        let names: Vec<&str> = module.imports.iter().map(|i| &i.name[..]).collect();
+        assert_eq!(vec!["foo", "bar", "big"], names);
+        assert_eq!(vec![Instr::GlobalGet(1), Instr::End], module.functions[0].body);
+    }
+
+    #[test]
+    fn compile_errors() {
+        let env_def = env_def();
+        let unresolved = compile(&Expr::new_variable_ref(String::from("x")), &env_def, &WasmOptions::default());
+        assert_eq!(CodegenErrorKind::UnresolvedVariable(String::from("x")), unresolved.unwrap_err().kind);
+
+        let invalid = compile(&Expr::new_variable_index(3), &env_def, &WasmOptions::default());
+        assert_eq!(CodegenErrorKind::InvalidGlobalIndex(3), invalid.unwrap_err().kind);
+    }
+}
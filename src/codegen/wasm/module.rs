@@ -0,0 +1,185 @@
+
+/*
+  An in-memory representation of the (small) subset of the WebAssembly binary format produced by
+  the backend.  `encode` turns a `Module` into bytes and `decode` turns the bytes back into an equal
+  `Module`.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValType {
+    I32,
+    I64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalType {
+    pub val_type: ValType,
+    pub mutable: bool
+}
+
+/// Only globals are ever imported by generated modules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub global_type: GlobalType
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub global_type: GlobalType,
+    /// A constant expression, not including the terminating `end`.
+    pub init: Vec<Instr>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportDesc {
+    Func(u32),
+    Global(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    pub name: String,
+    pub desc: ExportDesc
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub type_index: u32,
+    /// The declared locals, not including the parameters.
+    pub locals: Vec<ValType>,
+    /// The body of the function, including the terminating `end`.
+    pub body: Vec<Instr>
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub types: Vec<FuncType>,
+    pub imports: Vec<Import>,
+    pub functions: Vec<Function>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>
+}
+
+impl Module {
+    pub fn new() -> Module {
+        Module::default()
+    }
+
+    /// Returns the index of `func_type`, adding it to the type section if it isn't already present.
+    pub fn add_type(&mut self, func_type: FuncType) -> u32 {
+        match self.types.iter().position(|t| *t == func_type) {
+            Some(index) => index as u32,
+            None => {
+                self.types.push(func_type);
+                (self.types.len() - 1) as u32
+            }
+        }
+    }
+
+    /// Returns the type of the global with the specified index.  Imported globals come first.
+    pub fn global_type(&self, index: u32) -> Option<&GlobalType> {
+        let index = index as usize;
+        if index < self.imports.len() {
+            Some(&self.imports[index].global_type)
+        } else {
+            self.globals.get(index - self.imports.len()).map(|g| &g.global_type)
+        }
+    }
+
+    pub fn global_count(&self) -> u32 {
+        (self.imports.len() + self.globals.len()) as u32
+    }
+
+    pub fn find_export(&self, name: &str) -> Option<ExportDesc> {
+        self.exports.iter().find(|e| e.name == name).map(|e| e.desc)
+    }
+}
+
+/// The instructions used by generated code.  Structured control instructions are represented
+/// exactly as they are in the binary format:  `If` is followed by its body and a matching `End`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    Unreachable,
+    /// An `if` with an empty block type.
+    If,
+    End,
+    Return,
+    Call(u32),
+    Drop,
+    LocalGet(u32),
+    LocalSet(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    I32Const(i32),
+    I64Const(i64),
+    I32Eqz,
+    I32Eq,
+    I64Ne,
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32RemS,
+    I32And,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I32WrapI64,
+    I64ExtendI32S,
+}
+
+pub mod opcodes {
+    pub const UNREACHABLE: u8 = 0x00;
+    pub const IF: u8 = 0x04;
+    pub const END: u8 = 0x0b;
+    pub const RETURN: u8 = 0x0f;
+    pub const CALL: u8 = 0x10;
+    pub const DROP: u8 = 0x1a;
+    pub const LOCAL_GET: u8 = 0x20;
+    pub const LOCAL_SET: u8 = 0x21;
+    pub const GLOBAL_GET: u8 = 0x23;
+    pub const GLOBAL_SET: u8 = 0x24;
+    pub const I32_CONST: u8 = 0x41;
+    pub const I64_CONST: u8 = 0x42;
+    pub const I32_EQZ: u8 = 0x45;
+    pub const I32_EQ: u8 = 0x46;
+    pub const I64_NE: u8 = 0x52;
+    pub const I32_ADD: u8 = 0x6a;
+    pub const I32_SUB: u8 = 0x6b;
+    pub const I32_MUL: u8 = 0x6c;
+    pub const I32_DIV_S: u8 = 0x6d;
+    pub const I32_REM_S: u8 = 0x6f;
+    pub const I32_AND: u8 = 0x71;
+    pub const I64_ADD: u8 = 0x7c;
+    pub const I64_SUB: u8 = 0x7d;
+    pub const I64_MUL: u8 = 0x7e;
+    pub const I32_WRAP_I64: u8 = 0xa7;
+    pub const I64_EXTEND_I32_S: u8 = 0xac;
+
+    pub const BLOCK_TYPE_EMPTY: u8 = 0x40;
+    pub const TYPE_I32: u8 = 0x7f;
+    pub const TYPE_I64: u8 = 0x7e;
+    pub const TYPE_FUNC: u8 = 0x60;
+    pub const IMPORT_GLOBAL: u8 = 0x03;
+    pub const EXPORT_FUNC: u8 = 0x00;
+    pub const EXPORT_GLOBAL: u8 = 0x03;
+
+    pub const SECTION_CUSTOM: u8 = 0;
+    pub const SECTION_TYPE: u8 = 1;
+    pub const SECTION_IMPORT: u8 = 2;
+    pub const SECTION_FUNCTION: u8 = 3;
+    pub const SECTION_GLOBAL: u8 = 6;
+    pub const SECTION_EXPORT: u8 = 7;
+    pub const SECTION_CODE: u8 = 10;
+
+    pub const MAGIC: &[u8; 4] = b"\0asm";
+    pub const VERSION: u32 = 1;
+}
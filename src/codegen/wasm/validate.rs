@@ -0,0 +1,349 @@
+
+use std::fmt;
+use std::error::Error as StdError;
+use error::*;
+use super::module::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationErrorKind {
+    InvalidTypeIndex(u32),
+    InvalidFunctionIndex(u32),
+    InvalidGlobalIndex(u32),
+    InvalidLocalIndex(u32),
+    ImmutableGlobal(u32),
+    /// `found` is `None` when the operand stack was empty.
+    TypeMismatch { expected: ValType, found: Option<ValType> },
+    /// Values were left on the operand stack at the end of a block.
+    UnexpectedValues(usize),
+    UnbalancedControl,
+    InvalidConstantExpression,
+    DuplicateExport(String),
+}
+
+impl ErrorKind for ValidationErrorKind {
+    fn message(&self) -> String {
+        match *self {
+            ValidationErrorKind::InvalidTypeIndex(i) => format!("Type index {} does not exist", i),
+            ValidationErrorKind::InvalidFunctionIndex(i) => format!("Function index {} does not exist", i),
+            ValidationErrorKind::InvalidGlobalIndex(i) => format!("Global index {} does not exist", i),
+            ValidationErrorKind::InvalidLocalIndex(i) => format!("Local index {} does not exist", i),
+            ValidationErrorKind::ImmutableGlobal(i) => format!("Global {} is immutable", i),
+            ValidationErrorKind::TypeMismatch { ref expected, found: Some(ref found) } =>
+                format!("Type mismatch: expected {:?} but found {:?}", expected, found),
+            ValidationErrorKind::TypeMismatch { ref expected, found: None } =>
+                format!("Type mismatch: expected {:?} but the stack was empty", expected),
+            ValidationErrorKind::UnexpectedValues(count) => format!("{} unexpected values left on the stack", count),
+            ValidationErrorKind::UnbalancedControl => String::from("Unbalanced control instructions"),
+            ValidationErrorKind::InvalidConstantExpression => String::from("Invalid constant expression"),
+            ValidationErrorKind::DuplicateExport(ref name) => format!("Duplicate export name '{}'", name),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            ValidationErrorKind::InvalidTypeIndex(_) => "E0801",
+            ValidationErrorKind::InvalidFunctionIndex(_) => "E0802",
+            ValidationErrorKind::InvalidGlobalIndex(_) => "E0803",
+            ValidationErrorKind::InvalidLocalIndex(_) => "E0804",
+            ValidationErrorKind::ImmutableGlobal(_) => "E0805",
+            ValidationErrorKind::TypeMismatch { .. } => "E0806",
+            ValidationErrorKind::UnexpectedValues(_) => "E0807",
+            ValidationErrorKind::UnbalancedControl => "E0808",
+            ValidationErrorKind::InvalidConstantExpression => "E0809",
+            ValidationErrorKind::DuplicateExport(_) => "E0810",
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ::std::error::Error for ValidationErrorKind {}
+
+/// A validation error.  `function` and `instruction` identify the offending instruction
+/// when the error is within a function body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub kind: ValidationErrorKind,
+    pub function: Option<u32>,
+    pub instruction: Option<usize>
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.function, self.instruction) {
+            (Some(func), Some(instr)) => write!(f, "{} (function {}, instruction {})", self.kind.message(), func, instr),
+            _ => write!(f, "{}", self.kind.message())
+        }
+    }
+}
+
+impl StdError for ValidationError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.kind)
+    }
+}
+
+fn module_error(kind: ValidationErrorKind) -> ValidationError {
+    ValidationError { kind, function: None, instruction: None }
+}
+
+/// Checks that `module` is valid according to the WebAssembly typing rules.
+pub fn validate(module: &Module) -> Result<(), ValidationError> {
+    for global in &module.globals {
+        validate_constant_expr(module, &global.init, global.global_type.val_type)?;
+    }
+
+    let mut names: Vec<&str> = Vec::new();
+    for export in &module.exports {
+        if names.contains(&&export.name[..]) {
+            return Err(module_error(ValidationErrorKind::DuplicateExport(export.name.clone())));
+        }
+        names.push(&export.name);
+        match export.desc {
+            ExportDesc::Func(index) if index as usize >= module.functions.len() =>
+                return Err(module_error(ValidationErrorKind::InvalidFunctionIndex(index))),
+            ExportDesc::Global(index) if index >= module.global_count() =>
+                return Err(module_error(ValidationErrorKind::InvalidGlobalIndex(index))),
+            _ => {}
+        }
+    }
+
+    for (index, function) in module.functions.iter().enumerate() {
+        let func_type = module.types.get(function.type_index as usize)
+            .ok_or_else(|| module_error(ValidationErrorKind::InvalidTypeIndex(function.type_index)))?;
+
+        let mut checker = FunctionChecker {
+            module,
+            func_type,
+            locals: func_type.params.iter().chain(function.locals.iter()).cloned().collect(),
+            stack: Vec::new(),
+            frames: vec![Frame { height: 0, unreachable: false }],
+        };
+        for (i, instr) in function.body.iter().enumerate() {
+            let result = if checker.frames.is_empty() {
+                Err(ValidationErrorKind::UnbalancedControl)
+            } else {
+                checker.check(*instr)
+            };
+            result.map_err(|kind| ValidationError { kind, function: Some(index as u32), instruction: Some(i) })?;
+        }
+        if !checker.frames.is_empty() {
+            return Err(ValidationError { kind: ValidationErrorKind::UnbalancedControl, function: Some(index as u32), instruction: None });
+        }
+    }
+    Ok(())
+}
+
+fn validate_constant_expr(module: &Module, init: &[Instr], expected: ValType) -> Result<(), ValidationError> {
+    let found = match init {
+        [Instr::I32Const(_)] => ValType::I32,
+        [Instr::I64Const(_)] => ValType::I64,
+        [Instr::GlobalGet(index)] if (*index as usize) < module.imports.len() && !module.imports[*index as usize].global_type.mutable =>
+            module.imports[*index as usize].global_type.val_type,
+        _ => return Err(module_error(ValidationErrorKind::InvalidConstantExpression))
+    };
+    if found != expected {
+        return Err(module_error(ValidationErrorKind::TypeMismatch { expected, found: Some(found) }));
+    }
+    Ok(())
+}
+
+struct Frame {
+    height: usize,
+    unreachable: bool
+}
+
+struct FunctionChecker<'a> {
+    module: &'a Module,
+    func_type: &'a FuncType,
+    locals: Vec<ValType>,
+    stack: Vec<ValType>,
+    frames: Vec<Frame>
+}
+
+type CheckResult = Result<(), ValidationErrorKind>;
+
+impl <'a> FunctionChecker<'a> {
+    fn check(&mut self, instr: Instr) -> CheckResult {
+        use self::ValType::*;
+        match instr {
+            Instr::Unreachable => self.set_unreachable(),
+            Instr::If => {
+                self.pop(I32)?;
+                self.frames.push(Frame { height: self.stack.len(), unreachable: false });
+            },
+            Instr::End => {
+                if self.frames.len() == 1 {
+                    let results = self.func_type.results.clone();
+                    self.pop_all(&results)?;
+                }
+                let frame = self.frames.pop().ok_or(ValidationErrorKind::UnbalancedControl)?;
+                if self.stack.len() != frame.height {
+                    return Err(ValidationErrorKind::UnexpectedValues(self.stack.len() - frame.height));
+                }
+            },
+            Instr::Return => {
+                let results = self.func_type.results.clone();
+                self.pop_all(&results)?;
+                self.set_unreachable();
+            },
+            Instr::Call(index) => {
+                let function = self.module.functions.get(index as usize).ok_or(ValidationErrorKind::InvalidFunctionIndex(index))?;
+                let callee = self.module.types.get(function.type_index as usize)
+                    .ok_or(ValidationErrorKind::InvalidTypeIndex(function.type_index))?;
+                self.pop_all(&callee.params)?;
+                self.stack.extend(callee.results.iter().cloned());
+            },
+            Instr::Drop => { self.pop_any()?; },
+            Instr::LocalGet(index) => {
+                let t = self.local(index)?;
+                self.stack.push(t);
+            },
+            Instr::LocalSet(index) => {
+                let t = self.local(index)?;
+                self.pop(t)?;
+            },
+            Instr::GlobalGet(index) => {
+                let t = self.module.global_type(index).ok_or(ValidationErrorKind::InvalidGlobalIndex(index))?.val_type;
+                self.stack.push(t);
+            },
+            Instr::GlobalSet(index) => {
+                let global_type = self.module.global_type(index).ok_or(ValidationErrorKind::InvalidGlobalIndex(index))?;
+                if !global_type.mutable {
+                    return Err(ValidationErrorKind::ImmutableGlobal(index));
+                }
+                self.pop(global_type.val_type)?;
+            },
+            Instr::I32Const(_) => self.stack.push(I32),
+            Instr::I64Const(_) => self.stack.push(I64),
+            Instr::I32Eqz => self.unary(I32, I32)?,
+            Instr::I32WrapI64 => self.unary(I64, I32)?,
+            Instr::I64ExtendI32S => self.unary(I32, I64)?,
+            Instr::I32Eq | Instr::I32Add | Instr::I32Sub | Instr::I32Mul | Instr::I32DivS | Instr::I32RemS | Instr::I32And =>
+                self.binary(I32, I32)?,
+            Instr::I64Ne => self.binary(I64, I32)?,
+            Instr::I64Add | Instr::I64Sub | Instr::I64Mul => self.binary(I64, I64)?,
+        }
+        Ok(())
+    }
+
+    fn local(&self, index: u32) -> Result<ValType, ValidationErrorKind> {
+        self.locals.get(index as usize).cloned().ok_or(ValidationErrorKind::InvalidLocalIndex(index))
+    }
+
+    fn unary(&mut self, operand: ValType, result: ValType) -> CheckResult {
+        self.pop(operand)?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn binary(&mut self, operand: ValType, result: ValType) -> CheckResult {
+        self.pop(operand)?;
+        self.pop(operand)?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().unwrap();
+        self.stack.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    /// Pops a value of any type.  Returns `None` if the current frame is unreachable and the
+    /// stack is empty, in which case any type is allowed.
+    fn pop_any(&mut self) -> Result<Option<ValType>, ValidationErrorKind> {
+        let frame = self.frames.last().unwrap();
+        if self.stack.len() == frame.height {
+            return if frame.unreachable { Ok(None) } else { Err(ValidationErrorKind::TypeMismatch { expected: ValType::I32, found: None }) };
+        }
+        Ok(self.stack.pop())
+    }
+
+    fn pop(&mut self, expected: ValType) -> CheckResult {
+        match self.pop_any() {
+            Ok(None) => Ok(()),
+            Ok(Some(found)) if found == expected => Ok(()),
+            Ok(Some(found)) => Err(ValidationErrorKind::TypeMismatch { expected, found: Some(found) }),
+            Err(_) => Err(ValidationErrorKind::TypeMismatch { expected, found: None }),
+        }
+    }
+
+    fn pop_all(&mut self, types: &[ValType]) -> CheckResult {
+        for t in types.iter().rev() {
+            self.pop(*t)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module_with_body(params: Vec<ValType>, body: Vec<Instr>) -> Module {
+        let mut module = Module::new();
+        let type_index = module.add_type(FuncType { params, results: vec![ValType::I32] });
+        module.functions.push(Function { type_index, locals: vec![], body });
+        module
+    }
+
+    fn error_kind(module: &Module) -> ValidationErrorKind {
+        validate(module).unwrap_err().kind
+    }
+
+    #[test]
+    fn validate_valid_function() {
+        let module = module_with_body(vec![ValType::I32], vec![
+            Instr::LocalGet(0), Instr::I32Const(1), Instr::I32Add, Instr::End]);
+        assert_eq!(Ok(()), validate(&module));
+    }
+
+    #[test]
+    fn validate_type_mismatch() {
+        let module = module_with_body(vec![], vec![Instr::I64Const(1), Instr::End]);
+        assert_eq!(ValidationErrorKind::TypeMismatch { expected: ValType::I32, found: Some(ValType::I64) }, error_kind(&module));
+
+        let module = module_with_body(vec![], vec![Instr::I32Add, Instr::End]);
+        assert_eq!(ValidationErrorKind::TypeMismatch { expected: ValType::I32, found: None }, error_kind(&module));
+    }
+
+    #[test]
+    fn validate_unreachable_is_polymorphic() {
+        let module = module_with_body(vec![], vec![Instr::Unreachable, Instr::I32Add, Instr::End]);
+        assert_eq!(Ok(()), validate(&module));
+    }
+
+    #[test]
+    fn validate_control() {
+        let module = module_with_body(vec![], vec![Instr::I32Const(0), Instr::If, Instr::End]);
+        assert_eq!(ValidationErrorKind::UnbalancedControl, error_kind(&module));
+
+        let module = module_with_body(vec![], vec![Instr::I32Const(0), Instr::If, Instr::I32Const(1), Instr::End, Instr::I32Const(1), Instr::End]);
+        assert_eq!(ValidationErrorKind::UnexpectedValues(1), error_kind(&module));
+
+        let module = module_with_body(vec![], vec![Instr::I32Const(1), Instr::End, Instr::Drop]);
+        assert_eq!(ValidationErrorKind::UnbalancedControl, error_kind(&module));
+    }
+
+    #[test]
+    fn validate_indices() {
+        let module = module_with_body(vec![], vec![Instr::LocalGet(0), Instr::End]);
+        assert_eq!(ValidationErrorKind::InvalidLocalIndex(0), error_kind(&module));
+
+        let module = module_with_body(vec![], vec![Instr::GlobalGet(3), Instr::End]);
+        assert_eq!(ValidationErrorKind::InvalidGlobalIndex(3), error_kind(&module));
+
+        let mut module = module_with_body(vec![], vec![Instr::I32Const(1), Instr::GlobalSet(0), Instr::I32Const(1), Instr::End]);
+        module.imports.push(Import {
+            module: String::from("env"),
+            name: String::from("x"),
+            global_type: GlobalType { val_type: ValType::I32, mutable: false }
+        });
+        assert_eq!(ValidationErrorKind::ImmutableGlobal(0), error_kind(&module));
+    }
+}
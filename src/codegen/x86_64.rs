@@ -0,0 +1,240 @@
+
+/*
+  Translates a resolved `Expr` of `Int32` values into x86-64 assembly (GNU assembler, AT&T syntax,
+  ELF and the System V calling convention).  The generated function has the C signature:
+
+      int <name>(const int32_t *globals, int32_t *result);
+
+  `globals` points to the values of the `Env`, indexed by ordinal.  The return value and `*result`
+  have the same meaning as for the C backend:  `codegen::STATUS_OK` and the value of the expression,
+  or the `codegen::status_code` of the error `passes::evaluate` would have reported.
+
+  Registers are allocated while walking the `Binary` tree:  the result of a node is placed in the
+  first free register of `REGISTERS` and its right operand uses the next one.  When the tree is deeper
+  than the number of registers the left operand is spilled to the stack.  Operands are always evaluated
+  left to right (no Sethi-Ullman reordering) so that errors are reported in the same order as the
+  interpreter.  Literal and global right operands are used directly as immediate and memory operands.
+*/
+
+use std::fmt::Write;
+use ast::*;
+use common::*;
+use env::*;
+use value::Value;
+use passes::evaluate::EvaluateErrorKind;
+use codegen::*;
+
+#[derive(Debug, Clone)]
+pub struct AsmOptions {
+    /// The name of the generated (global) function symbol.
+    pub function_name: String
+}
+
+impl Default for AsmOptions {
+    fn default() -> AsmOptions {
+        AsmOptions { function_name: String::from("evaluate") }
+    }
+}
+
+/// Caller saved registers available for holding intermediate results.  `%eax` and `%edx` are
+/// reserved for `idivl` and `%rdi`/`%rsi` hold the arguments.
+const REGISTERS: [&str; 4] = ["%ecx", "%r8d", "%r9d", "%r10d"];
+/// The 64 bit names of `REGISTERS`, used when spilling.
+const REGISTERS_64: [&str; 4] = ["%rcx", "%r8", "%r9", "%r10"];
+/// Holds the right operand of an operation when it is not in one of `REGISTERS`.
+const SCRATCH: &str = "%r11d";
+
+/// Generates an assembly file containing the function described in the module documentation.
+/// `expr` must have been resolved against `env_def`.
+pub fn compile(expr: &Expr, env_def: &EnvDef, options: &AsmOptions) -> Result<String, CodegenError> {
+    let name = &options.function_name;
+    let mut emitter = Emitter { global_count: env_def.fields().len() as u32, name, code: String::new(), used_statuses: Vec::new(), label_count: 0 };
+    emitter.emit_expr(expr, 0)?;
+
+    let mut out = String::new();
+    writeln!(out, "    .text").unwrap();
+    writeln!(out, "    .globl {}", name).unwrap();
+    writeln!(out, "    .type {}, @function", name).unwrap();
+    writeln!(out, "{}:", name).unwrap();
+    writeln!(out, "    pushq %rbp").unwrap();
+    writeln!(out, "    movq %rsp, %rbp").unwrap();
+    out.push_str(&emitter.code);
+    writeln!(out, "    movl {}, (%rsi)", REGISTERS[0]).unwrap();
+    writeln!(out, "    movl ${}, %eax", STATUS_OK).unwrap();
+    writeln!(out, "    popq %rbp").unwrap();
+    writeln!(out, "    ret").unwrap();
+
+    if !emitter.used_statuses.is_empty() {
+        for status in &emitter.used_statuses {
+            writeln!(out, ".L{}_status_{}:", name, status).unwrap();
+            writeln!(out, "    movl ${}, %eax", status).unwrap();
+            writeln!(out, "    jmp .L{}_fail", name).unwrap();
+        }
+        //Discards anything that was spilled to the stack.
+        writeln!(out, ".L{}_fail:", name).unwrap();
+        writeln!(out, "    movq %rbp, %rsp").unwrap();
+        writeln!(out, "    popq %rbp").unwrap();
+        writeln!(out, "    ret").unwrap();
+    }
+    writeln!(out, "    .size {}, .-{}", name, name).unwrap();
+    writeln!(out, "    .section .note.GNU-stack,\"\",@progbits").unwrap();
+    Ok(out)
+}
+
+struct Emitter<'a> {
+    global_count: u32,
+    name: &'a str,
+    code: String,
+    used_statuses: Vec<i32>,
+    label_count: u32
+}
+
+impl <'a> Emitter<'a> {
+    /// Emits the code which evaluates `expr` into `REGISTERS[reg]`.  Registers before `reg` are in use.
+    fn emit_expr(&mut self, expr: &Expr, reg: usize) -> Result<(), CodegenError> {
+        match expr.kind {
+            ExprKind::Binary { ref op, ref left, ref right } => {
+                self.emit_expr(left, reg)?;
+
+                let right_operand = match self.simple_operand(right)? {
+                    Some(operand) => operand,
+                    None if reg + 1 < REGISTERS.len() => {
+                        self.emit_expr(right, reg + 1)?;
+                        String::from(REGISTERS[reg + 1])
+                    },
+                    None => {
+                        writeln!(self.code, "    pushq {}", REGISTERS_64[reg]).unwrap();
+                        self.emit_expr(right, reg)?;
+                        writeln!(self.code, "    movl {}, {}", REGISTERS[reg], SCRATCH).unwrap();
+                        writeln!(self.code, "    popq {}", REGISTERS_64[reg]).unwrap();
+                        String::from(SCRATCH)
+                    }
+                };
+                self.emit_binary(op, REGISTERS[reg], &right_operand);
+            },
+            ExprKind::CompoundExpr { ref exprs } => {
+                //Every expression is evaluated for its errors but only the value of the last is kept.
+                for e in exprs {
+                    self.emit_expr(e, reg)?;
+                }
+            },
+            _ => {
+                let operand = self.simple_operand(expr)?.unwrap();
+                writeln!(self.code, "    movl {}, {}", operand, REGISTERS[reg]).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the immediate or memory operand for literals and globals, or `None` for anything else.
+    fn simple_operand(&self, expr: &Expr) -> Result<Option<String>, CodegenError> {
+        match expr.kind {
+            ExprKind::Literal { value: Value::Int32(v) } => Ok(Some(format!("${}", v))),
+            ExprKind::VariableIndex { index } if index < self.global_count => Ok(Some(format!("{}(%rdi)", index * 4))),
+            ExprKind::VariableIndex { index } =>
+                Err(CodegenError::new_with_span(CodegenErrorKind::InvalidGlobalIndex(index), expr.span)),
+            ExprKind::VariableRef { ref name } =>
+                Err(CodegenError::new_with_span(CodegenErrorKind::UnresolvedVariable(name.clone()), expr.span)),
+            ExprKind::Error => Err(CodegenError::new_with_span(CodegenErrorKind::SyntaxError, expr.span)),
+            _ => Ok(None)
+        }
+    }
+
+    /// Emits a jump to the code which returns the status code for `kind` when `condition` holds.
+    fn emit_fail(&mut self, condition: &str, kind: EvaluateErrorKind) {
+        let status = status_code(&kind);
+        if !self.used_statuses.contains(&status) {
+            self.used_statuses.push(status);
+        }
+        writeln!(self.code, "    {} .L{}_status_{}", condition, self.name, status).unwrap();
+    }
+
+    /// Emits `left = left <op> right`.  `left` is a register; `right` may be a register, immediate or memory operand.
+    fn emit_binary(&mut self, op: &BinaryOp, left: &str, right: &str) {
+        let overflow = EvaluateErrorKind::IntegerOverflow(op.clone());
+        match *op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => {
+                let instruction = match *op { BinaryOp::Add => "addl", BinaryOp::Sub => "subl", _ => "imull" };
+                writeln!(self.code, "    {} {}, {}", instruction, right, left).unwrap();
+                self.emit_fail("jo", overflow);
+            },
+            BinaryOp::Div | BinaryOp::Mod => {
+                //idivl cannot take an immediate operand.
+                let divisor = if right.starts_with('$') {
+                    writeln!(self.code, "    movl {}, {}", right, SCRATCH).unwrap();
+                    SCRATCH
+                } else {
+                    right
+                };
+                writeln!(self.code, "    cmpl $0, {}", divisor).unwrap();
+                self.emit_fail("je", EvaluateErrorKind::DivisionByZero);
+
+                let label = format!(".L{}_div_{}", self.name, self.label_count);
+                self.label_count += 1;
+                writeln!(self.code, "    cmpl $-1, {}", divisor).unwrap();
+                writeln!(self.code, "    jne {}", label).unwrap();
+                writeln!(self.code, "    cmpl ${}, {}", i32::MIN, left).unwrap();
+                self.emit_fail("je", overflow);
+                writeln!(self.code, "{}:", label).unwrap();
+
+                writeln!(self.code, "    movl {}, %eax", left).unwrap();
+                writeln!(self.code, "    cltd").unwrap();
+                writeln!(self.code, "    idivl {}", divisor).unwrap();
+                let result = if *op == BinaryOp::Div { "%eax" } else { "%edx" };
+                writeln!(self.code, "    movl {}, {}", result, left).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(expr: &Expr) -> Vec<String> {
+        let env_def = EnvDefBuilder::new().with_item("foo", Value::Int32(1)).with_item("bar", Value::Int32(2)).build();
+        compile(expr, &env_def, &AsmOptions::default()).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn compile_uses_simple_operands() {
+        let expr = Expr::new_binary(BinaryOp::Add, Expr::new_variable_index(0), Expr::new_variable_index(1));
+        let lines = lines(&expr);
+
+        assert!(lines.contains(&String::from("    movl 0(%rdi), %ecx")));
+        assert!(lines.contains(&String::from("    addl 4(%rdi), %ecx")));
+        assert!(lines.contains(&String::from("    jo .Levaluate_status_3")));
+        assert!(lines.contains(&String::from("    movl %ecx, (%rsi)")));
+    }
+
+    #[test]
+    fn compile_allocates_registers() {
+        let nested = Expr::new_binary(BinaryOp::Mul, Expr::new_literal(Value::Int32(2)), Expr::new_literal(Value::Int32(3)));
+        let expr = Expr::new_binary(BinaryOp::Sub, Expr::new_literal(Value::Int32(1)), nested);
+        let lines = lines(&expr);
+
+        assert!(lines.contains(&String::from("    movl $2, %r8d")));
+        assert!(lines.contains(&String::from("    imull $3, %r8d")));
+        assert!(lines.contains(&String::from("    subl %r8d, %ecx")));
+    }
+
+    #[test]
+    fn compile_spills_when_out_of_registers() {
+        let mut expr = Expr::new_variable_index(0);
+        for _ in 0..REGISTERS.len() + 1 {
+            expr = Expr::new_binary(BinaryOp::Add, Expr::new_literal(Value::Int32(1)), expr);
+        }
+        let lines = lines(&expr);
+
+        assert!(lines.contains(&String::from("    pushq %r10")));
+        assert!(lines.contains(&String::from("    popq %r10")));
+        assert!(lines.contains(&String::from("    movq %rbp, %rsp")));
+    }
+
+    #[test]
+    fn compile_errors() {
+        let env_def = EnvDefBuilder::new().build();
+        let result = compile(&Expr::new_variable_index(0), &env_def, &AsmOptions::default());
+        assert_eq!(CodegenErrorKind::InvalidGlobalIndex(0), result.unwrap_err().kind);
+    }
+}
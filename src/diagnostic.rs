@@ -0,0 +1,378 @@
+/*
+  Renders errors as reports which show where in the source they occurred, in the style of rustc:
+
+      error: Variable 'x' does not exist
+       --> rules.txt:2:5
+        |
+      2 | 1 + x
+        |     ^ not found
+        = note: ...
+
+  The header names the `Severity` of the diagnostic and its code, if any.  The primary span of a `Diagnostic`
+  is underlined with `^`.  Secondary labelled spans, which may be in other files of the `SourceMap`, are
+  underlined with `-`.
+
+  Diagnostics can also be serialized to JSON for tools, with `to_json`.
+*/
+
+use std::fmt::Write;
+use std::collections::BTreeSet;
+use error::*;
+use source::*;
+use source_map::*;
+use json::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The `ErrorKind::code` of the error the diagnostic reports.
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub span: Span,
+    /// Printed next to the underline of `span`.
+    pub label: Option<String>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<String>
+}
+
+impl Diagnostic {
+    pub fn new(message: String, span: Span) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, code: None, message, span, label: None, secondary: Vec::new(), notes: Vec::new(), suggestions: Vec::new() }
+    }
+
+    pub fn from_error<TErrorKind: ErrorKind>(error: &SourceError<TErrorKind>) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(error.kind.message(), error.span).with_severity(error.kind.severity());
+        diagnostic.code = Some(error.kind.code());
+        diagnostic.suggestions = error.kind.suggestions();
+        diagnostic
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Diagnostic {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_label(mut self, message: &str) -> Diagnostic {
+        self.label = Some(String::from(message));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Diagnostic {
+        self.secondary.push(Label { span, message: String::from(message) });
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(String::from(note));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Colours the report with ANSI escape codes.
+    pub colour: bool
+}
+
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[1;32m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+fn severity_colour(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+        Severity::Note => GREEN
+    }
+}
+
+/// Renders `error` with the source of its span from `source_map`.
+pub fn render_error<TErrorKind: ErrorKind>(error: &SourceError<TErrorKind>, source_map: &SourceMap, options: &RenderOptions) -> String {
+    render(&Diagnostic::from_error(error), source_map, options)
+}
+
+/// A span to underline.
+struct Annotation<'a> {
+    span: Span,
+    message: Option<&'a str>,
+    primary: bool
+}
+
+/// Renders `diagnostic`.  Spans whose file is not in `source_map` are reported by line and column only.
+pub fn render(diagnostic: &Diagnostic, source_map: &SourceMap, options: &RenderOptions) -> String {
+    let paint = |colour: &str, text: &str| if options.colour { format!("{}{}{}", colour, text, RESET) } else { String::from(text) };
+
+    let mut annotations = vec![Annotation { span: diagnostic.span, message: diagnostic.label.as_ref().map(|l| &l[..]), primary: true }];
+    annotations.extend(diagnostic.secondary.iter().map(|l| Annotation { span: l.span, message: Some(&l.message[..]), primary: false }));
+
+    let gutter_width = annotations.iter()
+        .filter_map(|a| source_map.lookup(&a.span))
+        .map(|l| l.end.line.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let pad = " ".repeat(gutter_width);
+
+    let mut out = String::new();
+    let severity = match diagnostic.code {
+        Some(code) => format!("{}[{}]", diagnostic.severity.name(), code),
+        None => String::from(diagnostic.severity.name())
+    };
+    writeln!(out, "{}{}", paint(severity_colour(diagnostic.severity), &severity), paint(BOLD, &format!(": {}", diagnostic.message))).unwrap();
+
+    //The primary file is reported first, then the others in order of their first label.
+    let mut files: Vec<FileId> = Vec::new();
+    for a in &annotations {
+        if !files.contains(&a.span.file) {
+            files.push(a.span.file);
+        }
+    }
+    for (i, &file_id) in files.iter().enumerate() {
+        let arrow = if i == 0 { "-->" } else { ":::" };
+        let file_annotations: Vec<&Annotation> = annotations.iter().filter(|a| a.span.file == file_id).collect();
+        let first = file_annotations[0].span;
+        let file = match source_map.file(file_id) {
+            Some(file) => file,
+            None => {
+                writeln!(out, "{}{} <unknown>:{}:{}", pad, paint(BLUE, arrow), first.start.line, first.start.col_offset).unwrap();
+                continue;
+            }
+        };
+        let start = file.line_col(first.start.offset);
+        writeln!(out, "{}{} {}:{}:{}", pad, paint(BLUE, arrow), file.name, start.line, start.col).unwrap();
+        writeln!(out, "{} {}", pad, paint(BLUE, "|")).unwrap();
+        render_lines(&file_annotations, file, severity_colour(diagnostic.severity), &pad, &paint, &mut out);
+    }
+
+    for note in &diagnostic.notes {
+        writeln!(out, "{} {} note: {}", pad, paint(BLUE, "="), note).unwrap();
+    }
+    out
+}
+
+/// Renders the lines of `file` covered by `annotations`, each followed by the underlines of the annotations.
+fn render_lines(annotations: &[&Annotation], file: &SourceFile, primary_colour: &str, pad: &str, paint: &dyn Fn(&str, &str) -> String, out: &mut String) {
+    let ranges: Vec<(LineCol, LineCol)> = annotations.iter()
+        .map(|a| (file.line_col(a.span.start.offset), file.line_col(a.span.end.offset)))
+        .collect();
+    let lines: BTreeSet<u32> = ranges.iter().flat_map(|&(start, end)| start.line..end.line + 1).collect();
+
+    let mut previous = None;
+    for line in lines {
+        if previous.is_some_and(|p| line > p + 1) {
+            writeln!(out, "{}", paint(BLUE, "...")).unwrap();
+        }
+        previous = Some(line);
+
+        let text = file.line_text(line).unwrap_or("");
+        writeln!(out, "{} {} {}", paint(BLUE, &format!("{:>width$}", line, width = pad.len())), paint(BLUE, "|"), text).unwrap();
+
+        for (a, &(start, end)) in annotations.iter().zip(ranges.iter()) {
+            if line < start.line || line > end.line {
+                continue;
+            }
+            let line_len = text.chars().count() as u32;
+            let from = if line == start.line { start.col } else { 1 };
+            let to = if line == end.line { end.col } else { line_len.max(from) };
+
+            //Tabs are kept so the underline lines up with the text above it.
+            let indent: String = text.chars().take(from as usize - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+            let (mark, colour) = if a.primary { ("^", primary_colour) } else { ("-", BLUE) };
+            let mut underline = mark.repeat((to + 1).saturating_sub(from).max(1) as usize);
+            if let (true, Some(message)) = (line == end.line, a.message) {
+                underline = format!("{} {}", underline, message);
+            }
+            writeln!(out, "{} {} {}{}", pad, paint(BLUE, "|"), indent, paint(colour, &underline)).unwrap();
+        }
+    }
+}
+
+/// Serializes `error` as a single line of JSON.  See `to_json`.
+pub fn render_error_json<TErrorKind: ErrorKind>(error: &SourceError<TErrorKind>, source_map: &SourceMap) -> String {
+    to_json(&Diagnostic::from_error(error), source_map).to_string()
+}
+
+/// Returns the JSON object for `diagnostic`:
+///
+/// ```text
+/// {"code": "E0301" | null, "severity": "error" | "warning" | "note", "message": "...",
+///  "spans": [{"file": "name" | null, "primary": true, "label": "..." | null,
+///             "start": {"line": 1, "column": 5, "offset": 4}, "end": {...}}],
+///  "notes": ["..."], "suggestions": ["..."]}
+/// ```
+///
+/// The primary span is first.  `end` is the position of the last character of the span.  Lines and columns
+/// start at 1, columns count characters and offsets are in bytes.
+pub fn to_json(diagnostic: &Diagnostic, source_map: &SourceMap) -> Json {
+    let span_json = |span: &Span, primary: bool, label: Option<&str>| Json::object(vec![
+        ("file", Json::from(source_map.file_name(span))),
+        ("primary", Json::Bool(primary)),
+        ("label", Json::from(label)),
+        ("start", location_json(&span.start)),
+        ("end", location_json(&span.end))
+    ]);
+    let mut spans = vec![span_json(&diagnostic.span, true, diagnostic.label.as_ref().map(|l| &l[..]))];
+    spans.extend(diagnostic.secondary.iter().map(|l| span_json(&l.span, false, Some(&l.message))));
+
+    let strings = |items: &[String]| Json::Array(items.iter().map(|s| Json::string(s)).collect());
+    Json::object(vec![
+        ("code", Json::from(diagnostic.code)),
+        ("severity", Json::string(diagnostic.severity.name())),
+        ("message", Json::string(&diagnostic.message)),
+        ("spans", Json::Array(spans)),
+        ("notes", strings(&diagnostic.notes)),
+        ("suggestions", strings(&diagnostic.suggestions))
+    ])
+}
+
+fn location_json(location: &Location) -> Json {
+    Json::object(vec![
+        ("line", Json::from(location.line)),
+        ("column", Json::from(location.col_offset)),
+        ("offset", Json::from(location.offset))
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_with_file;
+    use passes::lint::*;
+
+    fn span_of(map: &SourceMap, file: FileId, text: &str) -> Span {
+        let source = &map.file(file).unwrap().source;
+        let start = source.find(text).unwrap();
+        let mut loc = Location::start();
+        for c in source[..start].chars() {
+            loc = loc.advance(c);
+        }
+        let mut end = loc;
+        for c in text.chars().take(text.chars().count() - 1) {
+            end = end.advance(c);
+        }
+        Span::new(file, loc, end)
+    }
+
+    #[test]
+    fn render_single_line() {
+        let mut map = SourceMap::new();
+        let file = map.add("rules.txt", String::from("1\n2 + foo\n"));
+        let diagnostic = Diagnostic::new(String::from("Variable 'foo' does not exist"), span_of(&map, file, "foo"))
+            .with_label("not found")
+            .with_note("variables must be defined in the environment");
+        assert_eq!(
+            concat!(
+                "error: Variable 'foo' does not exist\n",
+                " --> rules.txt:2:5\n",
+                "  |\n",
+                "2 | 2 + foo\n",
+                "  |     ^^^ not found\n",
+                "  = note: variables must be defined in the environment\n"),
+            render(&diagnostic, &map, &RenderOptions::default()));
+    }
+
+    #[test]
+    fn render_multiple_lines_and_labels() {
+        let mut map = SourceMap::new();
+        let file = map.add("a", String::from("1 +\n\t(2\n3)\n4\n5\n6\n7\n8\n9\n(10\n11)"));
+        let other = map.add("b", String::from("x"));
+        let diagnostic = Diagnostic::new(String::from("Problem"), span_of(&map, file, "(2\n3)"))
+            .with_secondary(span_of(&map, file, "(10\n11)"), "here")
+            .with_secondary(span_of(&map, other, "x"), "there");
+        assert_eq!(
+            concat!(
+                "error: Problem\n",
+                "  --> a:2:2\n",
+                "   |\n",
+                " 2 | \t(2\n",
+                "   | \t^^\n",
+                " 3 | 3)\n",
+                "   | ^^\n",
+                "...\n",
+                "10 | (10\n",
+                "   | ---\n",
+                "11 | 11)\n",
+                "   | --- here\n",
+                "  ::: b:1:1\n",
+                "   |\n",
+                " 1 | x\n",
+                "   | - there\n"),
+            render(&diagnostic, &map, &RenderOptions::default()));
+    }
+
+    #[test]
+    fn render_parse_error() {
+        let mut map = SourceMap::new();
+        let file = map.add("input", String::from("1 +"));
+        let error = parse_with_file("1 +", file).unwrap_err();
+        let rendered = render_error(&error, &map, &RenderOptions { colour: true });
+        assert!(rendered.starts_with("\x1b[1;31merror[E0203]\x1b[0m\x1b[1m: Unexpected end of input\x1b[0m\n"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+    }
+
+    #[test]
+    fn render_warning() {
+        let mut map = SourceMap::new();
+        let file = map.add("input", String::from("1 2"));
+        let expr = parse_with_file("1 2", file).unwrap();
+        let warning = &lint(&expr, &LintConfig::new())[0];
+        assert_eq!(
+            concat!(
+                "warning[W0001]: The result of this expression is unused\n",
+                " --> input:1:1\n",
+                "  |\n",
+                "1 | 1 2\n",
+                "  | ^\n"),
+            render_error(warning, &map, &RenderOptions::default()));
+        assert!(render_error(warning, &map, &RenderOptions { colour: true }).contains("\x1b[1;33m^\x1b[0m"));
+    }
+
+    #[test]
+    fn render_unknown_file() {
+        let error = ::parse("1 +").unwrap_err();
+        assert_eq!("error[E0203]: Unexpected end of input\n--> <unknown>:1:4\n", render_error(&error, &SourceMap::new(), &RenderOptions::default()));
+    }
+
+    #[test]
+    fn render_json() {
+        let mut map = SourceMap::new();
+        let file = map.add("in\"put", String::from("1 +\n\u{e9}"));
+        let error = parse_with_file("1 +\n\u{e9}", file).unwrap_err();
+        assert_eq!(
+            concat!(
+                "{\"code\":\"E0101\",\"severity\":\"error\",\"message\":\"Invalid character: '\u{e9}'\",",
+                "\"spans\":[{\"file\":\"in\\\"put\",\"primary\":true,\"label\":null,",
+                "\"start\":{\"line\":2,\"column\":1,\"offset\":4},\"end\":{\"line\":2,\"column\":1,\"offset\":4}}],",
+                "\"notes\":[],\"suggestions\":[]}"),
+            render_error_json(&error, &map));
+
+        let diagnostic = Diagnostic::new(String::from("Problem"), span_of(&map, file, "1"))
+            .with_secondary(Span::unknown(), "elsewhere")
+            .with_note("a note");
+        let json = to_json(&diagnostic, &map);
+        assert_eq!(Some(&Json::Null), json.get("code"));
+        assert_eq!(Some(&Json::Array(vec![Json::string("a note")])), json.get("notes"));
+        match json.get("spans") {
+            Some(Json::Array(spans)) => {
+                assert_eq!(Some(&Json::Null), spans[1].get("file"));
+                assert_eq!(Some(&Json::string("elsewhere")), spans[1].get("label"));
+            },
+            _ => panic!("Expected spans")
+        }
+
+        let env_def = ::env::EnvDefBuilder::new().with_item("customerId", ::value::Value::Int32(1)).build();
+        let error = ::execute_with_globals("custmerId", &env_def).unwrap_err();
+        let json = to_json(&Diagnostic::from_error(&error), &map);
+        assert_eq!(Some(&Json::string("E0301")), json.get("code"));
+        assert_eq!(Some(&Json::Array(vec![Json::string("customerId")])), json.get("suggestions"));
+    }
+}
@@ -0,0 +1,139 @@
+/*
+  Text dumps of what the lexer and parser produce, for `compiler_in_rust --emit=tokens|ast|dot`.
+
+  Spans are written `line:column-line:column`, where the end is the last character of the span.  For example
+  the tree of `1 + a * 2` is dumped by `ast` as:
+
+      Binary Add 1:1-1:9
+        Literal 1 1:1-1:1
+        Binary Mul 1:5-1:9
+          VariableRef a 1:5-1:5
+          Literal 2 1:9-1:9
+*/
+
+use std::fmt::Write;
+use ast::*;
+use ast::printer::op_text;
+use lexer::*;
+use source::*;
+use value::*;
+
+fn span_text(span: &Span) -> String {
+    format!("{}:{}-{}:{}", span.start.line, span.start.col_offset, span.end.line, span.end.col_offset)
+}
+
+/// Writes one token per line:  its span and `TokenKind`.
+pub fn tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        writeln!(out, "{:<10} {:?}", span_text(&token.span), token.kind).unwrap();
+    }
+    out
+}
+
+/// Writes one node of `expr` per line, with its span, indenting the children of each node by two spaces.
+pub fn ast(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_node(expr, 0, &mut out);
+    out
+}
+
+fn write_node(expr: &Expr, depth: usize, out: &mut String) {
+    let description = match expr.kind {
+        ExprKind::Literal { value: Value::Int32(v) } => format!("Literal {}", v),
+        ExprKind::Binary { ref op, .. } => format!("Binary {:?}", op),
+        ExprKind::VariableRef { ref name } => format!("VariableRef {}", name),
+        ExprKind::VariableIndex { index } => format!("VariableIndex {}", index),
+        ExprKind::CompoundExpr { .. } => String::from("CompoundExpr"),
+        ExprKind::Error => String::from("Error")
+    };
+    writeln!(out, "{}{} {}", "  ".repeat(depth), description, span_text(&expr.span)).unwrap();
+    for child in children(expr) {
+        write_node(child, depth + 1, out);
+    }
+}
+
+/// The children of `expr`, in order.
+fn children(expr: &Expr) -> Vec<&Expr> {
+    match expr.kind {
+        ExprKind::Binary { ref left, ref right, .. } => vec![left, right],
+        ExprKind::CompoundExpr { ref exprs } => exprs.iter().map(|e| &**e).collect(),
+        ExprKind::Literal { .. } | ExprKind::VariableRef { .. } | ExprKind::VariableIndex { .. } | ExprKind::Error => Vec::new()
+    }
+}
+
+/// Writes `expr` as a Graphviz DOT digraph with a node for each node of the tree, labelled with the operator,
+/// value or name and the span.  The edges from binary operators are labelled `left` and `right`, and the
+/// edges from compound expressions with the position of the child.
+pub fn dot(expr: &Expr) -> String {
+    let mut out = String::from("digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n");
+    write_dot_node(expr, &mut 0, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+/// Writes `expr`, which is numbered `next_id`, and its children.  Returns the id of `expr`.
+fn write_dot_node(expr: &Expr, next_id: &mut u32, out: &mut String) -> u32 {
+    let id = *next_id;
+    *next_id += 1;
+    let label = match expr.kind {
+        ExprKind::Literal { value: Value::Int32(v) } => v.to_string(),
+        ExprKind::Binary { ref op, .. } => String::from(op_text(op)),
+        ExprKind::VariableRef { ref name } => name.clone(),
+        ExprKind::VariableIndex { index } => format!("#{}", index),
+        ExprKind::CompoundExpr { .. } => String::from("compound"),
+        ExprKind::Error => String::from("<error>")
+    };
+    writeln!(out, "    n{} [label=\"{}\\n{}\"];", id, escape(&label), span_text(&expr.span)).unwrap();
+    let binary = matches!(expr.kind, ExprKind::Binary { .. });
+    for (i, child) in children(expr).into_iter().enumerate() {
+        let child_id = write_dot_node(child, next_id, out);
+        let edge = match (binary, i) {
+            (true, 0) => String::from("left"),
+            (true, _) => String::from("right"),
+            (false, i) => i.to_string()
+        };
+        writeln!(out, "    n{} -> n{} [label=\"{}\"];", id, child_id, edge).unwrap();
+    }
+    id
+}
+
+/// Escapes the characters of `text` which are special in a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::Lexer;
+    use parser::Parser;
+
+    fn parse(source: &str) -> Expr {
+        Parser::new(Lexer::new(source.chars())).parse().unwrap()
+    }
+
+    #[test]
+    fn dump_tokens() {
+        let tokens = Lexer::new("(12 +\n x)".chars()).tokens().unwrap();
+        assert_eq!(
+            "1:1-1:1    OpenParen\n1:2-1:3    LiteralInt32(12)\n1:5-1:5    BinaryOperator(Add)\n2:2-2:2    Identifier(\"x\")\n2:3-2:3    CloseParen\n",
+            super::tokens(&tokens));
+    }
+
+    #[test]
+    fn dump_ast() {
+        assert_eq!(
+            "Binary Add 1:1-1:9\n  Literal 1 1:1-1:1\n  Binary Mul 1:5-1:9\n    VariableRef a 1:5-1:5\n    Literal 2 1:9-1:9\n",
+            ast(&parse("1 + a * 2")));
+        assert_eq!("CompoundExpr 1:1-1:5\n  Literal 1 1:1-1:1\n  Binary Sub 1:3-1:5\n    Literal 2 1:3-1:3\n    Literal 3 1:5-1:5\n", ast(&parse("1 2-3")));
+    }
+
+    #[test]
+    fn dump_dot() {
+        assert_eq!(
+            "digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n    n0 [label=\"compound\\n1:1-1:7\"];\n    n1 [label=\"*\\n1:1-1:5\"];\n    n2 [label=\"a\\n1:1-1:1\"];\n    n1 -> n2 [label=\"left\"];\n    n3 [label=\"2\\n1:5-1:5\"];\n    n1 -> n3 [label=\"right\"];\n    n0 -> n1 [label=\"0\"];\n    n4 [label=\"b\\n1:7-1:7\"];\n    n0 -> n4 [label=\"1\"];\n}\n",
+            dot(&parse("a * 2 b")));
+        assert_eq!("\\\"<error>\\\\", escape("\"<error>\\"));
+    }
+}
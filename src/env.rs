@@ -11,11 +11,15 @@ impl EnvDef {
         self.fields.get(name)
     }
 
-    pub fn create_with_default_values(&self) -> Env {
+    /// Returns every field, ordered by ordinal.
+    pub fn fields(&self) -> Vec<&EnvField> {
         let mut fields = self.fields.values().collect::<Vec<&EnvField>>();
+        fields.sort_by_key(|f| f.ordinal);
+        fields
+    }
 
-        fields.sort_by(|a, b| a.ordinal.cmp(&b.ordinal));
-        let values = fields.iter().map(|f| f.default_value.clone()).collect();
+    pub fn create_with_default_values(&self) -> Env {
+        let values = self.fields().iter().map(|f| f.default_value.clone()).collect();
 
         Env::new(self, values)
     }
@@ -64,8 +68,12 @@ impl EnvDefBuilder {
         EnvDefBuilder { fields: HashMap::new() }
     }
 
+    /// Defines a field, or replaces the default value of the field if it has already been defined.
     pub fn with_item(mut self, name: &str, default_value: Value) -> EnvDefBuilder {
-        let ordinal = self.fields.len() as u32;
+        let ordinal = match self.fields.get(name) {
+            Some(field) => field.ordinal,
+            None => self.fields.len() as u32
+        };
         self.fields.insert(String::from(name), EnvField { name: String::from(name), default_value: default_value, ordinal: ordinal });
         self
     }
@@ -91,4 +99,17 @@ mod tests {
         assert_eq!(Some(&EnvField { name:String::from("foo"), default_value: Value::Int32(100), ordinal: 0 }), env_def.find("foo"));
         assert_eq!(Some(&EnvField { name:String::from("bar"), default_value: Value::Int32(200), ordinal: 1 }), env_def.find("bar"));
     }
+
+    #[test]
+    fn env_def_builder_redefinition() {
+        let env_def = EnvDefBuilder::new()
+            .with_item("foo", Value::Int32(100))
+            .with_item("bar", Value::Int32(200))
+            .with_item("foo", Value::Int32(300))
+            .build();
+
+        assert_eq!(Some(&EnvField { name:String::from("foo"), default_value: Value::Int32(300), ordinal: 0 }), env_def.find("foo"));
+        assert_eq!(Some(&Value::Int32(300)), env_def.create_with_default_values().get_by_name("foo"));
+        assert_eq!(2, env_def.fields().len());
+    }
 }
@@ -3,10 +3,50 @@
 use std::fmt;
 use std::cmp;
 use std::clone;
+use std::error::Error as StdError;
 use source::*;
+use explain;
 
-pub trait ErrorKind : fmt::Debug + cmp::PartialEq + clone::Clone +  {
+/// How serious a diagnostic is.  Only errors stop a program from being executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note"
+        }
+    }
+}
+
+/// Error kinds implement `Display` as their `message()`.  Kinds which wrap the kind of another phase return
+/// it from `Error::source`.
+pub trait ErrorKind : StdError + cmp::PartialEq + clone::Clone {
     fn message(&self) -> String;
+
+    /// A stable identifier of the kind of error, such as `E0101`, which does not change between releases.
+    /// Warnings start with `W`.
+    fn code(&self) -> &'static str;
+
+    /// The long-form explanation of `code`, with examples.
+    fn explanation(&self) -> Option<&'static str> {
+        explain::explanation(self.code())
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    /// Replacements for the erroneous code, such as the names of similar variables, best first.
+    fn suggestions(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -26,3 +66,48 @@ impl <TErrorKind: ErrorKind> SourceError<TErrorKind> {
     }
 }
 
+/// Displays the message followed by the line and column of the start of the span.
+impl <TErrorKind: ErrorKind> fmt::Display for SourceError<TErrorKind> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.kind.message(), self.span.start.line, self.span.start.col_offset)
+    }
+}
+
+impl <TErrorKind: ErrorKind> StdError for SourceError<TErrorKind> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.kind.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::*;
+    use parser::*;
+    use lexer::*;
+
+    #[test]
+    fn errors_display_message_and_location() {
+        let error = execute("1 +\n  $").unwrap_err();
+        assert_eq!("Invalid character: '$' at 2:3", error.to_string());
+        assert_eq!("Invalid character: '$'", error.kind.to_string());
+    }
+
+    #[test]
+    fn errors_chain_sources() {
+        let error = execute("1 +\n  $").unwrap_err();
+        let parse_kind = error.source().unwrap();
+        assert_eq!("Invalid character: '$'", parse_kind.to_string());
+        assert_eq!(
+            Some(&ParseErrorKind::LexerError(LexerErrorKind::InvalidCharacter('$'))),
+            parse_kind.downcast_ref::<ParseErrorKind>());
+        let lexer_kind = parse_kind.source().unwrap();
+        assert_eq!(Some(&LexerErrorKind::InvalidCharacter('$')), lexer_kind.downcast_ref::<LexerErrorKind>());
+        assert!(lexer_kind.source().is_none());
+
+        let error = execute("1 / 0").unwrap_err();
+        let boxed: Box<dyn StdError> = Box::new(error);
+        assert_eq!("Division by zero at 1:1", boxed.to_string());
+        assert!(boxed.source().unwrap().source().is_none());
+    }
+}
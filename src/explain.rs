@@ -0,0 +1,381 @@
+/*
+  Long-form explanations of every error code returned by `ErrorKind::code`, as printed by
+  `compiler_in_rust --explain <code>`.
+
+  Codes are grouped by the phase which reports them:
+
+      E01xx  lexer                      E05xx  code generation
+      E02xx  parser                     E06xx  bytecode loading
+      E03xx  variable resolution        E07xx  WebAssembly decoding
+      E04xx  evaluation                 E08xx  WebAssembly validation
+      E09xx  REPL sessions              W00xx  lints
+
+  Codes are never reused:  when an error is removed its code is retired.  Every new error kind
+  variant must be given a new code and an explanation here.
+*/
+
+const EXPLANATIONS: &[(&str, &str)] = &[
+    ("E0101", "\
+A character was found which does not start any token.
+
+Expressions may only contain integer literals, variable names made of ASCII letters, digits and
+underscores, the operators `+ - * / %`, parentheses, whitespace and comments.
+
+Erroneous code example:
+
+    price * 1.5
+
+Integers are the only numbers supported, so `.` is not a valid character.
+"),
+    ("E0102", "\
+An integer literal does not fit in a 32-bit signed integer.
+
+Literals must be between 0 and 2147483647.
+
+Erroneous code example:
+
+    3000000000 / 2
+"),
+    ("E0103", "\
+A block comment was opened with `/*` but never closed with `*/`.
+
+Block comments nest, so every `/*` inside a comment needs its own `*/`.
+
+Erroneous code example:
+
+    1 + /* outer /* inner */ 2
+"),
+    ("E0201", "\
+A binary operator was expected but another token was found.
+
+Expressions written next to each other form a sequence rather than requiring an operator between
+them, so this is not normally reported.
+"),
+    ("E0202", "\
+A token was found where the start of an expression was expected.
+
+Expressions start with an integer literal, a variable name or `(`.  There are no unary operators.
+
+Erroneous code example:
+
+    1 + * 2
+    -5
+
+Write `0 - 5` for a negative number.
+"),
+    ("E0203", "\
+The input ended while an expression was incomplete.
+
+Erroneous code example:
+
+    1 +
+
+The operator `+` needs a right operand.
+"),
+    ("E0204", "\
+A `(` has no matching `)`.
+
+Erroneous code example:
+
+    (1 + 2 * 3
+"),
+    ("E0205", "\
+No expression was found, either in the whole input or between a pair of parentheses.
+
+Erroneous code example:
+
+    1 + ()
+
+Input which is empty, or only contains comments, also reports this error.
+"),
+    ("E0301", "\
+A variable was referenced which is not defined in the environment.
+
+Variables must be defined by the host before the expression is executed, for example with
+`EnvDefBuilder::with_item`.  Names are case sensitive.  When there are defined variables with
+similar names they are suggested.
+
+Erroneous code example, where only `customerId` is defined:
+
+    custmerId * 2
+"),
+    ("E0401", "\
+A variable index was out of the range of the environment the expression was executed in.
+
+This happens when an expression is resolved against one environment definition and executed with
+another, smaller one.  Resolve and execute expressions with the same `EnvDef`.
+"),
+    ("E0402", "\
+The right operand of `/` or `%` was zero.
+
+Erroneous code example:
+
+    total / count
+
+fails when `count` is zero.  The `division_by_zero` lint (W0002) warns about division by a
+literal `0`.
+"),
+    ("E0403", "\
+The result of an arithmetic operation does not fit in a 32-bit signed integer.
+
+Erroneous code example:
+
+    2147483647 + 1
+
+Operations never wrap around.  Dividing the smallest integer by -1 also overflows.
+"),
+    ("E0501", "\
+Code was generated for an expression which still refers to variables by name.
+
+Run the `resolve_variables` pass before passing an expression to a code generator.
+"),
+    ("E0502", "\
+The expression refers to a global which does not exist in the environment definition given to the
+code generator.  Generate code with the same `EnvDef` the expression was resolved against.
+"),
+    ("E0503", "\
+Code was generated for an expression containing syntax errors.  Only expressions which parsed
+without errors can be compiled.
+"),
+    ("E0601", "\
+The data does not start with the magic number of compiled bytecode, so it is not a bytecode file.
+"),
+    ("E0602", "\
+The bytecode was written by a different version of the compiler.  Compile the source again.
+"),
+    ("E0603", "\
+The bytecode ended unexpectedly.  The file is truncated or corrupt.
+"),
+    ("E0604", "\
+The bytecode contains a constant of an unknown type.  The file is corrupt.
+"),
+    ("E0605", "\
+The bytecode contains an unknown instruction.  The file is corrupt.
+"),
+    ("E0606", "\
+An instruction of the bytecode refers to a constant which does not exist.  The file is corrupt.
+"),
+    ("E0607", "\
+An instruction of the bytecode would pop a value from an empty stack.  The file is corrupt.
+"),
+    ("E0608", "\
+The bytecode would not leave exactly one value, the result, on the stack.  The file is corrupt.
+"),
+    ("E0609", "\
+There is data after the end of the bytecode.  The file is corrupt.
+"),
+    ("E0701", "\
+The data does not start with the WebAssembly magic number, so it is not a WebAssembly module.
+"),
+    ("E0702", "\
+The WebAssembly module has a version other than 1.
+"),
+    ("E0703", "\
+The WebAssembly module ended unexpectedly.  The module is truncated or corrupt.
+"),
+    ("E0704", "\
+An integer in the WebAssembly module is not a valid LEB128 encoding.
+"),
+    ("E0705", "\
+A name in the WebAssembly module is not valid UTF-8.
+"),
+    ("E0706", "\
+The WebAssembly module contains a section which the decoder does not support.  Only the sections
+the WebAssembly backend emits are supported.
+"),
+    ("E0707", "\
+The sections of the WebAssembly module are not in the order the specification requires.
+"),
+    ("E0708", "\
+The contents of a section of the WebAssembly module do not match the size declared for it.
+"),
+    ("E0709", "\
+The WebAssembly module contains an unknown value type.
+"),
+    ("E0710", "\
+A type in the WebAssembly module is not a function type.
+"),
+    ("E0711", "\
+The WebAssembly module imports something other than a function or global, which the decoder does
+not support.
+"),
+    ("E0712", "\
+The WebAssembly module exports something other than a function or global, which the decoder does
+not support.
+"),
+    ("E0713", "\
+The WebAssembly module contains a block with a type the decoder does not support.
+"),
+    ("E0714", "\
+A global of the WebAssembly module has an invalid mutability flag.
+"),
+    ("E0715", "\
+The WebAssembly module contains an instruction the decoder does not support.  Only the instructions
+the WebAssembly backend emits are supported.
+"),
+    ("E0716", "\
+The number of function bodies in the WebAssembly module differs from the number of functions
+declared.
+"),
+    ("E0717", "\
+A function of the WebAssembly module declares more locals than are supported.
+"),
+    ("E0801", "\
+An instruction refers to a function type which does not exist.
+"),
+    ("E0802", "\
+An instruction or export refers to a function which does not exist.
+"),
+    ("E0803", "\
+An instruction or export refers to a global which does not exist.
+"),
+    ("E0804", "\
+An instruction refers to a local which does not exist.
+"),
+    ("E0805", "\
+An instruction sets a global which is immutable.
+"),
+    ("E0806", "\
+An instruction's operand has a different type than the instruction requires, or the operand
+stack is empty.
+"),
+    ("E0807", "\
+Values were left on the operand stack at the end of a block.
+"),
+    ("E0808", "\
+The `block`, `if`, `else` and `end` instructions of a function are not balanced.
+"),
+    ("E0809", "\
+The initializer of a global is not a constant expression.
+"),
+    ("E0810", "\
+Two exports of the module have the same name.
+"),
+    ("E0901", "\
+An input to the REPL starts with `let` but is not a definition of the form
+`let <name> = <expression>`.
+
+Erroneous code example:
+
+    let total 10
+
+Write `let total = 10`.  Names are made of ASCII letters, digits and underscores, and do not start
+with a digit.
+"),
+    ("W0001", "\
+The value of an expression in a sequence other than the last is discarded, and evaluating the
+expression can have no effect, so it can be removed.
+
+Example:
+
+    discount total * rate
+
+Only the value of `total * rate` is the result, so `discount` is unused.  Perhaps an operator is
+missing.  This lint is named `unused_result`.
+"),
+    ("W0002", "\
+The right operand of `/` or `%` is the literal `0`, so evaluation will always fail with a
+division by zero (E0402).
+
+Example:
+
+    total / 0
+
+This lint is named `division_by_zero`.
+"),
+    ("W0003", "\
+The value bound by a `let` definition is not used by any input before the name is defined again,
+or before the inputs end.
+
+Example:
+
+    let rate = 3
+    let rate = 4
+    total * rate
+
+The first value of `rate` is replaced before it is used, so its definition can be removed.  This
+lint is named `unused_let`.
+"),
+    ("W0004", "\
+A `let` definition has the name of a global, so the inputs which follow use the value of the
+definition instead of the global's.
+
+Example:
+
+    let total = 10
+
+Choose another name if the global is still needed.  This lint is named `shadowed_global`.
+"),
+];
+
+/// Returns the explanation of the error with the code `code`, such as `E0101`.
+pub fn explanation(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter().find(|e| e.0 == code).map(|e| e.1)
+}
+
+/// Returns every code which has an explanation, in order.
+pub fn codes() -> Vec<&'static str> {
+    EXPLANATIONS.iter().map(|e| e.0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::*;
+    use common::*;
+    use lexer::*;
+    use parser::*;
+    use passes::*;
+    use passes::evaluate::*;
+    use passes::lint::*;
+    use codegen::*;
+    use bytecode::serialize::*;
+    use codegen::wasm::decode::*;
+    use codegen::wasm::validate::*;
+    use codegen::wasm::module::*;
+    use repl::*;
+
+    fn code<T: ErrorKind>(kind: T) -> &'static str {
+        kind.code()
+    }
+
+    #[test]
+    fn every_code_is_explained_once() {
+        let codes = vec![
+            code(LexerErrorKind::InvalidCharacter('.')), code(LexerErrorKind::InvalidInteger(String::new())),
+            code(LexerErrorKind::UnterminatedBlockComment),
+            code(ParseErrorKind::ExpectedBinaryOperator(TokenKind::OpenParen)),
+            code(ParseErrorKind::InvalidPrefixExpressionTerm(TokenKind::OpenParen)), code(ParseErrorKind::UnexpectedEndOfInput),
+            code(ParseErrorKind::UnclosedParen), code(ParseErrorKind::EmptyExpr),
+            code(PassErrorKind::VariableDoesNotExist { variable_name: String::new(), suggestions: Vec::new() }),
+            code(EvaluateErrorKind::IndexOutOfRange(0)), code(EvaluateErrorKind::DivisionByZero),
+            code(EvaluateErrorKind::IntegerOverflow(BinaryOp::Add)),
+            code(CodegenErrorKind::UnresolvedVariable(String::new())), code(CodegenErrorKind::InvalidGlobalIndex(0)),
+            code(CodegenErrorKind::SyntaxError),
+            code(LoadErrorKind::InvalidMagic), code(LoadErrorKind::UnsupportedVersion(0)), code(LoadErrorKind::UnexpectedEndOfData),
+            code(LoadErrorKind::InvalidConstantTag(0)), code(LoadErrorKind::InvalidOpCode(0)),
+            code(LoadErrorKind::ConstantIndexOutOfRange(0)), code(LoadErrorKind::StackUnderflow { instruction: 0 }),
+            code(LoadErrorKind::InvalidFinalStackDepth(0)), code(LoadErrorKind::TrailingData(0)),
+            code(DecodeErrorKind::InvalidMagic), code(DecodeErrorKind::UnsupportedVersion(0)),
+            code(DecodeErrorKind::UnexpectedEndOfData), code(DecodeErrorKind::InvalidLeb128), code(DecodeErrorKind::InvalidUtf8),
+            code(DecodeErrorKind::UnsupportedSection(0)), code(DecodeErrorKind::SectionOutOfOrder(0)),
+            code(DecodeErrorKind::SectionSizeMismatch(0)), code(DecodeErrorKind::InvalidValueType(0)),
+            code(DecodeErrorKind::InvalidFunctionType(0)), code(DecodeErrorKind::UnsupportedImportKind(0)),
+            code(DecodeErrorKind::UnsupportedExportKind(0)), code(DecodeErrorKind::UnsupportedBlockType(0)),
+            code(DecodeErrorKind::InvalidMutability(0)), code(DecodeErrorKind::UnsupportedOpCode(0)),
+            code(DecodeErrorKind::FunctionCountMismatch { functions: 0, bodies: 0 }), code(DecodeErrorKind::TooManyLocals),
+            code(ValidationErrorKind::InvalidTypeIndex(0)), code(ValidationErrorKind::InvalidFunctionIndex(0)),
+            code(ValidationErrorKind::InvalidGlobalIndex(0)), code(ValidationErrorKind::InvalidLocalIndex(0)),
+            code(ValidationErrorKind::ImmutableGlobal(0)), code(ValidationErrorKind::TypeMismatch { expected: ValType::I32, found: None }),
+            code(ValidationErrorKind::UnexpectedValues(0)), code(ValidationErrorKind::UnbalancedControl),
+            code(ValidationErrorKind::InvalidConstantExpression), code(ValidationErrorKind::DuplicateExport(String::new())),
+            code(SessionErrorKind::InvalidLet),
+            code(Lint::UnusedResult), code(Lint::DivisionByZero), code(Lint::UnusedLet), code(Lint::ShadowedGlobal),
+        ];
+        assert_eq!(codes, super::codes());
+        for code in codes {
+            assert!(explanation(code).unwrap().ends_with('\n'));
+        }
+        assert_eq!(None, explanation("E9999"));
+    }
+}
@@ -0,0 +1,241 @@
+/*
+  Reads definitions of global variables, for supplying an `EnvDef` from the command line or a file.
+
+  A definition is written `name=value`, where the type of the value is inferred from its literal form by
+  `Value::parse`.  A globals file is either a JSON object whose members are the globals:
+
+      { "customerId": 42, "discount": -5 }
+
+  or has one definition per line, with blank lines and lines starting with `#` ignored:
+
+      # Production defaults
+      customerId = 42
+      discount = -5
+
+  The binaries take definitions as `--define name=value` and files as `--globals <file>`, read by
+  `globals_from_arg`.
+*/
+
+use std::fmt;
+use std::fs;
+use std::io;
+use json::*;
+use lexer::{is_identifier_start, is_identifier_char};
+use repl::KEYWORDS;
+use value::*;
+
+/// An invalid definition.  `line` starts at 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalsError {
+    pub line: u32,
+    pub message: String
+}
+
+impl fmt::Display for GlobalsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The exit codes of the binaries for invalid arguments, invalid data and files which cannot be read, as in
+/// `sysexits.h`.
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_DATA_ERROR: i32 = 65;
+pub const EXIT_IO_ERROR: i32 = 66;
+
+/// Why the globals of a command-line argument could not be read.
+#[derive(Debug)]
+pub enum LoadGlobalsError {
+    /// An option is not followed by its value.
+    MissingValue(String),
+    /// The value of `--define` is not a valid definition.
+    InvalidDefinition(String),
+    /// The file `path` cannot be read.
+    Io { path: String, error: io::Error },
+    /// The file `path` is not a valid globals file.
+    InvalidFile { path: String, error: GlobalsError }
+}
+
+impl LoadGlobalsError {
+    /// The code a binary exits with for the error.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            LoadGlobalsError::MissingValue(_) | LoadGlobalsError::InvalidDefinition(_) => EXIT_USAGE,
+            LoadGlobalsError::Io { .. } => EXIT_IO_ERROR,
+            LoadGlobalsError::InvalidFile { .. } => EXIT_DATA_ERROR
+        }
+    }
+}
+
+impl fmt::Display for LoadGlobalsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadGlobalsError::MissingValue(ref option) => write!(f, "'{}' requires a value", option),
+            LoadGlobalsError::InvalidDefinition(ref message) => write!(f, "invalid --define: {}", message),
+            LoadGlobalsError::Io { ref path, ref error } => write!(f, "cannot read '{}': {}", path, error),
+            LoadGlobalsError::InvalidFile { ref path, ref error } => write!(f, "{}:{}: {}", path, error.line, error.message)
+        }
+    }
+}
+
+/// Returns an error unless `name` can be referred to by an expression.  The keywords of the REPL cannot, as an
+/// input starting with one is not an expression.
+fn check_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    match chars.next() {
+        _ if KEYWORDS.contains(&name) => Err(format!("'{}' is a keyword, so it cannot be a variable name", name)),
+        Some(c) if is_identifier_start(c) && chars.all(is_identifier_char) => Ok(()),
+        _ => Err(format!("'{}' is not a valid variable name", name))
+    }
+}
+
+/// Parses a definition of the form `name=value`.  Whitespace around the name and value is ignored.
+pub fn parse_definition(text: &str) -> Result<(String, Value), String> {
+    let (name, value) = match text.find('=') {
+        Some(index) => (text[..index].trim(), text[index + 1..].trim()),
+        None => return Err(format!("'{}' is not of the form name=value", text))
+    };
+    check_name(name)?;
+    Ok((String::from(name), Value::parse(value)?))
+}
+
+/// Parses the definitions of a globals file, in the order they are written.
+pub fn parse_globals(source: &str) -> Result<Vec<(String, Value)>, GlobalsError> {
+    if source.trim_start().starts_with('{') {
+        parse_json_globals(source)
+    } else {
+        parse_line_globals(source)
+    }
+}
+
+/// Reads the definitions of the globals file at `path`.
+pub fn load_file(path: &str) -> Result<Vec<(String, Value)>, LoadGlobalsError> {
+    let source = fs::read_to_string(path).map_err(|error| LoadGlobalsError::Io { path: String::from(path), error })?;
+    parse_globals(&source).map_err(|error| LoadGlobalsError::InvalidFile { path: String::from(path), error })
+}
+
+/// Reads the globals defined by the command-line argument `arg` if it is `--define <definition>`,
+/// `--define=<definition>` or `--globals <file>`, taking the value of the option from `args`.  Returns `None`
+/// for any other argument.
+pub fn globals_from_arg<I: Iterator<Item = String>>(arg: &str, args: &mut I) -> Option<Result<Vec<(String, Value)>, LoadGlobalsError>> {
+    let mut value = || args.next().ok_or_else(|| LoadGlobalsError::MissingValue(String::from(arg)));
+    let definition = match arg {
+        "--globals" => return Some(value().and_then(|path| load_file(&path))),
+        "--define" => value(),
+        _ => Ok(String::from(arg.strip_prefix("--define=")?))
+    };
+    Some(definition.and_then(|definition| {
+        parse_definition(&definition).map(|global| vec![global]).map_err(LoadGlobalsError::InvalidDefinition)
+    }))
+}
+
+fn parse_line_globals(source: &str) -> Result<Vec<(String, Value)>, GlobalsError> {
+    let mut globals = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let definition = parse_definition(line).map_err(|message| GlobalsError { line: i as u32 + 1, message })?;
+        globals.push(definition);
+    }
+    Ok(globals)
+}
+
+fn parse_json_globals(source: &str) -> Result<Vec<(String, Value)>, GlobalsError> {
+    let line_of = |offset: usize| source[..offset.min(source.len())].matches('\n').count() as u32 + 1;
+    let json = Json::parse(source).map_err(|e| GlobalsError { line: line_of(e.offset), message: e.message })?;
+    let members = json_members(&json).map_err(|message| GlobalsError { line: 1, message })?;
+    members.iter().map(|m| {
+        //The members have no positions, so the line of the first occurrence of the name is reported.
+        let line = line_of(source.find(&format!("\"{}\"", m.0)).unwrap_or(0));
+        global_from_json(&m.0, &m.1).map_err(|message| GlobalsError { line, message })
+    }).collect()
+}
+
+/// Reads the globals of `json`, an object whose members are the globals, as in a JSON globals file.
+pub fn globals_from_json(json: &Json) -> Result<Vec<(String, Value)>, String> {
+    json_members(json)?.iter().map(|m| global_from_json(&m.0, &m.1)).collect()
+}
+
+fn json_members(json: &Json) -> Result<&[(String, Json)], String> {
+    match *json {
+        Json::Object(ref members) => Ok(members),
+        _ => Err(String::from("Expected a JSON object"))
+    }
+}
+
+fn global_from_json(name: &str, value: &Json) -> Result<(String, Value), String> {
+    check_name(name)?;
+    let value = match *value {
+        //Parsing the number's text rejects fractions and numbers out of range.
+        Json::Number(n) => Value::parse(&n.to_string())?,
+        ref other => return Err(format!("The value of '{}' must be an integer, not {}", name, other))
+    };
+    Ok((String::from(name), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(name: &str, value: i32) -> (String, Value) {
+        (String::from(name), Value::Int32(value))
+    }
+
+    #[test]
+    fn parse_definitions() {
+        assert_eq!(Ok(int("abc1", -7)), parse_definition(" abc1 = -7"));
+        assert!(parse_definition("abc").is_err());
+        assert!(parse_definition("1abc=1").is_err());
+        assert_eq!(Ok(int("_a_1", 1)), parse_definition("_a_1=1"));
+        assert!(parse_definition("a-b=1").is_err());
+        assert!(parse_definition("a=1.5").is_err());
+        assert_eq!(Err(String::from("'let' is a keyword, so it cannot be a variable name")), parse_definition("let=1"));
+        assert!(parse_definition("exit=1").is_err());
+        assert_eq!(Ok(int("lets", 1)), parse_definition("lets=1"));
+    }
+
+    #[test]
+    fn parse_globals_lines() {
+        assert_eq!(
+            Ok(vec![int("a", 1), int("b", -2), int("a", 3)]),
+            parse_globals("# comment\na=1\n\n  b = -2\r\na=3\n"));
+        assert_eq!(Err(GlobalsError { line: 3, message: String::from("'x' is not of the form name=value") }), parse_globals("a=1\n\nx\n"));
+    }
+
+    #[test]
+    fn parse_globals_json() {
+        assert_eq!(Ok(vec![int("a", 1), int("b", -2)]), parse_globals(" {\"a\": 1,\n \"b\": -2.0}"));
+        assert_eq!(Ok(Vec::new()), parse_globals("{}"));
+        assert_eq!(2, parse_globals("{\"a\": 1,\n \"b\": 1.5}").unwrap_err().line);
+        assert_eq!(2, parse_globals("{\"a\": 1,\n \"b\": \"x\"}").unwrap_err().line);
+        assert_eq!(3, parse_globals("{\"a\": 1,\n\n ]").unwrap_err().line);
+        assert_eq!(1, parse_globals("{\"a b\": 1}").unwrap_err().line);
+        assert_eq!(2, parse_globals("{\"a\": 1,\n \"let\": 2}").unwrap_err().line);
+        assert!(parse_globals("{\"a\": 2147483648}").is_err());
+    }
+
+    #[test]
+    fn read_globals_from_json() {
+        let json = Json::object(vec![("a", Json::Number(1.0)), ("b", Json::Number(-2.0))]);
+        assert_eq!(Ok(vec![int("a", 1), int("b", -2)]), globals_from_json(&json));
+        assert_eq!(Err(String::from("Expected a JSON object")), globals_from_json(&Json::Array(Vec::new())));
+        assert!(globals_from_json(&Json::object(vec![("a", Json::Bool(true))])).is_err());
+    }
+
+    #[test]
+    fn globals_from_args() {
+        let from_arg = |args: &[&str]| {
+            let mut args = args.iter().map(|a| String::from(*a));
+            let arg = args.next().unwrap();
+            globals_from_arg(&arg, &mut args).map(|result| result.map_err(|e| (e.exit_code(), e.to_string())))
+        };
+        assert_eq!(Some(Ok(vec![int("a", 1)])), from_arg(&["--define", "a=1", "b=2"]));
+        assert_eq!(Some(Ok(vec![int("a", 2)])), from_arg(&["--define=a=2"]));
+        assert_eq!(None, from_arg(&["--defined"]));
+        assert_eq!(Some(Err((EXIT_USAGE, String::from("'--define' requires a value")))), from_arg(&["--define"]));
+        assert_eq!(Some(Err((EXIT_USAGE, String::from("invalid --define: '1' is not a valid variable name")))), from_arg(&["--define=1=2"]));
+        assert_eq!(Some(EXIT_IO_ERROR), from_arg(&["--globals", "/nonexistent/globals"]).unwrap().err().map(|e| e.0));
+    }
+}
@@ -8,12 +8,23 @@ use std::collections::VecDeque;
 pub struct CharsReader<'a> {
     input_chars: Chars<'a>,
     lookahead: VecDeque<char>,
-    loc: Option<Location>
+    loc: Option<Location>,
+    last: Option<char>,
+    start: Location
 }
 
 impl <'a> CharsReader<'a> {
     pub fn new(input_chars: Chars<'a>) -> CharsReader<'a> {
-        CharsReader { input_chars, lookahead: VecDeque::new(), loc: None }
+        CharsReader::new_at(input_chars, Location::start())
+    }
+
+    /// Creates a reader whose first character is at `start`.
+    pub fn new_at(input_chars: Chars<'a>, start: Location) -> CharsReader<'a> {
+        CharsReader { input_chars, lookahead: VecDeque::new(), loc: None, last: None, start }
+    }
+
+    pub fn start(&self) -> Location {
+        self.start
     }
 
     pub fn loc(&self) -> Location {
@@ -23,6 +34,14 @@ impl <'a> CharsReader<'a> {
         }
     }
 
+    /// Returns the location of the next character, or of the end of input if there are no more.
+    pub fn next_loc(&self) -> Location {
+        match (self.loc, self.last) {
+            (Some(loc), Some(last)) => loc.advance(last),
+            _ => self.start
+        }
+    }
+
     pub fn next(&mut self) -> Option<char> {
         //Note: prime() will do nothing if end of stream has been previously reached.
         self.prime(1);
@@ -30,10 +49,8 @@ impl <'a> CharsReader<'a> {
         match self.lookahead.pop_front() {
             None => None,
             Some(c) => {
-                self.loc = Some(match self.loc {
-                    None => Location::start(),
-                    Some(ref l) => if c == '\n' { l.next_line() } else { l.next() }
-                });
+                self.loc = Some(self.next_loc());
+                self.last = Some(c);
                 Some(c)
             }
         }
@@ -88,24 +105,25 @@ mod reader_tests {
 
         let chr = reader.next().unwrap();
         assert_eq!('a', chr);
-        assert_eq!(Location::new(1, 1), reader.loc());
+        assert_eq!(Location::new(1, 1, 0), reader.loc());
 
         let chr = reader.next().unwrap();
         assert_eq!('b', chr);
-        assert_eq!(Location::new(1, 2), reader.loc());
+        assert_eq!(Location::new(1, 2, 1), reader.loc());
 
         let chr = reader.next().unwrap();
         assert_eq!('\n', chr);
-        assert_eq!(Location::new(2, 0), reader.loc());
+        assert_eq!(Location::new(1, 3, 2), reader.loc());
 
         let chr = reader.next().unwrap();
         assert_eq!('c', chr);
-        assert_eq!(Location::new(2, 1), reader.loc());
+        assert_eq!(Location::new(2, 1, 3), reader.loc());
 
         let chr = reader.next().unwrap();
         assert_eq!('d', chr);
-        assert_eq!(Location::new(2, 2), reader.loc());
+        assert_eq!(Location::new(2, 2, 4), reader.loc());
 
         assert!(reader.next().is_none());
+        assert_eq!(Location::new(2, 3, 5), reader.next_loc());
     }
 }
@@ -0,0 +1,405 @@
+/*
+  A minimal JSON document model, used to emit machine-readable output such as diagnostics and to read
+  configuration such as globals files.
+
+  Objects keep their members in insertion order so that output is stable.
+*/
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(members.into_iter().map(|(k, v)| (String::from(k), v)).collect())
+    }
+
+    pub fn string(text: &str) -> Json {
+        Json::String(String::from(text))
+    }
+
+    /// Returns the value of the member `key` if this is an object which has one.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) => members.iter().find(|m| m.0 == key).map(|m| &m.1),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match *self {
+            Json::Number(n) => Some(n),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match *self {
+            Json::Array(ref items) => Some(items),
+            _ => None
+        }
+    }
+}
+
+/// A syntax error in JSON text.  `offset` is the byte offset at which it was detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+    pub message: String,
+    pub offset: usize
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte offset {}", self.message, self.offset)
+    }
+}
+
+impl Json {
+    /// Parses `text`, which must contain exactly one JSON value surrounded by optional whitespace.
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut reader = JsonReader { text, pos: 0, depth: 0 };
+        let value = reader.read_value()?;
+        reader.skip_whitespace();
+        if reader.pos < text.len() {
+            return Err(reader.error("Unexpected text after the value"));
+        }
+        Ok(value)
+    }
+}
+
+/// The most arrays and objects a value may be nested in, so that reading deeply nested text cannot overflow
+/// the stack.
+const MAX_DEPTH: usize = 128;
+
+struct JsonReader<'a> {
+    text: &'a str,
+    pos: usize,
+    /// The number of arrays and objects being read.
+    depth: usize
+}
+
+impl <'a> JsonReader<'a> {
+    fn error(&self, message: &str) -> JsonError {
+        JsonError { message: String::from(message), offset: self.pos }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", c)))
+        }
+    }
+
+    fn read_keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonError> {
+        if self.text[self.pos..].starts_with(keyword) {
+            self.pos += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("Invalid value"))
+        }
+    }
+
+    /// Starts reading an array or object.  Returns an error if it would be nested too deeply.
+    fn enter(&mut self) -> Result<(), JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("Too deeply nested"));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn read_value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.read_keyword("null", Json::Null),
+            Some('t') => self.read_keyword("true", Json::Bool(true)),
+            Some('f') => self.read_keyword("false", Json::Bool(false)),
+            Some('"') => self.read_string().map(Json::String),
+            Some('[') => {
+                self.enter()?;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.read_value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {},
+                        Some(']') => {
+                            self.depth -= 1;
+                            return Ok(Json::Array(items));
+                        },
+                        _ => return Err(self.error("Expected ',' or ']'"))
+                    }
+                }
+            },
+            Some('{') => {
+                self.enter()?;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some('"') {
+                        return Err(self.error("Expected a member name"));
+                    }
+                    let key = self.read_string()?;
+                    self.expect(':')?;
+                    members.push((key, self.read_value()?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {},
+                        Some('}') => {
+                            self.depth -= 1;
+                            return Ok(Json::Object(members));
+                        },
+                        _ => return Err(self.error("Expected ',' or '}'"))
+                    }
+                }
+            },
+            Some(c) if c == '-' || c.is_ascii_digit() => self.read_number(),
+            Some(_) => Err(self.error("Invalid value")),
+            None => Err(self.error("Unexpected end of input"))
+        }
+    }
+
+    fn read_number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+                break;
+            }
+            self.pos += 1;
+        }
+        //Rust accepts some numbers JSON doesn't, such as "1." and "01".
+        let text = &self.text[start..self.pos];
+        let digits = text.trim_start_matches('-');
+        let leading_zero = digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit());
+        let invalid = !digits.starts_with(|c: char| c.is_ascii_digit())
+            || leading_zero || text.contains(".e") || text.contains(".E") || text.ends_with('.');
+        match text.parse::<f64>() {
+            Ok(n) if !invalid => Ok(Json::Number(n)),
+            _ => Err(JsonError { message: format!("Invalid number '{}'", text), offset: start })
+        }
+    }
+
+    /// Reads a string, starting at its opening quote.
+    fn read_string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error("Unterminated string")),
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.read_unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape sequence"))
+                    };
+                    s.push(c);
+                },
+                Some(c) if (c as u32) < 0x20 => return Err(self.error("Control character in string")),
+                Some(c) => s.push(c)
+            }
+        }
+    }
+
+    /// Reads the hex digits of a `\\u` escape, and the second half of a surrogate pair if there is one.
+    fn read_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.read_hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) && self.text[self.pos..].starts_with("\\u") {
+            self.pos += 2;
+            let low = self.read_hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("Invalid surrogate pair"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        ::std::char::from_u32(code).ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn read_hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self.text.get(self.pos..self.pos + 4).ok_or_else(|| self.error("Invalid unicode escape"))?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(n: u32) -> Json {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as f64)
+    }
+}
+
+impl <T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, |v| v.into())
+    }
+}
+
+impl <'a> From<&'a str> for Json {
+    fn from(text: &'a str) -> Json {
+        Json::string(text)
+    }
+}
+
+fn write_string(text: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Writes the value as compact JSON, without any whitespace.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            //JSON has no representation of infinity or NaN.
+            Json::Number(n) if !n.is_finite() => write!(f, "null"),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(ref s) => write_string(s, f),
+            Json::Array(ref items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(ref members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(key, f)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_display() {
+        let json = Json::object(vec![
+            ("a", Json::Array(vec![Json::Null, Json::Bool(true), Json::from(12u32), Json::Number(-1.5)])),
+            ("b \"quoted\"", Json::string("line\n\ttab \\ \u{1} \u{e9}")),
+            ("c", Json::Object(Vec::new())),
+            ("d", Json::from(None::<u32>))
+        ]);
+        assert_eq!(
+            "{\"a\":[null,true,12,-1.5],\"b \\\"quoted\\\"\":\"line\\n\\ttab \\\\ \\u0001 \u{e9}\",\"c\":{},\"d\":null}",
+            json.to_string());
+        assert_eq!(Some(&Json::Object(Vec::new())), json.get("c"));
+        assert_eq!(None, json.get("e"));
+        assert_eq!(Some(-1.5), json.get("a").and_then(Json::as_array).and_then(|a| a[3].as_number()));
+        assert_eq!(None, json.get("c").and_then(Json::as_number));
+        assert_eq!(Some("line\n\ttab \\ \u{1} \u{e9}"), json.get("b \"quoted\"").and_then(Json::as_str));
+    }
+
+    #[test]
+    fn json_parse() {
+        assert_eq!(
+            Ok(Json::object(vec![
+                ("a", Json::Array(vec![Json::Null, Json::Bool(true), Json::Bool(false), Json::Number(12.0), Json::Number(-1.5e2)])),
+                ("b", Json::string("q\" \\ / \u{8}\u{c}\n\r\t \u{e9} \u{1f600}")),
+                ("c", Json::Object(Vec::new())),
+                ("d", Json::Array(Vec::new()))
+            ])),
+            Json::parse(" {\"a\" : [null, true,false, 12, -1.5E2], \"b\":\"q\\\" \\\\ \\/ \\b\\f\\n\\r\\t \\u00e9 \\ud83d\\ude00\",\n\"c\":{}, \"d\": [ ] } "));
+        let json = Json::object(vec![("x", Json::string("\u{1}\"")), ("y", Json::Array(vec![Json::Number(0.25)]))]);
+        assert_eq!(Ok(json.clone()), Json::parse(&json.to_string()));
+    }
+
+    #[test]
+    fn json_parse_errors() {
+        for text in &["", "nul", "[1,]", "[1 2]", "{\"a\" 1}", "{a: 1}", "\"abc", "\"\\x\"", "01", "1.", "-", "1 2", "\"\\ud83d\\u0041\""] {
+            assert!(Json::parse(text).is_err(), "{}", text);
+        }
+        assert_eq!(Err(JsonError { message: String::from("Expected ',' or ']'"), offset: 5 }), Json::parse("[1, 2"));
+
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(128)).is_ok());
+        assert_eq!(Err(JsonError { message: String::from("Too deeply nested"), offset: 128 }), Json::parse(&nested(129)));
+        assert_eq!("Too deeply nested", Json::parse(&"[{\"a\":".repeat(100000)).unwrap_err().message);
+    }
+}
@@ -10,7 +10,8 @@ use common::*;
 #[derive(Debug, Clone, PartialEq)]
 pub enum LexerErrorKind {
     InvalidCharacter(char),
-    InvalidInteger(String)
+    InvalidInteger(String),
+    UnterminatedBlockComment
 }
 
 impl ErrorKind for LexerErrorKind {
@@ -18,10 +19,27 @@ impl ErrorKind for LexerErrorKind {
         match self {
             &LexerErrorKind::InvalidInteger(ref text) => format!("Invalid integer: '{}'", text),
             &LexerErrorKind::InvalidCharacter(ref chr) => format!("Invalid character: '{}'", chr),
+            &LexerErrorKind::UnterminatedBlockComment => String::from("Unterminated block comment"),
         }
     }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            LexerErrorKind::InvalidCharacter(_) => "E0101",
+            LexerErrorKind::InvalidInteger(_) => "E0102",
+            LexerErrorKind::UnterminatedBlockComment => "E0103",
+        }
+    }
+}
+
+impl fmt::Display for LexerErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
 }
 
+impl ::std::error::Error for LexerErrorKind {}
+
 pub type LexerError = SourceError<LexerErrorKind>;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -32,6 +50,8 @@ pub enum TokenKind {
     // operators that may be either unary or n-ary depending on context.  Good thing I don't like
     // context sensitivity.
     BinaryOperator(BinaryOp),
+    OpenParen,
+    CloseParen,
 }
 
 impl fmt::Display for TokenKind {
@@ -45,7 +65,9 @@ impl fmt::Display for TokenKind {
                 &BinaryOp::Mul => write!(f, "operator *"),
                 &BinaryOp::Div => write!(f, "operator /"),
                 &BinaryOp::Mod => write!(f, "operator %"),
-            }
+            },
+            &TokenKind::OpenParen => write!(f, "'('"),
+            &TokenKind::CloseParen => write!(f, "')'"),
         }
     }
 }
@@ -77,27 +99,159 @@ fn is_white(chr: char) -> bool {
     }
 }
 
-fn is_digit(chr: char) -> bool {
+pub fn is_digit(chr: char) -> bool {
     chr >= '0' && chr <= '9'
 }
 
-fn is_letter(chr: char) -> bool {
+pub fn is_letter(chr: char) -> bool {
     (chr >= 'a' && chr <= 'z') || (chr >= 'A' && chr <= 'Z')
 }
 
+/// Identifiers start with a letter or `_`, which may be followed by letters, digits and `_`.
+pub fn is_identifier_start(chr: char) -> bool {
+    is_letter(chr) || chr == '_'
+}
+
+pub fn is_identifier_char(chr: char) -> bool {
+    is_identifier_start(chr) || is_digit(chr)
+}
+
+/// What a token starting with a character is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenStart {
+    Operator(BinaryOp),
+    OpenParen,
+    CloseParen,
+    /// An integer literal, which continues with `is_digit` characters.
+    Integer,
+    /// An identifier, which continues with `is_identifier_char` characters.
+    Identifier,
+    /// The character does not start any token.
+    Invalid
+}
+
+/// Returns what a token starting with `chr` is.  Comments must have been skipped, as `/` is an operator.
+pub fn token_start(chr: char) -> TokenStart {
+    match chr {
+        '+' => TokenStart::Operator(BinaryOp::Add),
+        '-' => TokenStart::Operator(BinaryOp::Sub),
+        '*' => TokenStart::Operator(BinaryOp::Mul),
+        '/' => TokenStart::Operator(BinaryOp::Div),
+        '%' => TokenStart::Operator(BinaryOp::Mod),
+        '(' => TokenStart::OpenParen,
+        ')' => TokenStart::CloseParen,
+        _ if is_digit(chr) => TokenStart::Integer,
+        _ if is_identifier_start(chr) => TokenStart::Identifier,
+        _ => TokenStart::Invalid
+    }
+}
+
+/// The characters a tokenizer reads.  The rules for comments are written against this, so that `Lexer` and the
+/// lossless tokenizer of `syntax` share them.
+pub trait CharSource {
+    /// Returns the character `n` characters after the next, without reading it.
+    fn peek_n(&mut self, n: u32) -> Option<char>;
+    fn next(&mut self) -> Option<char>;
+}
+
+impl <'a> CharSource for CharsReader<'a> {
+    fn peek_n(&mut self, n: u32) -> Option<char> {
+        CharsReader::peek_n(self, n)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        CharsReader::next(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentKind {
+    Line,
+    Block
+}
+
+/// Returns the kind of the comment which starts at the next character of `chars`, if any.
+pub fn comment_start<S: CharSource>(chars: &mut S) -> Option<CommentKind> {
+    match (chars.peek_n(0), chars.peek_n(1)) {
+        (Some('/'), Some('/')) => Some(CommentKind::Line),
+        (Some('/'), Some('*')) => Some(CommentKind::Block),
+        _ => None
+    }
+}
+
+/// Reads a `//` comment up to, but not including, the line ending, which is either `\n` or `\r\n`.
+pub fn read_line_comment<S: CharSource>(chars: &mut S) {
+    loop {
+        match (chars.peek_n(0), chars.peek_n(1)) {
+            (None, _) | (Some('\n'), _) | (Some('\r'), Some('\n')) => return,
+            _ => {
+                chars.next();
+            }
+        }
+    }
+}
+
+/// Reads a `/* */` comment.  Block comments nest, so `/* /* */ */` is a single comment.  Returns false if the
+/// input ends before the comment does.
+pub fn read_block_comment<S: CharSource>(chars: &mut S) -> bool {
+    chars.next();
+    chars.next();
+    let mut depth = 1;
+    while depth > 0 {
+        match (chars.next(), chars.peek_n(0)) {
+            (Some('/'), Some('*')) => {
+                chars.next();
+                depth += 1;
+            },
+            (Some('*'), Some('/')) => {
+                chars.next();
+                depth -= 1;
+            },
+            (Some(_), _) => {},
+            (None, _) => return false
+        }
+    }
+    true
+}
+
 pub struct Lexer<'a> {
     reader: CharsReader<'a>,
     lookahead: VecDeque<LexResult>,
+    file: FileId,
 }
 
 impl <'a> Lexer<'a> {
     pub fn new(chars: Chars<'a>) -> Lexer<'a> {
+        Lexer::new_with_file(chars, FileId::NONE)
+    }
+
+    /// Creates a lexer whose spans refer to `file`.
+    pub fn new_with_file(chars: Chars<'a>, file: FileId) -> Lexer<'a> {
+        Lexer::new_at(chars, file, Location::start())
+    }
+
+    /// Creates a lexer for text starting at `start` within `file`, such as the remainder of a line.
+    pub fn new_at(chars: Chars<'a>, file: FileId, start: Location) -> Lexer<'a> {
         return Lexer {
-            reader: CharsReader::new(chars),
+            reader: CharsReader::new_at(chars, start),
             lookahead: VecDeque::new(),
+            file
         }
     }
 
+    /// The location of the first character.
+    pub fn start(&self) -> Location {
+        self.reader.start()
+    }
+
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    fn span(&self, start: Location, end: Location) -> Span {
+        Span::new(self.file, start, end)
+    }
+
     pub fn has_more(&mut self) -> bool {
 
         return if !self.lookahead.is_empty() {
@@ -107,8 +261,13 @@ impl <'a> Lexer<'a> {
                 true
             }
         } else {
-            self.eat_white();
-            self.reader.has_more()
+            match self.eat_trivia() {
+                Some(err) => {
+                    self.lookahead.push_back(LexResult::Err(err));
+                    true
+                },
+                None => self.reader.has_more()
+            }
         }
     }
 
@@ -123,6 +282,35 @@ impl <'a> Lexer<'a> {
         self.peek_n(0)
     }
 
+    /// Reads the character `chr` if it is next after whitespace and comments, and returns its span.  This is for
+    /// punctuation which is not a token of the language, such as the `=` of a definition in the REPL.  Must not be
+    /// called once a token has been peeked.
+    pub fn next_char(&mut self, chr: char) -> Option<Span> {
+        debug_assert!(self.lookahead.is_empty());
+        if let Some(err) = self.eat_trivia() {
+            self.lookahead.push_back(LexResult::Err(err));
+            return None;
+        }
+        if self.reader.peek() != Some(chr) {
+            return None;
+        }
+        self.reader.next();
+        let loc = self.reader.loc();
+        Some(self.span(loc, loc))
+    }
+
+    /// Reads every remaining token, stopping at the first error.
+    pub fn tokens(&mut self) -> Result<Vec<Token>, LexerError> {
+        let mut tokens = Vec::new();
+        loop {
+            match self.next() {
+                LexResult::Ok(token) => tokens.push(token),
+                LexResult::EndOfInput(_) => return Ok(tokens),
+                LexResult::Err(error) => return Err(error)
+            }
+        }
+    }
+
     pub fn peek_n(&mut self, n: u32) -> LexResult {
         self.prime(n + 1);
         self.lookahead[n as usize].clone() 
@@ -138,98 +326,82 @@ impl <'a> Lexer<'a> {
     /// Stuffs a LexResult into the lookahead.
     /// If the end of input has been reached, stuffs a LexResult::EndOfInput.
     fn read_next_token(&mut self) -> LexResult {
-        //Note:  eat_white() gracefully handles EOF
-        self.eat_white();
-        if !self.reader.has_more() {
-            LexResult::EndOfInput(self.reader.loc())
+        //Note:  eat_trivia() gracefully handles EOF
+        if let Some(err) = self.eat_trivia() {
+            LexResult::Err(err)
+        } else if !self.reader.has_more() {
+            LexResult::EndOfInput(self.reader.next_loc())
         } else {
-            if let Some(token) = self.read_single_char_token() {
-                LexResult::Ok(token)
-            } else if let Some(token) = self.read_literal_number() {
-                LexResult::Ok(token)
-            } else if let Some(token) = self.read_identifier() {
-                LexResult::Ok(token)
-            } else {
-                //Note:  if self.reader.has_more() then self.reader.next() shouldn't ever return None.
-                LexResult::Err(
-                    LexerError::new_with_location(
-                        LexerErrorKind::InvalidCharacter(self.reader.next().unwrap()),
-                        self.reader.loc())) 
-            }
-        }
-    }
-
-    fn eat_white(&mut self) {
-        while let Some(c) = self.reader.peek() {
-            if !is_white(c) {
-                break;
-            }
+            //Note:  if self.reader.has_more() then self.reader.peek() shouldn't ever return None.
+            let chr = self.reader.peek().unwrap();
+            let kind = match token_start(chr) {
+                TokenStart::Operator(op) => TokenKind::BinaryOperator(op),
+                TokenStart::OpenParen => TokenKind::OpenParen,
+                TokenStart::CloseParen => TokenKind::CloseParen,
+                TokenStart::Integer => return self.read_literal_number(),
+                TokenStart::Identifier => {
+                    let (text, span) = self.read_while(is_identifier_char);
+                    return LexResult::Ok(Token::new(TokenKind::Identifier(text), span));
+                },
+                TokenStart::Invalid => {
+                    self.reader.next();
+                    let loc = self.reader.loc();
+                    return LexResult::Err(LexerError::new_with_span(LexerErrorKind::InvalidCharacter(chr), self.span(loc, loc)));
+                }
+            };
             self.reader.next();
+            let loc = self.reader.loc();
+            LexResult::Ok(Token::new(kind, self.span(loc, loc)))
         }
     }
 
-    fn read_single_char_token(&mut self) -> Option<Token> {
-        let kind = match self.reader.peek() {
-            Some('+') => Some(TokenKind::BinaryOperator(BinaryOp::Add)),
-            Some('-') => Some(TokenKind::BinaryOperator(BinaryOp::Sub)),
-            Some('*') => Some(TokenKind::BinaryOperator(BinaryOp::Mul)),
-            Some('/') => Some(TokenKind::BinaryOperator(BinaryOp::Div)),
-            Some('%') => Some(TokenKind::BinaryOperator(BinaryOp::Mod)),
-            _ => None
-        };
-        match kind {
-            Some(kind) => {
-                self.reader.next();
-                Some(Token::new(kind, Span::from_locations(self.reader.loc(), self.reader.loc())))
-            }
-            None => None
-        }
-    }
-
-    fn read_literal_number(&mut self) -> Option<Token> {
-        if let Some((text, span)) = self.read_token(|c| is_digit(c), |c| is_digit(c)) {
-            // For now we don't care too much about error handling.
-            let maybe_int = text.parse::<i32>();
-            match maybe_int {
-                Ok(i) => Some(Token::new(TokenKind::LiteralInt32(i), span)),
-                Err(_e) => panic!("Crap, it wasn't an integer!")
+    /// Skips whitespace and comments.  Returns an error if a block comment is not terminated.
+    fn eat_trivia(&mut self) -> Option<LexerError> {
+        loop {
+            match comment_start(&mut self.reader) {
+                Some(CommentKind::Line) => read_line_comment(&mut self.reader),
+                Some(CommentKind::Block) => {
+                    let start = self.reader.next_loc();
+                    if !read_block_comment(&mut self.reader) {
+                        return Some(LexerError::new_with_span(
+                            LexerErrorKind::UnterminatedBlockComment,
+                            self.span(start, self.reader.loc())));
+                    }
+                },
+                None => match self.reader.peek() {
+                    Some(c) if is_white(c) => {
+                        self.reader.next();
+                    },
+                    _ => return None
+                }
             }
-        } else {
-            None
         }
     }
 
-    fn read_identifier(&mut self) -> Option<Token> {
-        if let Some((text, span)) = self.read_token(|c| is_letter(c), |c| is_letter(c) || is_digit(c)) {
-            Some(Token::new(TokenKind::Identifier(text), span))
-        } else {
-            None
+    fn read_literal_number(&mut self) -> LexResult {
+        let (text, span) = self.read_while(is_digit);
+        //The only way a string of digits can fail to parse is by being too large.
+        match text.parse::<i32>() {
+            Ok(i) => LexResult::Ok(Token::new(TokenKind::LiteralInt32(i), span)),
+            Err(_) => LexResult::Err(LexerError::new_with_span(LexerErrorKind::InvalidInteger(text), span))
         }
     }
 
-    fn read_token(&mut self, start_cond: fn(char)->bool, continue_cond: fn(char)->bool) -> Option<(String, Span)> {
-        match self.reader.peek() {
-            None => None,
-            Some(c) => {
-                if !start_cond(c) {
-                    None
-                } else {
-                    let mut buf = String::new();
-                    buf.push(c);
-                    self.reader.next();
-                    let start = self.reader.loc();
-                    while let Some(c) = self.reader.peek() {
-                        if !continue_cond(c) {
-                            break;
-                        }
-                        buf.push(c);
-
-                        self.reader.next();
-                    }
-                    Some((buf, Span::from_locations(start, self.reader.loc())))
-                }
+    /// Reads the next character, which starts a token, and the characters following it which satisfy
+    /// `continue_cond`.
+    fn read_while(&mut self, continue_cond: fn(char) -> bool) -> (String, Span) {
+        let mut buf = String::new();
+        //Note:  only called once the first character has been peeked.
+        buf.push(self.reader.next().unwrap());
+        let start = self.reader.loc();
+        while let Some(c) = self.reader.peek() {
+            if !continue_cond(c) {
+                break;
             }
+            buf.push(c);
+            self.reader.next();
         }
+        (buf, self.span(start, self.reader.loc()))
     }
 }
 
@@ -237,29 +409,94 @@ impl <'a> Lexer<'a> {
 mod tests {
     use super::*;
 
-    fn tok(kind: TokenKind, start_line: u32, start_col: u32, end_line: u32, end_col: u32) -> LexResult {
-        LexResult::Ok(Token::new(kind, Span::from_locations(Location::new(start_line, start_col), Location::new(end_line, end_col))))
+    fn tok(kind: TokenKind, start: (u32, u32, usize), end: (u32, u32, usize)) -> LexResult {
+        LexResult::Ok(Token::new(kind, Span::from_locations(Location::new(start.0, start.1, start.2), Location::new(end.0, end.1, end.2))))
+    }
+
+    #[test]
+    fn lexer_tokens() {
+        let kinds = Lexer::new("a+ 1".chars()).tokens().unwrap().into_iter().map(|t| t.kind).collect::<Vec<_>>();
+        assert_eq!(vec![TokenKind::Identifier(String::from("a")), TokenKind::BinaryOperator(BinaryOp::Add), TokenKind::LiteralInt32(1)], kinds);
+        assert_eq!(LexerErrorKind::InvalidCharacter('$'), Lexer::new("1 $ 2".chars()).tokens().unwrap_err().kind);
     }
 
     #[test]
     fn lexer_test() {
         let mut l = Lexer::new("  123  \n 456 \nabc\na123 \n+\n-\n*\n/\n%".chars());
 
-        assert_eq!(tok(TokenKind::LiteralInt32(123), 1, 3, 1, 5), l.next());
-        assert_eq!(tok(TokenKind::LiteralInt32(456), 2, 2, 2, 4), l.next());
-        assert_eq!(tok(TokenKind::Identifier(String::from("abc")), 3, 1, 3, 3), l.next());
-        assert_eq!(tok(TokenKind::Identifier(String::from("a123")), 4, 1, 4, 4), l.next());
+        assert_eq!(tok(TokenKind::LiteralInt32(123), (1, 3, 2), (1, 5, 4)), l.next());
+        assert_eq!(tok(TokenKind::LiteralInt32(456), (2, 2, 9), (2, 4, 11)), l.next());
+        assert_eq!(tok(TokenKind::Identifier(String::from("abc")), (3, 1, 14), (3, 3, 16)), l.next());
+        assert_eq!(tok(TokenKind::Identifier(String::from("a123")), (4, 1, 18), (4, 4, 21)), l.next());
 
-        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Add), 5, 1, 5, 1), l.next());
-        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Sub), 6, 1, 6, 1), l.next());
-        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Mul), 7, 1, 7, 1), l.next());
-        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Div), 8, 1, 8, 1), l.next());
-        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Mod), 9, 1, 9, 1), l.next());
+        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Add), (5, 1, 24), (5, 1, 24)), l.next());
+        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Sub), (6, 1, 26), (6, 1, 26)), l.next());
+        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Mul), (7, 1, 28), (7, 1, 28)), l.next());
+        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Div), (8, 1, 30), (8, 1, 30)), l.next());
+        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Mod), (9, 1, 32), (9, 1, 32)), l.next());
     }
+
+    #[test]
+    fn lexer_parens() {
+        let mut l = Lexer::new("(a)".chars());
+        assert_eq!(tok(TokenKind::OpenParen, (1, 1, 0), (1, 1, 0)), l.next());
+        assert_eq!(tok(TokenKind::Identifier(String::from("a")), (1, 2, 1), (1, 2, 1)), l.next());
+        assert_eq!(tok(TokenKind::CloseParen, (1, 3, 2), (1, 3, 2)), l.next());
+    }
+    #[test]
+    fn lexer_skips_comments() {
+        let mut l = Lexer::new("1 // one\n/* two\n /* nested */\n*/ 2/**/3 // end".chars());
+        assert_eq!(tok(TokenKind::LiteralInt32(1), (1, 1, 0), (1, 1, 0)), l.next());
+        assert_eq!(tok(TokenKind::LiteralInt32(2), (4, 4, 33), (4, 4, 33)), l.next());
+        assert_eq!(tok(TokenKind::LiteralInt32(3), (4, 9, 38), (4, 9, 38)), l.next());
+        assert!(!l.has_more());
+    }
+
+    #[test]
+    fn lexer_division_is_not_a_comment() {
+        let mut l = Lexer::new("1 / 2".chars());
+        l.next();
+        assert_eq!(tok(TokenKind::BinaryOperator(BinaryOp::Div), (1, 3, 2), (1, 3, 2)), l.next());
+    }
+
+    #[test]
+    fn lexer_unterminated_block_comment() {
+        let mut l = Lexer::new("1 /* a /* b */\n c".chars());
+        l.next();
+        assert!(l.has_more());
+        assert_eq!(
+            LexResult::Err(LexerError::new_with_span(
+                LexerErrorKind::UnterminatedBlockComment,
+                Span::from_locations(Location::new(1, 3, 2), Location::new(2, 2, 16)))),
+            l.next());
+    }
+
+    #[test]
+    fn lexer_integer_too_large() {
+        let mut l = Lexer::new("2147483648".chars());
+        assert_eq!(
+            LexResult::Err(LexerError::new_with_span(
+                LexerErrorKind::InvalidInteger(String::from("2147483648")),
+                Span::from_locations(Location::new(1, 1, 0), Location::new(1, 10, 9)))),
+            l.next());
+    }
+
     #[test]
     fn lexer_parse_single_identifier() {
         let mut l = Lexer::new("abc".chars());
-        assert_eq!(tok(TokenKind::Identifier(String::from("abc")), 1, 1, 1, 3), l.next());
+        assert_eq!(tok(TokenKind::Identifier(String::from("abc")), (1, 1, 0), (1, 3, 2)), l.next());
+    }
+
+    #[test]
+    fn lexer_next_char() {
+        let mut l = Lexer::new("a /* = */ = 1 =".chars());
+        l.next();
+        assert_eq!(Some(Span::from_location(Location::new(1, 11, 10))), l.next_char('='));
+        assert_eq!(None, l.next_char('='));
+        assert_eq!(tok(TokenKind::LiteralInt32(1), (1, 13, 12), (1, 13, 12)), l.next());
+        assert_eq!(Some(Span::from_location(Location::new(1, 15, 14))), l.next_char('='));
+        assert_eq!(None, l.next_char('='));
+        assert!(matches!(l.next(), LexResult::EndOfInput(_)));
     }
 }
 
@@ -1,5 +1,8 @@
 
 pub mod source;
+pub mod source_map;
+pub mod diagnostic;
+pub mod json;
 pub mod input;
 pub mod lexer;
 pub mod value;
@@ -7,9 +10,21 @@ pub mod ast;
 pub mod parser;
 pub mod passes;
 pub mod error;
+pub mod explain;
+pub mod dump;
 pub mod env;
+pub mod globals;
+pub mod repl;
+pub mod analysis;
+pub mod lsp;
+pub mod line_editor;
 pub mod common;
+pub mod bytecode;
+pub mod codegen;
+pub mod syntax;
 
+use std::fmt;
+use std::error::Error as StdError;
 use lexer::*;
 use parser::*;
 use passes::*;
@@ -26,11 +41,28 @@ pub fn execute(source: &str) -> ExecuteResult {
 }
 
 pub fn parse(source: &str) -> ParseResult {
-    let lexer = Lexer::new(source.chars());
+    parse_with_file(source, source::FileId::NONE)
+}
+
+/// Parses `source`, the text of `file`, so that the spans of the result and of any error refer to `file`.
+pub fn parse_with_file(source: &str, file: source::FileId) -> ParseResult {
+    let lexer = Lexer::new_with_file(source.chars(), file);
     let mut parser = Parser::new(lexer);
     parser.parse()
 }
 
+pub fn parse_recovering(source: &str) -> (ast::Expr, Vec<ParseError>) {
+    parse_recovering_with_file(source, source::FileId::NONE)
+}
+
+/// Parses `source`, the text of `file`, without stopping at the first syntax error.  Returns the partial
+/// expression, with an `ExprKind::Error` node in place of each part which could not be parsed, and every error.
+pub fn parse_recovering_with_file(source: &str, file: source::FileId) -> (ast::Expr, Vec<ParseError>) {
+    let lexer = Lexer::new_with_file(source.chars(), file);
+    let mut parser = Parser::new(lexer);
+    parser.parse_recovering()
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExecuteErrorKind {
     Parse(ParseErrorKind),
@@ -46,30 +78,71 @@ impl ErrorKind for ExecuteErrorKind {
             &ExecuteErrorKind::Pass(ref kind) => kind.message()
         }
     }
+
+    fn suggestions(&self) -> Vec<String> {
+        match *self {
+            ExecuteErrorKind::Parse(ref kind) => kind.suggestions(),
+            ExecuteErrorKind::Evaluate(ref kind) => kind.suggestions(),
+            ExecuteErrorKind::Pass(ref kind) => kind.suggestions()
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            ExecuteErrorKind::Parse(ref kind) => kind.code(),
+            ExecuteErrorKind::Evaluate(ref kind) => kind.code(),
+            ExecuteErrorKind::Pass(ref kind) => kind.code()
+        }
+    }
+}
+
+impl fmt::Display for ExecuteErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl StdError for ExecuteErrorKind {
+    /// The error of the phase which failed.
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            ExecuteErrorKind::Parse(ref kind) => Some(kind),
+            ExecuteErrorKind::Pass(ref kind) => Some(kind),
+            ExecuteErrorKind::Evaluate(ref kind) => Some(kind)
+        }
+    }
 }
 
 pub type ExecuteError = SourceError<ExecuteErrorKind>;
 pub type ExecuteResult = Result<Value, ExecuteError>;
 
 pub fn execute_with_globals(source: &str, global_env_def: &EnvDef) -> ExecuteResult {
-    match parse(source) {
-        Ok(ast) =>
-            match resolve_variables(ast, &global_env_def) {
-                Ok(ast) => {
-                    let global_env = global_env_def.create_with_default_values();
-                    match evaluate(&ast, &global_env) {
-                        Ok(value) => Ok(value),
-                        Err(err) => Err(ExecuteError::new_with_span(ExecuteErrorKind::Evaluate(err.kind), err.span))
-                    }
-                },
-                Err(pass_err) => {
-                    Err(ExecuteError::new_with_span(ExecuteErrorKind::Pass(pass_err.kind), pass_err.span))
-                }
-            },
+    execute_with_file(source, source::FileId::NONE, global_env_def)
+}
+
+/// Executes `source`, the text of `file`, so that the span of any error refers to `file`.
+pub fn execute_with_file(source: &str, file: source::FileId, global_env_def: &EnvDef) -> ExecuteResult {
+    match parse_with_file(source, file) {
+        Ok(ast) => execute_expr(ast, global_env_def),
         Err(parse_error) => {
             Err(ExecuteError::new_with_span(ExecuteErrorKind::Parse(parse_error.kind), parse_error.span))
         }
-   }
+    }
+}
 
+/// Resolves the variables of `expr`, which has already been parsed, against `global_env_def` and evaluates it.
+pub fn execute_expr(expr: ast::Expr, global_env_def: &EnvDef) -> ExecuteResult {
+    match resolve_variables(expr, global_env_def) {
+        Ok(ast) => {
+            let global_env = global_env_def.create_with_default_values();
+            match evaluate(&ast, &global_env) {
+                Ok(value) => Ok(value),
+                Err(err) => Err(ExecuteError::new_with_span(ExecuteErrorKind::Evaluate(err.kind), err.span))
+            }
+        },
+        Err(pass_err) => {
+            Err(ExecuteError::new_with_span(ExecuteErrorKind::Pass(pass_err.kind), pass_err.span))
+        }
+    }
 }
 
@@ -0,0 +1,732 @@
+/*
+  A small line editor for the REPL, used when standard input and output are a terminal.
+
+  The terminal is put in raw mode while a line is read, so that each key is received as it is pressed.
+  The keys supported are:
+
+      Left, Right, Home, End, Ctrl-B, Ctrl-F, Ctrl-A, Ctrl-E    move the cursor
+      Backspace, Delete, Ctrl-D                                  delete a character
+      Ctrl-K, Ctrl-U, Ctrl-W                                     delete to the end, to the start, the previous word
+      Up, Down, Ctrl-P, Ctrl-N                                   recall lines from the history
+      Ctrl-R                                                     search the history, see below
+      Tab                                                        complete the word before the cursor
+      Ctrl-C                                                     abandon the line
+      Ctrl-D on an empty line                                    end the input
+
+  Ctrl-R starts a reverse incremental search:  typing searches for the most recent line of the history
+  containing the text typed, and pressing Ctrl-R again finds an older one.  Enter runs the line found, Ctrl-G
+  cancels the search and any other key stops searching and edits the line found.
+
+  `Editor` is the state of the line being edited, independent of the terminal, and `LineEditor` reads keys from
+  the terminal and draws the line.  Raw mode is only supported on Linux on x86-64 and AArch64, the platforms whose
+  `struct termios` is declared here;  elsewhere `read_line` fails and the caller should read lines without
+  editing.
+*/
+
+use std::io;
+use std::io::{Read, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The number of lines of history kept.
+const MAX_HISTORY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Up,
+    Down,
+    Tab,
+    /// Ctrl-K
+    DeleteToEnd,
+    /// Ctrl-U
+    DeleteToStart,
+    /// Ctrl-W
+    DeleteWord,
+    /// Ctrl-R
+    Search,
+    /// Ctrl-G
+    Cancel,
+    /// Ctrl-C
+    Interrupt,
+    /// Ctrl-D
+    EndOfFile,
+    /// A control character or escape sequence which has no binding.
+    Unknown
+}
+
+/// Reads the bytes of one key from `input`.  Returns `None` at the end of input.
+pub fn read_key<R: Read>(input: &mut R) -> io::Result<Option<Key>> {
+    let byte = match read_byte(input)? {
+        Some(byte) => byte,
+        None => return Ok(None)
+    };
+    let key = match byte {
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 => Key::Interrupt,
+        0x04 => Key::EndOfFile,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x07 => Key::Cancel,
+        0x08 | 0x7f => Key::Backspace,
+        0x09 => Key::Tab,
+        0x0a | 0x0d => Key::Enter,
+        0x0b => Key::DeleteToEnd,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x12 => Key::Search,
+        0x15 => Key::DeleteToStart,
+        0x17 => Key::DeleteWord,
+        0x1b => read_escape_sequence(input)?,
+        0x00..=0x1f => Key::Unknown,
+        0x20..=0x7e => Key::Char(byte as char),
+        _ => read_utf8(byte, input)?
+    };
+    Ok(Some(key))
+}
+
+fn read_byte<R: Read>(input: &mut R) -> io::Result<Option<u8>> {
+    let mut buf = [0u8];
+    match input.read(&mut buf)? {
+        0 => Ok(None),
+        _ => Ok(Some(buf[0]))
+    }
+}
+
+/// Reads the rest of a sequence starting with escape, such as `ESC [ A` for the up arrow.
+fn read_escape_sequence<R: Read>(input: &mut R) -> io::Result<Key> {
+    match read_byte(input)? {
+        Some(b'[') | Some(b'O') => {},
+        _ => return Ok(Key::Unknown)
+    }
+    //Parameters are digits and `;`, and the sequence ends with a byte in the range `@` to `~`.
+    let mut parameter = String::new();
+    loop {
+        match read_byte(input)? {
+            Some(byte) if (0x40..=0x7e).contains(&byte) => return Ok(match (byte, &parameter[..]) {
+                (b'A', _) => Key::Up,
+                (b'B', _) => Key::Down,
+                (b'C', _) => Key::Right,
+                (b'D', _) => Key::Left,
+                (b'H', _) | (b'~', "1") | (b'~', "7") => Key::Home,
+                (b'F', _) | (b'~', "4") | (b'~', "8") => Key::End,
+                (b'~', "3") => Key::Delete,
+                _ => Key::Unknown
+            }),
+            Some(byte) => parameter.push(byte as char),
+            None => return Ok(Key::Unknown)
+        }
+    }
+}
+
+fn read_utf8<R: Read>(first: u8, input: &mut R) -> io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Key::Unknown)
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match read_byte(input)? {
+            Some(byte) => bytes.push(byte),
+            None => return Ok(Key::Unknown)
+        }
+    }
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => Key::Char(text.chars().next().unwrap()),
+        Err(_) => Key::Unknown
+    })
+}
+
+/// Lines which have been entered, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: Vec<String>
+}
+
+impl History {
+    pub fn new() -> History {
+        History { entries: Vec::new() }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Adds `line` unless it is blank or the same as the last line.  Returns true if it was added.
+    pub fn add(&mut self, line: &str) -> bool {
+        if line.trim().is_empty() || self.entries.last().map(|l| &l[..]) == Some(line) {
+            return false;
+        }
+        self.entries.push(String::from(line));
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.remove(0);
+        }
+        true
+    }
+
+    /// Reads the history written by `save` to `path`.  A file which does not exist is an empty history.
+    pub fn load(path: &Path) -> io::Result<History> {
+        let mut history = History::new();
+        match fs::read_to_string(path) {
+            Ok(text) => for line in text.lines() {
+                history.add(line);
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => return Err(err)
+        }
+        Ok(history)
+    }
+
+    /// Writes each entry on its own line.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(entry);
+            text.push('\n');
+        }
+        fs::write(path, text)
+    }
+
+    /// Returns the index of the most recent entry before `before` which contains `query`.
+    fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())].iter().rposition(|e| e.contains(query))
+    }
+}
+
+/// The candidates for completing the word which starts at the byte offset `start` and ends at the cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub start: usize,
+    pub candidates: Vec<String>
+}
+
+/// What the caller of `Editor::apply` should do next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Continue,
+    /// The line was entered.
+    Submit(String),
+    /// Tab was pressed with several candidates which have no longer common prefix.  They should be listed.
+    List(Vec<String>),
+    Interrupt,
+    EndOfInput
+}
+
+struct Search {
+    query: String,
+    /// The entry of the history which was found.
+    found: Option<usize>,
+    failed: bool,
+    /// The line being edited when the search started, restored by cancelling it.
+    original: String
+}
+
+/// A line being edited.  `cursor` is a byte offset into `buffer`.
+pub struct Editor {
+    buffer: String,
+    cursor: usize,
+    /// The entry of the history being shown, or `None` when editing a new line.
+    history_index: Option<usize>,
+    /// The new line, while entries of the history are shown.
+    new_line: String,
+    search: Option<Search>
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor::new()
+    }
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor { buffer: String::new(), cursor: 0, history_index: None, new_line: String::new(), search: None }
+    }
+
+    pub fn buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn set_buffer(&mut self, text: &str) {
+        self.buffer = String::from(text);
+        self.cursor = self.buffer.len();
+    }
+
+    fn previous_char(&self) -> Option<char> {
+        self.buffer[..self.cursor].chars().next_back()
+    }
+
+    fn next_char(&self) -> Option<char> {
+        self.buffer[self.cursor..].chars().next()
+    }
+
+    /// Applies `key` to the line.  `complete` returns the completions of the word before the cursor, given the line
+    /// and the cursor.
+    pub fn apply(&mut self, key: Key, history: &History, complete: &dyn Fn(&str, usize) -> Completion) -> Action {
+        if self.search.is_some() {
+            return self.apply_search(key, history, complete);
+        }
+        match key {
+            Key::Char(c) => {
+                self.buffer.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            },
+            Key::Enter => return Action::Submit(::std::mem::take(&mut self.buffer)),
+            Key::Backspace => if let Some(c) = self.previous_char() {
+                self.cursor -= c.len_utf8();
+                self.buffer.remove(self.cursor);
+            },
+            Key::EndOfFile if self.buffer.is_empty() => return Action::EndOfInput,
+            Key::Delete | Key::EndOfFile => if self.next_char().is_some() {
+                self.buffer.remove(self.cursor);
+            },
+            Key::Left => if let Some(c) = self.previous_char() {
+                self.cursor -= c.len_utf8();
+            },
+            Key::Right => if let Some(c) = self.next_char() {
+                self.cursor += c.len_utf8();
+            },
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.buffer.len(),
+            Key::Up => self.recall_previous(history),
+            Key::Down => self.recall_next(history),
+            Key::DeleteToEnd => self.buffer.truncate(self.cursor),
+            Key::DeleteToStart => {
+                self.buffer.drain(..self.cursor);
+                self.cursor = 0;
+            },
+            Key::DeleteWord => {
+                let before = &self.buffer[..self.cursor];
+                let start = before.trim_end().rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                self.buffer.drain(start..self.cursor);
+                self.cursor = start;
+            },
+            Key::Tab => return self.complete(complete),
+            Key::Search => self.search = Some(Search { query: String::new(), found: None, failed: false, original: self.buffer.clone() }),
+            Key::Interrupt => return Action::Interrupt,
+            Key::Cancel | Key::Unknown => {}
+        }
+        Action::Continue
+    }
+
+    fn recall_previous(&mut self, history: &History) {
+        let index = match self.history_index {
+            None if history.entries().is_empty() => return,
+            None => {
+                self.new_line = self.buffer.clone();
+                history.entries().len() - 1
+            },
+            Some(0) => return,
+            Some(index) => index - 1
+        };
+        self.history_index = Some(index);
+        self.set_buffer(&history.entries()[index]);
+    }
+
+    fn recall_next(&mut self, history: &History) {
+        match self.history_index {
+            Some(index) if index + 1 < history.entries().len() => {
+                self.history_index = Some(index + 1);
+                self.set_buffer(&history.entries()[index + 1]);
+            },
+            Some(_) => {
+                self.history_index = None;
+                let new_line = ::std::mem::take(&mut self.new_line);
+                self.set_buffer(&new_line);
+            },
+            None => {}
+        }
+    }
+
+    fn complete(&mut self, complete: &dyn Fn(&str, usize) -> Completion) -> Action {
+        let completion = complete(&self.buffer, self.cursor);
+        let prefix = common_prefix(&completion.candidates);
+        if prefix.len() > self.cursor - completion.start {
+            self.buffer.replace_range(completion.start..self.cursor, &prefix);
+            self.cursor = completion.start + prefix.len();
+            Action::Continue
+        } else if completion.candidates.len() > 1 {
+            Action::List(completion.candidates)
+        } else {
+            Action::Continue
+        }
+    }
+
+    fn apply_search(&mut self, key: Key, history: &History, complete: &dyn Fn(&str, usize) -> Completion) -> Action {
+        let mut search = self.search.take().unwrap();
+        let before = match key {
+            Key::Char(c) => {
+                search.query.push(c);
+                //The entry found is searched again, as it may still contain the longer query.
+                search.found.map_or(history.entries().len(), |i| i + 1)
+            },
+            Key::Backspace => {
+                search.query.pop();
+                history.entries().len()
+            },
+            Key::Search => search.found.unwrap_or(history.entries().len()),
+            Key::Cancel | Key::Interrupt => {
+                self.set_buffer(&search.original);
+                return Action::Continue;
+            },
+            _ => {
+                if let Some(found) = search.found {
+                    self.history_index = None;
+                    self.set_buffer(&history.entries()[found]);
+                }
+                return self.apply(key, history, complete);
+            }
+        };
+        match history.search(&search.query, before) {
+            Some(found) => {
+                search.found = Some(found);
+                search.failed = false;
+            },
+            None => search.failed = true
+        }
+        self.search = Some(search);
+        Action::Continue
+    }
+
+    /// Returns the text which redraws the line, after `prompt`, and puts the terminal's cursor at the cursor.
+    pub fn render(&self, prompt: &str, history: &History) -> String {
+        let (line, cursor) = match self.search {
+            Some(ref search) => {
+                let label = format!("({}reverse-i-search)`{}': ", if search.failed { "failed " } else { "" }, search.query);
+                let found = search.found.map_or("", |i| &history.entries()[i][..]);
+                (format!("{}{}", label, found), label.chars().count())
+            },
+            None => (format!("{}{}", prompt, self.buffer), prompt.chars().count() + self.buffer[..self.cursor].chars().count())
+        };
+        let mut out = format!("\r{}\x1b[K\r", line);
+        if cursor > 0 {
+            out.push_str(&format!("\x1b[{}C", cursor));
+        }
+        out
+    }
+}
+
+/// Returns the longest prefix of every candidate.
+fn common_prefix(candidates: &[String]) -> String {
+    let first = match candidates.first() {
+        Some(first) => first,
+        None => return String::new()
+    };
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first.char_indices()
+            .zip(candidate.chars())
+            .find(|&((_, a), b)| a != b)
+            .map_or(len.min(candidate.len()), |((i, _), _)| i.min(len));
+    }
+    String::from(&first[..len])
+}
+
+/// The result of reading a line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadLine {
+    Line(String),
+    /// Ctrl-C was pressed.
+    Interrupted,
+    EndOfInput
+}
+
+/// Reads lines from the terminal, keeping a history which is saved to a file.
+pub struct LineEditor {
+    history: History,
+    history_file: Option<PathBuf>
+}
+
+impl LineEditor {
+    /// Creates an editor whose history is loaded from and saved to `history_file`, if any.  A history which
+    /// cannot be loaded is ignored.
+    pub fn new(history_file: Option<PathBuf>) -> LineEditor {
+        let history = history_file.as_ref().and_then(|path| History::load(path).ok()).unwrap_or_default();
+        LineEditor { history, history_file }
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Adds `line` to the history, saving the history file.  Errors saving it are ignored.
+    pub fn add_history(&mut self, line: &str) {
+        if self.history.add(line) {
+            if let Some(ref path) = self.history_file {
+                let _ = self.history.save(path);
+            }
+        }
+    }
+
+    /// Reads a line from the terminal after printing `prompt`.  Fails if the terminal cannot be put in raw mode.
+    pub fn read_line(&mut self, prompt: &str, complete: &dyn Fn(&str, usize) -> Completion) -> io::Result<ReadLine> {
+        let _raw_mode = raw::RawMode::enable()?;
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let mut editor = Editor::new();
+        loop {
+            write!(out, "{}", editor.render(prompt, &self.history))?;
+            out.flush()?;
+            let key = match read_key(&mut input)? {
+                Some(key) => key,
+                None => return Ok(ReadLine::EndOfInput)
+            };
+            match editor.apply(key, &self.history, complete) {
+                Action::Continue => {},
+                Action::Submit(line) => {
+                    write!(out, "\r{}{}\x1b[K\n", prompt, line)?;
+                    return Ok(ReadLine::Line(line));
+                },
+                Action::List(candidates) => write!(out, "\n{}\n", candidates.join("  "))?,
+                Action::Interrupt => {
+                    writeln!(out, "^C")?;
+                    return Ok(ReadLine::Interrupted);
+                },
+                Action::EndOfInput => {
+                    writeln!(out)?;
+                    return Ok(ReadLine::EndOfInput);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod raw {
+    use std::io;
+
+    /// `struct termios` of glibc on x86-64 and AArch64.  The layout and the values of the constants below differ
+    /// on other architectures.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Termios {
+        c_iflag: u32,
+        c_oflag: u32,
+        c_cflag: u32,
+        c_lflag: u32,
+        c_line: u8,
+        c_cc: [u8; 32],
+        c_ispeed: u32,
+        c_ospeed: u32
+    }
+
+    extern "C" {
+        fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+        fn tcsetattr(fd: i32, optional_actions: i32, termios: *const Termios) -> i32;
+    }
+
+    const STDIN: i32 = 0;
+    const TCSADRAIN: i32 = 1;
+    const ICRNL: u32 = 0o400;
+    const IXON: u32 = 0o2000;
+    const ISIG: u32 = 0o1;
+    const ICANON: u32 = 0o2;
+    const ECHO: u32 = 0o10;
+    const IEXTEN: u32 = 0o100000;
+    const VTIME: usize = 5;
+    const VMIN: usize = 6;
+
+    /// Keeps standard input in raw mode until dropped.  Output processing is left on, so `\n` still starts a new
+    /// line.
+    pub struct RawMode {
+        original: Termios
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            let mut termios = Termios { c_iflag: 0, c_oflag: 0, c_cflag: 0, c_lflag: 0, c_line: 0, c_cc: [0; 32], c_ispeed: 0, c_ospeed: 0 };
+            if unsafe { tcgetattr(STDIN, &mut termios) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let original = termios;
+            termios.c_iflag &= !(ICRNL | IXON);
+            termios.c_lflag &= !(ECHO | ICANON | ISIG | IEXTEN);
+            termios.c_cc[VMIN] = 1;
+            termios.c_cc[VTIME] = 0;
+            if unsafe { tcsetattr(STDIN, TCSADRAIN, &termios) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode { original })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                tcsetattr(STDIN, TCSADRAIN, &self.original);
+            }
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+mod raw {
+    use std::io;
+
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            Err(io::Error::new(io::ErrorKind::Other, "raw terminal mode is not supported on this platform"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(bytes: &[u8]) -> Vec<Key> {
+        let mut input = bytes;
+        let mut keys = Vec::new();
+        while let Some(key) = read_key(&mut input).unwrap() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    fn no_completions(_: &str, cursor: usize) -> Completion {
+        Completion { start: cursor, candidates: Vec::new() }
+    }
+
+    fn history(entries: &[&str]) -> History {
+        let mut history = History::new();
+        for entry in entries {
+            history.add(entry);
+        }
+        history
+    }
+
+    /// Applies `keys` to a new editor, returning it and the last action.
+    fn edit(keys: &[Key], history: &History, complete: &dyn Fn(&str, usize) -> Completion) -> (Editor, Action) {
+        let mut editor = Editor::new();
+        let mut action = Action::Continue;
+        for &key in keys {
+            action = editor.apply(key, history, complete);
+        }
+        (editor, action)
+    }
+
+    fn typed(text: &str) -> Vec<Key> {
+        text.chars().map(Key::Char).collect()
+    }
+
+    #[test]
+    fn read_keys() {
+        assert_eq!(
+            vec![Key::Char('a'), Key::Up, Key::Down, Key::Right, Key::Left, Key::Home, Key::End, Key::Delete, Key::Home, Key::End,
+                 Key::Char('é'), Key::Enter, Key::Backspace, Key::Search, Key::Interrupt, Key::Unknown],
+            keys(b"a\x1b[A\x1b[B\x1b[C\x1b[D\x1b[H\x1bOF\x1b[3~\x1b[1~\x1b[4~\xc3\xa9\r\x7f\x12\x03\x1bx"));
+        assert_eq!(vec![Key::Unknown], keys(b"\x1b[1;5X"));
+    }
+
+    #[test]
+    fn edit_line() {
+        let history = History::new();
+        let mut keys = typed("1 + 3");
+        keys.extend(vec![Key::Left, Key::Backspace, Key::Backspace, Key::Char('*'), Key::Home, Key::Char('('), Key::End, Key::Char(')')]);
+        let (editor, _) = edit(&keys, &history, &no_completions);
+        assert_eq!("(1 *3)", editor.buffer());
+
+        let mut keys = typed("ab cd ef");
+        keys.extend(vec![Key::DeleteWord, Key::Left, Key::DeleteToEnd, Key::Home, Key::Right, Key::Delete]);
+        let (editor, _) = edit(&keys, &history, &no_completions);
+        assert_eq!(("a cd", 1), (editor.buffer(), editor.cursor()));
+
+        let mut keys = typed("é1");
+        keys.extend(vec![Key::Left, Key::DeleteToStart, Key::End, Key::Enter]);
+        assert_eq!(Action::Submit(String::from("1")), edit(&keys, &history, &no_completions).1);
+        assert_eq!(Action::EndOfInput, edit(&[Key::EndOfFile], &history, &no_completions).1);
+        assert_eq!(Action::Continue, edit(&[Key::Char('x'), Key::EndOfFile], &history, &no_completions).1);
+    }
+
+    #[test]
+    fn recall_history() {
+        let history = history(&["1", "2", "2", " ", "3"]);
+        assert_eq!(&["1", "2", "3"], history.entries());
+        let mut keys = typed("new");
+        keys.extend(vec![Key::Up, Key::Up]);
+        let (mut editor, _) = edit(&keys, &history, &no_completions);
+        assert_eq!("2", editor.buffer());
+        for _ in 0..3 {
+            editor.apply(Key::Up, &history, &no_completions);
+        }
+        assert_eq!("1", editor.buffer());
+        for _ in 0..3 {
+            editor.apply(Key::Down, &history, &no_completions);
+        }
+        assert_eq!(("new", 3), (editor.buffer(), editor.cursor()));
+    }
+
+    #[test]
+    fn search_history() {
+        let history = history(&["let a = 1", "a + 2", "let b = a", "b * 3"]);
+        let mut keys = vec![Key::Search];
+        keys.extend(typed("let"));
+        let (mut editor, _) = edit(&keys, &history, &no_completions);
+        assert_eq!("\r(reverse-i-search)`let': let b = a\x1b[K\r\x1b[25C", editor.render(">", &history));
+        editor.apply(Key::Search, &history, &no_completions);
+        assert!(editor.render(">", &history).contains("`let': let a = 1"));
+        editor.apply(Key::Search, &history, &no_completions);
+        assert!(editor.render(">", &history).contains("(failed reverse-i-search)`let': let a = 1"));
+        editor.apply(Key::End, &history, &no_completions);
+        editor.apply(Key::Char('0'), &history, &no_completions);
+        assert_eq!("let a = 10", editor.buffer());
+
+        let mut keys = typed("x");
+        keys.extend(vec![Key::Search, Key::Char('*'), Key::Cancel]);
+        assert_eq!("x", edit(&keys, &history, &no_completions).0.buffer());
+        let keys = vec![Key::Search, Key::Char('+'), Key::Enter];
+        assert_eq!(Action::Submit(String::from("a + 2")), edit(&keys, &history, &no_completions).1);
+    }
+
+    #[test]
+    fn complete_words() {
+        let history = History::new();
+        let complete = |line: &str, cursor: usize| {
+            let start = line[..cursor].rfind(' ').map_or(0, |i| i + 1);
+            let candidates = ["total", "totalTax", "tax"].iter()
+                .filter(|c| c.starts_with(&line[start..cursor]))
+                .map(|c| String::from(*c))
+                .collect();
+            Completion { start, candidates }
+        };
+        let mut keys = typed("1 + to");
+        keys.push(Key::Tab);
+        let (mut editor, action) = edit(&keys, &history, &complete);
+        assert_eq!((Action::Continue, "1 + total"), (action, editor.buffer()));
+        assert_eq!(Action::List(vec![String::from("total"), String::from("totalTax")]), editor.apply(Key::Tab, &history, &complete));
+        editor.apply(Key::Char('T'), &history, &complete);
+        editor.apply(Key::Tab, &history, &complete);
+        assert_eq!("1 + totalTax", editor.buffer());
+        assert_eq!("\r>1 + totalTax\x1b[K\r\x1b[13C", editor.render(">", &history));
+    }
+
+    #[test]
+    fn save_and_load_history() {
+        let path = ::std::env::temp_dir().join(format!("line_editor_history_{}", ::std::process::id()));
+        let mut editor = LineEditor::new(Some(path.clone()));
+        editor.add_history("let a = 1");
+        editor.add_history("a + 1");
+        let loaded = History::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(&["let a = 1", "a + 1"], loaded.entries());
+        assert!(History::load(&path).unwrap().entries().is_empty());
+    }
+}
@@ -0,0 +1,645 @@
+/*
+  A Language Server Protocol server for files of inputs (see `analysis`), run by the `lsp` binary.  It
+  publishes diagnostics when a document is opened or changed, shows the type and value of a variable on hover,
+  goes to the definition of a `let` binding, completes the names of globals and bindings, and gives the
+  semantic tokens and the symbols of a document.
+
+  Messages are JSON-RPC 2.0, each preceded by a `Content-Length` header, on standard input and output.
+  Documents are synchronized in full on every change.  Positions in the protocol have zero-based lines and
+  count UTF-16 code units along the line, and are converted to and from byte offsets.
+
+  The globals are given when the server is created, and can be added to or replaced by a `globals` object in
+  the `initializationOptions` of the `initialize` request, written like a JSON globals file.
+*/
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+use analysis::*;
+use diagnostic::Diagnostic;
+use env::*;
+use error::Severity;
+use globals::globals_from_json;
+use json::*;
+use lexer::is_identifier_char;
+use passes::lint::LintConfig;
+use repl::LAST_RESULT;
+use source::*;
+
+/// The error codes of JSON-RPC and the protocol.
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const SERVER_NOT_INITIALIZED: i32 = -32002;
+
+/// The protocol's `TextDocumentSyncKind.Full`.
+const SYNC_FULL: u32 = 1;
+/// The protocol's `CompletionItemKind`s.
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_KEYWORD: u32 = 14;
+/// The protocol's `SymbolKind.Variable`.
+const SYMBOL_VARIABLE: u32 = 13;
+
+/// The legend of semantic tokens:  a token's type is an index of `TOKEN_TYPES`, and its modifiers a set of bits
+/// of the indexes of `TOKEN_MODIFIERS`.  Globals are static variables.
+const TOKEN_TYPES: &[&str] = &["keyword", "number", "operator", "variable"];
+const TOKEN_MODIFIERS: &[&str] = &["declaration", "static"];
+const MODIFIER_DECLARATION: u32 = 1;
+const MODIFIER_STATIC: u32 = 2;
+
+/// The type of every expression, as there are no other types.
+const EXPR_TYPE: &str = "Int32";
+
+/// Reads the body of the next message, or returns `None` at the end of `input`.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut length = None;
+    let mut read_header = false;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return if read_header { Err(invalid("Unexpected end of input in the headers of a message")) } else { Ok(None) };
+        }
+        read_header = true;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        //Headers other than the length, such as `Content-Type`, are ignored.
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>().map_err(|_| invalid("Invalid Content-Length"))?);
+            }
+        }
+    }
+    //The body is read as it arrives, rather than into a buffer of the length, which the client may have got wrong.
+    let length = length.ok_or_else(|| invalid("A message has no Content-Length"))?;
+    let mut body = Vec::new();
+    if (&mut *input).take(length as u64).read_to_end(&mut body)? < length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected end of input in the body of a message"));
+    }
+    String::from_utf8(body).map(Some).map_err(|_| invalid("A message is not UTF-8"))
+}
+
+/// Writes `message` with its header.
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Handles the messages of `input`, writing the replies to `output`, until the client sends `exit` or `input`
+/// ends.
+pub fn serve<R: BufRead, W: Write>(mut input: R, mut output: W, server: &mut Server) -> io::Result<()> {
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match Json::parse(&body) {
+            Ok(message) => server.handle(&message),
+            Err(error) => vec![error_response(Json::Null, ResponseError::new(PARSE_ERROR, error.to_string()))]
+        };
+        for reply in &replies {
+            write_message(&mut output, reply)?;
+        }
+        if server.has_exited() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The `error` of a response.
+#[derive(Debug, Clone, PartialEq)]
+struct ResponseError {
+    code: i32,
+    message: String
+}
+
+impl ResponseError {
+    fn new<S: Into<String>>(code: i32, message: S) -> ResponseError {
+        ResponseError { code, message: message.into() }
+    }
+}
+
+fn response(id: Json, result: Json) -> Json {
+    Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", id), ("result", result)])
+}
+
+fn error_response(id: Json, error: ResponseError) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::from("2.0")),
+        ("id", id),
+        ("error", Json::object(vec![("code", Json::Number(error.code as f64)), ("message", Json::String(error.message))]))
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object(vec![("jsonrpc", Json::from("2.0")), ("method", Json::from(method)), ("params", params)])
+}
+
+/// Returns the byte offset of the position `line`, `character` of `text`.  A position past the end of its line
+/// is at the end of the line, and one past the last line is at the end of `text`.
+fn offset_of(text: &str, line: usize, character: usize) -> usize {
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].find('\n') {
+            Some(i) => start += i + 1,
+            None => return text.len()
+        }
+    }
+    let mut units = 0;
+    for (i, c) in text[start..].char_indices() {
+        if c == '\n' || units >= character {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Returns the line and character of the byte offset `offset` of `text`.
+fn line_and_character(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count(), before[line_start..].chars().map(char::len_utf16).sum())
+}
+
+/// Returns the `Position` of the byte offset `offset` of `text`.
+fn position(text: &str, offset: usize) -> Json {
+    let (line, character) = line_and_character(text, offset);
+    Json::object(vec![("line", Json::from(line)), ("character", Json::from(character))])
+}
+
+/// Returns the byte offset which follows `span`.  The end of a span is its last character.
+fn end_offset(text: &str, span: &Span) -> usize {
+    span.end.offset + text.get(span.end.offset..).and_then(|rest| rest.chars().next()).map_or(0, char::len_utf8)
+}
+
+/// Returns the `Range` of `span` in `text`.
+fn range(text: &str, span: &Span) -> Json {
+    Json::object(vec![("start", position(text, span.start.offset)), ("end", position(text, end_offset(text, span)))])
+}
+
+/// Encodes `tokens` as the `data` of `SemanticTokens`:  five numbers for each token, which are its line and
+/// start character relative to the previous token, its length, type and modifiers.  Tokens are on one line.
+fn encode_tokens(text: &str, tokens: &[ClassifiedToken]) -> Vec<Json> {
+    let mut data = Vec::new();
+    let mut previous = (0, 0);
+    for token in tokens {
+        let (line, character) = line_and_character(text, token.span.start.offset);
+        let length: usize = text[token.span.start.offset..end_offset(text, &token.span)].chars().map(char::len_utf16).sum();
+        let (token_type, mut modifiers) = match token.class {
+            TokenClass::Keyword => (0, 0),
+            TokenClass::Number => (1, 0),
+            TokenClass::Operator => (2, 0),
+            TokenClass::GlobalVariable => (3, MODIFIER_STATIC),
+            TokenClass::LocalVariable => (3, 0)
+        };
+        if token.declaration {
+            modifiers |= MODIFIER_DECLARATION;
+        }
+        let delta_character = if line == previous.0 { character - previous.1 } else { character };
+        data.extend(vec![Json::from(line - previous.0), Json::from(delta_character), Json::from(length), Json::from(token_type as u32), Json::from(modifiers)]);
+        previous = (line, character);
+    }
+    data
+}
+
+fn diagnostic_json(text: &str, diagnostic: &Diagnostic) -> Json {
+    let severity: u32 = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3
+    };
+    Json::object(vec![
+        ("range", range(text, &diagnostic.span)),
+        ("severity", Json::from(severity)),
+        ("code", Json::from(diagnostic.code)),
+        ("source", Json::from("compiler_in_rust")),
+        ("message", Json::string(&diagnostic.message))
+    ])
+}
+
+/// The markdown shown on hover:  the declaration of the variable, in a code block, and where its value
+/// comes from.
+fn hover_text(declaration: &str, description: &str) -> Json {
+    Json::object(vec![
+        ("kind", Json::from("markdown")),
+        ("value", Json::String(format!("```\n{}\n```\n{}", declaration, description)))
+    ])
+}
+
+fn document_uri(params: &Json) -> Option<&str> {
+    params.get("textDocument").and_then(|d| d.get("uri")).and_then(Json::as_str)
+}
+
+/// An open document.
+struct Document {
+    text: String,
+    analysis: Analysis
+}
+
+pub struct Server {
+    globals: EnvDef,
+    documents: HashMap<String, Document>,
+    initialized: bool,
+    shutdown: bool,
+    exited: bool
+}
+
+impl Server {
+    pub fn new(globals: EnvDef) -> Server {
+        Server { globals, documents: HashMap::new(), initialized: false, shutdown: false, exited: false }
+    }
+
+    /// True once the client has sent `exit`.
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// The exit code of the server:  0 if the client asked it to shut down before exiting, otherwise 1.
+    pub fn exit_code(&self) -> i32 {
+        if self.shutdown { 0 } else { 1 }
+    }
+
+    /// Handles `message`, a request, notification or response, and returns the messages to send in reply:  the
+    /// response to a request, and any notifications.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let id = message.get("id").cloned();
+        let method = match message.get("method").and_then(Json::as_str) {
+            Some(method) => method,
+            //The server sends no requests, so ignores responses.
+            None if message.get("result").is_some() || message.get("error").is_some() => return Vec::new(),
+            None => return vec![error_response(id.unwrap_or(Json::Null), ResponseError::new(INVALID_REQUEST, "Expected a method"))]
+        };
+        let params = message.get("params").unwrap_or(&Json::Null);
+        match id {
+            Some(id) => vec![match self.request(method, params) {
+                Ok(result) => response(id, result),
+                Err(error) => error_response(id, error)
+            }],
+            None => self.notify(method, params)
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, ResponseError> {
+        if self.shutdown {
+            return Err(ResponseError::new(INVALID_REQUEST, "The server has been shut down"));
+        }
+        match method {
+            "initialize" if self.initialized => Err(ResponseError::new(INVALID_REQUEST, "The server has already been initialized")),
+            "initialize" => self.initialize(params),
+            _ if !self.initialized => Err(ResponseError::new(SERVER_NOT_INITIALIZED, "The server has not been initialized")),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            },
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            _ => Err(ResponseError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method)))
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        if method == "exit" {
+            self.exited = true;
+            return Vec::new();
+        }
+        if !self.initialized || self.shutdown {
+            return Vec::new();
+        }
+        let uri = match document_uri(params) {
+            Some(uri) => String::from(uri),
+            None => return Vec::new()
+        };
+        match method {
+            "textDocument/didOpen" => match params.get("textDocument").and_then(|d| d.get("text")).and_then(Json::as_str) {
+                Some(text) => self.update(uri, String::from(text)),
+                None => Vec::new()
+            },
+            //Only full changes are accepted, so the last change is the whole text.
+            "textDocument/didChange" => match params.get("contentChanges").and_then(Json::as_array).and_then(|c| c.last()).and_then(|c| c.get("text")).and_then(Json::as_str) {
+                Some(text) => self.update(uri, String::from(text)),
+                None => Vec::new()
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                vec![Server::publish(&uri, Vec::new())]
+            },
+            _ => Vec::new()
+        }
+    }
+
+    fn initialize(&mut self, params: &Json) -> Result<Json, ResponseError> {
+        if let Some(globals) = params.get("initializationOptions").and_then(|o| o.get("globals")) {
+            let globals = globals_from_json(globals)
+                .map_err(|message| ResponseError::new(INVALID_PARAMS, format!("Invalid globals: {}", message)))?;
+            let builder = self.globals.fields().iter().fold(EnvDefBuilder::new(), |b, f| b.with_item(&f.name, f.default_value.clone()));
+            self.globals = globals.iter().fold(builder, |b, g| b.with_item(&g.0, g.1.clone())).build();
+        }
+        self.initialized = true;
+        Ok(Json::object(vec![
+            ("capabilities", Json::object(vec![
+                ("textDocumentSync", Json::object(vec![("openClose", Json::Bool(true)), ("change", Json::from(SYNC_FULL))])),
+                ("hoverProvider", Json::Bool(true)),
+                ("definitionProvider", Json::Bool(true)),
+                ("completionProvider", Json::object(Vec::new())),
+                ("documentSymbolProvider", Json::Bool(true)),
+                ("semanticTokensProvider", Json::object(vec![
+                    ("legend", Json::object(vec![
+                        ("tokenTypes", Json::Array(TOKEN_TYPES.iter().map(|t| Json::from(*t)).collect())),
+                        ("tokenModifiers", Json::Array(TOKEN_MODIFIERS.iter().map(|m| Json::from(*m)).collect()))
+                    ])),
+                    ("full", Json::Bool(true))
+                ]))
+            ])),
+            ("serverInfo", Json::object(vec![("name", Json::from("compiler_in_rust")), ("version", Json::from(env!("CARGO_PKG_VERSION")))]))
+        ]))
+    }
+
+    /// Analyses the new text of the document `uri`, and returns the notification of its diagnostics.
+    fn update(&mut self, uri: String, text: String) -> Vec<Json> {
+        let analysis = analyze(&text, FileId::NONE, &self.globals, &LintConfig::new());
+        let diagnostics = analysis.diagnostics.iter().map(|d| diagnostic_json(&text, d)).collect();
+        let notification = Server::publish(&uri, diagnostics);
+        self.documents.insert(uri, Document { text, analysis });
+        vec![notification]
+    }
+
+    fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+        notification("textDocument/publishDiagnostics", Json::object(vec![("uri", Json::from(uri)), ("diagnostics", Json::Array(diagnostics))]))
+    }
+
+    /// The URI and document of the `textDocument` of `params`.
+    fn document<'a>(&self, params: &'a Json) -> Result<(&'a str, &Document), ResponseError> {
+        let uri = document_uri(params).ok_or_else(|| ResponseError::new(INVALID_PARAMS, "Expected a textDocument"))?;
+        let document = self.documents.get(uri).ok_or_else(|| ResponseError::new(INVALID_PARAMS, format!("'{}' is not open", uri)))?;
+        Ok((uri, document))
+    }
+
+    /// The URI, document and byte offset of the `TextDocumentPositionParams` `params`.
+    fn position_params<'a>(&self, params: &'a Json) -> Result<(&'a str, &Document, usize), ResponseError> {
+        let (uri, document) = self.document(params)?;
+        let position = params.get("position");
+        let number = |name: &str| position.and_then(|p| p.get(name)).and_then(Json::as_number);
+        match (number("line"), number("character")) {
+            (Some(line), Some(character)) => Ok((uri, document, offset_of(&document.text, line as usize, character as usize))),
+            _ => Err(ResponseError::new(INVALID_PARAMS, "Expected a position"))
+        }
+    }
+
+    fn hover(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (_, document, offset) = self.position_params(params)?;
+        let analysis = &document.analysis;
+        let (span, contents) = if let Some(reference) = analysis.reference_at(offset) {
+            let contents = match reference.binding {
+                Binding::Global => {
+                    let value = &self.globals.find(&reference.name).unwrap().default_value;
+                    hover_text(&format!("{}: {}", reference.name, value.type_name()), &format!("Global, with the default value `{}`.", value))
+                },
+                Binding::Let(i) => self.let_hover(document, i),
+                Binding::LastResult(span) =>
+                    hover_text(&format!("{}: {}", LAST_RESULT, EXPR_TYPE), &format!("The value of the expression on line {}.", span.start.line)),
+                Binding::Undefined => return Ok(Json::Null)
+            };
+            (reference.span, contents)
+        } else if let Some(i) = analysis.definition_at(offset) {
+            (analysis.definitions[i].name_span, self.let_hover(document, i))
+        } else {
+            return Ok(Json::Null);
+        };
+        Ok(Json::object(vec![("contents", contents), ("range", range(&document.text, &span))]))
+    }
+
+    fn let_hover(&self, document: &Document, definition: usize) -> Json {
+        let definition = &document.analysis.definitions[definition];
+        hover_text(&format!("let {}: {}", definition.name, EXPR_TYPE), &format!("Defined on line {}.", definition.span.start.line))
+    }
+
+    /// Goes to the definition of the binding of a `let` or `_`.  Globals are not defined in a document.
+    fn definition(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (uri, document, offset) = self.position_params(params)?;
+        let analysis = &document.analysis;
+        let span = match analysis.reference_at(offset).map(|r| &r.binding) {
+            Some(&Binding::Let(i)) => analysis.definitions[i].name_span,
+            Some(&Binding::LastResult(span)) => span,
+            Some(_) => return Ok(Json::Null),
+            None => match analysis.definition_at(offset) {
+                Some(i) => analysis.definitions[i].name_span,
+                None => return Ok(Json::Null)
+            }
+        };
+        Ok(Json::object(vec![("uri", Json::from(uri)), ("range", range(&document.text, &span))]))
+    }
+
+    /// Completes the names of the globals and of the bindings defined before the cursor, and `let` at the start
+    /// of a line.  The client filters the items by the word being typed.
+    fn completion(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (_, document, offset) = self.position_params(params)?;
+        let definitions = document.analysis.definitions_before(offset);
+        let item = |label: &str, kind: u32, detail: String| Json::object(vec![
+            ("label", Json::from(label)),
+            ("kind", Json::from(kind)),
+            ("detail", Json::String(detail))
+        ]);
+
+        let mut items: Vec<Json> = self.globals.fields().iter()
+            .filter(|f| !definitions.iter().any(|d| d.name == f.name))
+            .map(|f| item(&f.name, COMPLETION_VARIABLE, format!("{} = {}", f.default_value.type_name(), f.default_value)))
+            .collect();
+        items.extend(definitions.iter().map(|d| item(&d.name, COMPLETION_VARIABLE, format!("let {}: {}", d.name, EXPR_TYPE))));
+
+        let before = &document.text[..offset];
+        let word_start = before.trim_end_matches(is_identifier_char);
+        if word_start[word_start.rfind('\n').map_or(0, |i| i + 1)..].trim().is_empty() {
+            items.push(item("let", COMPLETION_KEYWORD, String::from("let <name> = <expression>")));
+        }
+        Ok(Json::object(vec![("isIncomplete", Json::Bool(false)), ("items", Json::Array(items))]))
+    }
+
+    fn semantic_tokens(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (_, document) = self.document(params)?;
+        let tokens = classify_tokens(&document.text, FileId::NONE, &document.analysis);
+        Ok(Json::object(vec![("data", Json::Array(encode_tokens(&document.text, &tokens)))]))
+    }
+
+    /// Lists the definitions, as `DocumentSymbol`s.
+    fn document_symbols(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (_, document) = self.document(params)?;
+        Ok(Json::Array(document.analysis.definitions.iter().map(|d| Json::object(vec![
+            ("name", Json::string(&d.name)),
+            ("detail", Json::from(EXPR_TYPE)),
+            ("kind", Json::from(SYMBOL_VARIABLE)),
+            ("range", range(&document.text, &d.span)),
+            ("selectionRange", range(&document.text, &d.name_span))
+        ])).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use value::*;
+
+    fn position_json(line: u32, character: u32) -> Json {
+        Json::object(vec![("line", Json::from(line)), ("character", Json::from(character))])
+    }
+
+    fn request(id: u32, method: &str, params: Json) -> Json {
+        Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", Json::from(id)), ("method", Json::from(method)), ("params", params)])
+    }
+
+    fn at(line: u32, character: u32) -> Json {
+        Json::object(vec![("textDocument", Json::object(vec![("uri", Json::from("file:///a"))])), ("position", position_json(line, character))])
+    }
+
+    /// Returns a server which has been initialized and has opened `file:///a` with `text`.
+    fn open(text: &str) -> Server {
+        let mut server = Server::new(EnvDefBuilder::new().with_item("total", Value::Int32(5)).build());
+        server.handle(&request(1, "initialize", Json::object(Vec::new())));
+        server.handle(&notification("textDocument/didOpen", Json::object(vec![
+            ("textDocument", Json::object(vec![("uri", Json::from("file:///a")), ("text", Json::from(text))]))
+        ])));
+        server
+    }
+
+    fn result(server: &mut Server, method: &str, params: Json) -> Json {
+        server.handle(&request(2, method, params)).remove(0).get("result").unwrap().clone()
+    }
+
+    #[test]
+    fn convert_positions() {
+        let text = "a\u{e9}\u{1f600}b\nxy\n";
+        assert_eq!(0, offset_of(text, 0, 0));
+        assert_eq!(3, offset_of(text, 0, 2));
+        assert_eq!(7, offset_of(text, 0, 4));
+        assert_eq!(8, offset_of(text, 0, 9));
+        assert_eq!(10, offset_of(text, 1, 1));
+        assert_eq!(12, offset_of(text, 2, 0));
+        assert_eq!(12, offset_of(text, 5, 0));
+        assert_eq!(position_json(0, 4), position(text, 7));
+        assert_eq!(position_json(1, 1), position(text, 10));
+        let span = Span::new(FileId::NONE, Location::new(1, 4, 7), Location::new(2, 1, 9));
+        assert_eq!(Json::object(vec![("start", position_json(0, 4)), ("end", position_json(1, 1))]), range(text, &span));
+    }
+
+    #[test]
+    fn read_and_write_messages() {
+        let mut output = Vec::new();
+        write_message(&mut output, &Json::from("\u{e9}")).unwrap();
+        assert_eq!("Content-Length: 4\r\n\r\n\"\u{e9}\"", String::from_utf8(output.clone()).unwrap());
+
+        let mut input = io::Cursor::new([&b"Content-Type: x\r\ncontent-length: 2\r\n\r\n{}"[..], &output[..]].concat());
+        assert_eq!(Some(String::from("{}")), read_message(&mut input).unwrap());
+        assert_eq!(Some(String::from("\"\u{e9}\"")), read_message(&mut input).unwrap());
+        assert_eq!(None, read_message(&mut input).unwrap());
+        assert!(read_message(&mut io::Cursor::new(&b"Content-Length: 2\r\n"[..])).is_err());
+        assert!(read_message(&mut io::Cursor::new(&b"\r\n{}"[..])).is_err());
+        let error = read_message(&mut io::Cursor::new(&b"Content-Length: 99999999999999\r\n\r\n{}"[..])).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+    }
+
+    #[test]
+    fn publishes_diagnostics() {
+        let mut server = Server::new(EnvDefBuilder::new().build());
+        server.handle(&request(1, "initialize", Json::object(vec![
+            ("initializationOptions", Json::object(vec![("globals", Json::object(vec![("total", Json::from(7u32))]))]))
+        ])));
+        let replies = server.handle(&notification("textDocument/didOpen", Json::object(vec![
+            ("textDocument", Json::object(vec![("uri", Json::from("file:///a")), ("text", Json::from("let a = 1\n\u{e9} + totl"))]))
+        ])));
+        let diagnostics = replies[0].get("params").and_then(|p| p.get("diagnostics")).and_then(Json::as_array).unwrap();
+        assert_eq!(
+            "{\"range\":{\"start\":{\"line\":1,\"character\":0},\"end\":{\"line\":1,\"character\":1}},\"severity\":1,\"code\":\"E0101\",\"source\":\"compiler_in_rust\",\"message\":\"Invalid character: 'é'\"}",
+            diagnostics[0].to_string());
+        assert_eq!(Some("Variable 'totl' does not exist; did you mean 'total'?"), diagnostics[1].get("message").and_then(Json::as_str));
+        assert_eq!(Some("W0003"), diagnostics[2].get("code").and_then(Json::as_str));
+        assert_eq!(3, diagnostics.len());
+
+        let replies = server.handle(&notification("textDocument/didChange", Json::object(vec![
+            ("textDocument", Json::object(vec![("uri", Json::from("file:///a"))])),
+            ("contentChanges", Json::Array(vec![Json::object(vec![("text", Json::from("1 / 0"))])]))
+        ])));
+        let diagnostics = replies[0].get("params").and_then(|p| p.get("diagnostics")).and_then(Json::as_array).unwrap();
+        assert_eq!(Some(2.0), diagnostics[0].get("severity").and_then(Json::as_number));
+
+        let replies = server.handle(&notification("textDocument/didClose", Json::object(vec![("textDocument", Json::object(vec![("uri", Json::from("file:///a"))]))])));
+        assert_eq!(Some(&Json::Array(Vec::new())), replies[0].get("params").and_then(|p| p.get("diagnostics")));
+    }
+
+    #[test]
+    fn hover_and_definition() {
+        let mut server = open("let a = total\na + _x\n2\n_");
+        assert_eq!(
+            "{\"contents\":{\"kind\":\"markdown\",\"value\":\"```\\ntotal: Int32\\n```\\nGlobal, with the default value `5`.\"},\"range\":{\"start\":{\"line\":0,\"character\":8},\"end\":{\"line\":0,\"character\":13}}}",
+            result(&mut server, "textDocument/hover", at(0, 10)).to_string());
+        let hover = result(&mut server, "textDocument/hover", at(1, 1));
+        assert_eq!(Some("```\nlet a: Int32\n```\nDefined on line 1."), hover.get("contents").and_then(|c| c.get("value")).and_then(Json::as_str));
+        assert_eq!(Json::Null, result(&mut server, "textDocument/hover", at(1, 5)));
+        assert_eq!(Json::Null, result(&mut server, "textDocument/hover", at(0, 0)));
+
+        let definition = result(&mut server, "textDocument/definition", at(1, 0));
+        assert_eq!(
+            "{\"uri\":\"file:///a\",\"range\":{\"start\":{\"line\":0,\"character\":4},\"end\":{\"line\":0,\"character\":5}}}",
+            definition.to_string());
+        assert_eq!(definition, result(&mut server, "textDocument/definition", at(0, 4)));
+        assert_eq!(Some(&position_json(2, 0)), result(&mut server, "textDocument/definition", at(3, 1)).get("range").and_then(|r| r.get("start")));
+        assert_eq!(Json::Null, result(&mut server, "textDocument/definition", at(0, 9)));
+    }
+
+    #[test]
+    fn completes_names() {
+        let mut server = open("let total = 1\nlet b = 2\n\nt");
+        let labels = |completion: Json| completion.get("items").and_then(Json::as_array).unwrap().iter()
+            .map(|i| String::from(i.get("label").and_then(Json::as_str).unwrap()))
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["total", "let"], labels(result(&mut server, "textDocument/completion", at(1, 0))));
+        assert_eq!(vec!["total", "b", "let"], labels(result(&mut server, "textDocument/completion", at(3, 1))));
+        assert_eq!(vec!["total"], labels(result(&mut server, "textDocument/completion", at(1, 8))));
+
+        let detail = |completion: Json| completion.get("items").and_then(Json::as_array).and_then(|i| i[0].get("detail")).and_then(Json::as_str).map(String::from);
+        assert_eq!(Some(String::from("let total: Int32")), detail(result(&mut server, "textDocument/completion", at(1, 0))));
+        assert_eq!(Some(String::from("Int32 = 5")), detail(result(&mut server, "textDocument/completion", at(0, 0))));
+    }
+
+    #[test]
+    fn semantic_tokens_and_symbols() {
+        let mut server = open("let a = total\n\u{e9} (a  *\n 10)");
+        let numbers = |json: &Json| json.as_array().unwrap().iter().map(|n| n.as_number().unwrap() as u32).collect::<Vec<u32>>();
+        assert_eq!(
+            vec![0, 0, 3, 0, 0,  0, 4, 1, 3, 1,  0, 2, 1, 2, 0,  0, 2, 5, 3, 2,  1, 3, 1, 3, 0,  0, 3, 1, 2, 0,  1, 1, 2, 1, 0],
+            numbers(result(&mut server, "textDocument/semanticTokens/full", at(0, 0)).get("data").unwrap()));
+
+        let symbols = result(&mut server, "textDocument/documentSymbol", at(0, 0));
+        assert_eq!(
+            "[{\"name\":\"a\",\"detail\":\"Int32\",\"kind\":13,\"range\":{\"start\":{\"line\":0,\"character\":0},\"end\":{\"line\":0,\"character\":13}},\"selectionRange\":{\"start\":{\"line\":0,\"character\":4},\"end\":{\"line\":0,\"character\":5}}}]",
+            symbols.to_string());
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut server = Server::new(EnvDefBuilder::new().build());
+        let error_code = |replies: Vec<Json>| replies[0].get("error").and_then(|e| e.get("code")).and_then(Json::as_number);
+        assert_eq!(Some(SERVER_NOT_INITIALIZED as f64), error_code(server.handle(&request(1, "shutdown", Json::Null))));
+        let invalid_globals = Json::object(vec![("initializationOptions", Json::object(vec![("globals", Json::object(vec![("a b", Json::from(1u32))]))]))]);
+        let replies = server.handle(&request(2, "initialize", invalid_globals));
+        assert_eq!(Some(INVALID_PARAMS as f64), error_code(replies.clone()));
+        assert_eq!(
+            Some("Invalid globals: 'a b' is not a valid variable name"),
+            replies[0].get("error").and_then(|e| e.get("message")).and_then(Json::as_str));
+        let capabilities = result(&mut server, "initialize", Json::Null).get("capabilities").cloned().unwrap();
+        assert_eq!(Some(&Json::Bool(true)), capabilities.get("hoverProvider"));
+        assert_eq!(Some(METHOD_NOT_FOUND as f64), error_code(server.handle(&request(3, "workspace/symbol", Json::Null))));
+        assert_eq!(Some(INVALID_REQUEST as f64), error_code(server.handle(&Json::object(vec![("id", Json::from(4u32))]))));
+        assert!(server.handle(&Json::object(vec![("id", Json::from(4u32)), ("result", Json::Null)])).is_empty());
+
+        assert_eq!(Json::Null, result(&mut server, "shutdown", Json::Null));
+        assert_eq!(Some(INVALID_REQUEST as f64), error_code(server.handle(&request(5, "textDocument/hover", Json::Null))));
+        assert!(!server.has_exited());
+        server.handle(&notification("exit", Json::Null));
+        assert!(server.has_exited());
+        assert_eq!(0, server.exit_code());
+        assert_eq!(1, Server::new(EnvDefBuilder::new().build()).exit_code());
+    }
+}
@@ -7,17 +7,23 @@ use error::*;
 use common::*;
 
 use std::vec::Vec;
+use std::fmt;
+use std::error::Error as StdError;
 
 // http://en.cppreference.com/w/cpp/language/operator_precedence
 // https://keepcalmandlearnrust.com/2016/08/pratt-parser-in-rust/
 
-fn get_precedence(token_kind: &TokenKind) -> u32 {
-    if let &TokenKind::BinaryOperator(ref kind) = token_kind {
-        match kind  {
-            &BinaryOp::Add | &BinaryOp::Sub => 10,
-            &BinaryOp::Mul | &BinaryOp::Div => 20,
-            _ => 0
-        }
+/// Returns the binding power of `op`.  All binary operators are left associative.
+pub fn get_binary_op_precedence(op: &BinaryOp) -> u32 {
+    match *op {
+        BinaryOp::Add | BinaryOp::Sub => 10,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 20,
+    }
+}
+
+pub fn get_precedence(token_kind: &TokenKind) -> u32 {
+    if let &TokenKind::BinaryOperator(ref op) = token_kind {
+        get_binary_op_precedence(op)
     } else {
         0
     }
@@ -29,6 +35,7 @@ pub enum ParseErrorKind {
     ExpectedBinaryOperator(TokenKind),
     InvalidPrefixExpressionTerm(TokenKind),
     UnexpectedEndOfInput,
+    UnclosedParen,
     EmptyExpr,
 }
 
@@ -39,7 +46,34 @@ impl ErrorKind for ParseErrorKind {
             &ParseErrorKind::EmptyExpr => String::from("No expressions were found"),
             &ParseErrorKind::ExpectedBinaryOperator(ref tok) => format!("Expected binary operator but found: {}", tok),
             &ParseErrorKind::InvalidPrefixExpressionTerm(ref tok) => format!("Invalid prefix expression term: {}", tok),
-            &ParseErrorKind::UnexpectedEndOfInput => String::from("Unexpected end of input")
+            &ParseErrorKind::UnexpectedEndOfInput => String::from("Unexpected end of input"),
+            &ParseErrorKind::UnclosedParen => String::from("Unclosed '('")
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            ParseErrorKind::LexerError(ref le) => le.code(),
+            ParseErrorKind::ExpectedBinaryOperator(_) => "E0201",
+            ParseErrorKind::InvalidPrefixExpressionTerm(_) => "E0202",
+            ParseErrorKind::UnexpectedEndOfInput => "E0203",
+            ParseErrorKind::UnclosedParen => "E0204",
+            ParseErrorKind::EmptyExpr => "E0205",
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl StdError for ParseErrorKind {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            ParseErrorKind::LexerError(ref kind) => Some(kind),
+            _ => None
         }
     }
 }
@@ -51,98 +85,167 @@ fn lex_to_parse_error(lex_err: LexerError) -> ParseError {
     ParseError::new_with_span(ParseErrorKind::LexerError(lex_err.kind), lex_err.span)
 }
 
+/// The parser recovers from syntax errors so that every error in the input can be reported at once.  Each
+/// part of the input which cannot be parsed is replaced by an `ExprKind::Error` node and parsing continues:
+///
+///  - An invalid token is skipped, except for a `)` which closes an enclosing paren.
+///  - A missing operand at the end of the input, or before a closing `)`, becomes an error node.
+///  - An unclosed paren is closed at the end of the input.
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
+    errors: Vec<ParseError>,
+    /// The number of open parens enclosing the expression being parsed.
+    paren_depth: u32,
 }
 
 impl <'a> Parser<'a> {
     pub fn new(lexer: Lexer<'a>) -> Parser {
-        Parser { lexer: lexer }
+        Parser { lexer: lexer, errors: Vec::new(), paren_depth: 0 }
     }
 
+    /// Parses the input, failing with the first syntax error.
     pub fn parse(&mut self) -> ParseResult {
+        let (expr, mut errors) = self.parse_recovering();
+        if errors.is_empty() {
+            Ok(expr)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Parses the input, returning the partial expression, which contains an `ExprKind::Error` node for each part
+    /// that could not be parsed, along with every syntax error in source order.
+    pub fn parse_recovering(&mut self) -> (Expr, Vec<ParseError>) {
         let mut exprs = Vec::new();
 
         while self.lexer.has_more() {
-            match self.parse_expr(0) {
-                Err(e) => return Err(e),
-                Ok(expr) => exprs.push(Box::new(expr))
-            }
+            exprs.push(Box::new(self.parse_expr(0)));
         }
-        return match exprs.len() {
-                0 => Err(ParseError::new_with_location(ParseErrorKind::EmptyExpr, Location::start())),
-                1 => Ok(*exprs.pop().unwrap()), //Note: .pop() should never return None because .len() == 1
-                _ => Ok({
-                    let first_span = exprs[0].span.clone();
-                    let last_span = exprs[exprs.len() - 1].span.clone();
-                    Expr::new_compound_expr_with_span(exprs, Span::from_locations(first_span.start, last_span.end))
-                })
+        let expr = match exprs.len() {
+            0 => {
+                let span = Span::new(self.lexer.file(), self.lexer.start(), self.lexer.start());
+                self.error(ParseErrorKind::EmptyExpr, span)
+            },
+            1 => *exprs.pop().unwrap(), //Note: .pop() should never return None because .len() == 1
+            _ => {
+                let span = exprs[0].span.to(exprs[exprs.len() - 1].span);
+                Expr::new_compound_expr_with_span(exprs, span)
             }
+        };
+        (expr, ::std::mem::take(&mut self.errors))
     }
 
-    fn parse_expr(&mut self, precedence: u32) -> ParseResult {
-        let prefix_result = self.parse_prefix();
-        match prefix_result {
-            Err(_) => prefix_result,
-            Ok(mut expr) => {
-                while let LexResult::Ok(next_token) = self.lexer.peek() {
-                    let next_precedence = get_precedence(&next_token.kind);
+    /// Records an error and returns the node which takes the place of the invalid expression.
+    fn error(&mut self, kind: ParseErrorKind, span: Span) -> Expr {
+        self.errors.push(ParseError::new_with_span(kind, span));
+        Expr::new_error_with_span(span)
+    }
 
-                    if precedence >= next_precedence {
-                        break;
-                    }
+    fn parse_expr(&mut self, precedence: u32) -> Expr {
+        let mut expr = self.parse_prefix();
+        while let LexResult::Ok(next_token) = self.lexer.peek() {
+            let next_precedence = get_precedence(&next_token.kind);
 
-                    expr = match self.parse_infix(expr, next_precedence) {
-                        Err(e) => return Err(e),
-                        Ok(expr) => expr
-                    }
-                }
-                Ok(expr)
+            if precedence >= next_precedence {
+                break;
             }
+
+            expr = self.parse_infix(expr, next_precedence);
         }
+        expr
     }
 
-    fn parse_prefix(&mut self) -> ParseResult {
+    fn parse_prefix(&mut self) -> Expr {
+        //A close paren ends the enclosing paren, so it is left for `parse_parenthesized`.
+        if let LexResult::Ok(Token { kind: TokenKind::CloseParen, span }) = self.lexer.peek() {
+            if self.paren_depth > 0 {
+                return self.error(ParseErrorKind::InvalidPrefixExpressionTerm(TokenKind::CloseParen), span);
+            }
+        }
+
         match self.lexer.next() {
-            LexResult::Err(lex_err) => Err(lex_to_parse_error(lex_err)),
+            LexResult::Err(lex_err) => {
+                let span = lex_err.span;
+                self.errors.push(lex_to_parse_error(lex_err));
+                Expr::new_error_with_span(span)
+            },
 
-            LexResult::EndOfInput(last_location) => Err(
-                ParseError::new_with_location(
-                    ParseErrorKind::UnexpectedEndOfInput,
-                    last_location)),
+            LexResult::EndOfInput(last_location) => {
+                let span = Span::new(self.lexer.file(), last_location, last_location);
+                self.error(ParseErrorKind::UnexpectedEndOfInput, span)
+            },
 
             LexResult::Ok(token) =>
                 match token.kind {
-                    TokenKind::LiteralInt32(value) => Ok(Expr::new_literal_with_span(Value::Int32(value), token.span)),
-                    TokenKind::Identifier(text) => Ok(Expr::new_variable_ref_with_span(text, token.span)),
-                    _ => Err(ParseError::new_with_span(ParseErrorKind::InvalidPrefixExpressionTerm(token.kind), token.span))
+                    TokenKind::LiteralInt32(value) => Expr::new_literal_with_span(Value::Int32(value), token.span),
+                    TokenKind::Identifier(text) => Expr::new_variable_ref_with_span(text, token.span),
+                    TokenKind::OpenParen => self.parse_parenthesized(token.span),
+                    _ => self.error(ParseErrorKind::InvalidPrefixExpressionTerm(token.kind), token.span)
+                }
+        }
+    }
+
+    /// Parses the expressions following an open paren at `open_span` up to the matching close paren.
+    /// As at the top level, a sequence of more than one expression is a `CompoundExpr`.
+    fn parse_parenthesized(&mut self, open_span: Span) -> Expr {
+        let mut exprs = Vec::new();
+        self.paren_depth += 1;
+        let close_span = loop {
+            match self.lexer.peek() {
+                LexResult::Ok(Token { kind: TokenKind::CloseParen, span }) => {
+                    self.lexer.next();
+                    break Some(span);
+                },
+                LexResult::EndOfInput(_) => break None,
+                _ => exprs.push(Box::new(self.parse_expr(0)))
+            }
+        };
+        self.paren_depth -= 1;
+
+        let span = match close_span {
+            Some(close_span) => open_span.to(close_span),
+            None => {
+                self.errors.push(ParseError::new_with_span(ParseErrorKind::UnclosedParen, open_span));
+                match exprs.last() {
+                    Some(last) => open_span.to(last.span),
+                    //Only the unclosed paren is reported, not that it is also empty.
+                    None => return Expr::new_error_with_span(open_span)
                 }
+            }
+        };
+        match exprs.len() {
+            0 => self.error(ParseErrorKind::EmptyExpr, span),
+            1 => {
+                let mut expr = *exprs.pop().unwrap();
+                expr.span = span;
+                expr
+            },
+            _ => Expr::new_compound_expr_with_span(exprs, span)
         }
     }
 
-    fn parse_infix(&mut self, left: Expr, precedence: u32) -> ParseResult {
+    fn parse_infix(&mut self, left: Expr, precedence: u32) -> Expr {
         match self.lexer.next() {
-            LexResult::Err(lex_err) => Err(lex_to_parse_error(lex_err)),
+            LexResult::Err(lex_err) => {
+                let span = lex_err.span;
+                self.errors.push(lex_to_parse_error(lex_err));
+                Expr::new_error_with_span(span)
+            },
 
-            LexResult::EndOfInput(last_location) => Err(
-                ParseError::new_with_location(
-                    ParseErrorKind::UnexpectedEndOfInput,
-                    last_location)),
+            LexResult::EndOfInput(last_location) => {
+                let span = Span::new(self.lexer.file(), last_location, last_location);
+                self.error(ParseErrorKind::UnexpectedEndOfInput, span)
+            },
 
             LexResult::Ok(token) => {
                 let binary_op = match token.kind {
                     TokenKind::BinaryOperator(op) => op,
-                    _ => return Err(ParseError::new_with_span(ParseErrorKind::ExpectedBinaryOperator(token.kind), token.span))
+                    _ => return self.error(ParseErrorKind::ExpectedBinaryOperator(token.kind), token.span)
                 };
 
-                let parse_result = self.parse_expr(precedence);
-                match parse_result {
-                    Err(_) => parse_result,
-                    Ok(right) => {
-                        let span = Span::from_locations(left.span.start.clone(), right.span.end.clone());
-                        Ok(Expr::new_binary_with_span(binary_op, left, right, span))
-                    },
-                }
+                let right = self.parse_expr(precedence);
+                let span = left.span.to(right.span);
+                Expr::new_binary_with_span(binary_op, left, right, span)
             }
         }
     }
@@ -199,6 +302,128 @@ mod tests {
             parse("1+2*3")
         )
     }
+    #[test]
+    pub fn parse_literal_binary_mod() {
+        assert_eq!(
+            Expr::new_binary(
+                BinaryOp::Sub,
+                Expr::new_literal(Value::Int32(1)),
+                Expr::new_binary(
+                    BinaryOp::Mod,
+                    Expr::new_literal(Value::Int32(2)),
+                    Expr::new_literal(Value::Int32(3)))),
+            parse("1-2%3")
+        )
+    }
+
+    #[test]
+    pub fn parse_mod_binds_like_mul() {
+        let lit = |value| Expr::new_literal(Value::Int32(value));
+        assert_eq!(
+            Expr::new_binary(BinaryOp::Add, lit(1), Expr::new_binary(BinaryOp::Mod, lit(5), lit(3))),
+            parse("1 + 5 % 3"));
+        //Operators of the same precedence are left associative.
+        assert_eq!(
+            Expr::new_binary(BinaryOp::Mod, Expr::new_binary(BinaryOp::Mul, lit(2), lit(5)), lit(3)),
+            parse("2 * 5 % 3"));
+        assert_eq!(
+            Expr::new_binary(BinaryOp::Mul, Expr::new_binary(BinaryOp::Mod, lit(2), lit(5)), lit(3)),
+            parse("2 % 5 * 3"));
+    }
+
+    #[test]
+    pub fn parse_parens() {
+        assert_eq!(
+            Expr::new_binary(
+                BinaryOp::Mul,
+                Expr::new_binary(
+                    BinaryOp::Add,
+                    Expr::new_literal(Value::Int32(1)),
+                    Expr::new_literal(Value::Int32(2))),
+                Expr::new_literal(Value::Int32(3))),
+            parse("(1 + 2) * 3")
+        );
+        assert_eq!(
+            Expr::new_binary(
+                BinaryOp::Sub,
+                Expr::new_literal(Value::Int32(1)),
+                Expr::new_compound_expr(vec![
+                    Box::new(Expr::new_literal(Value::Int32(2))),
+                    Box::new(Expr::new_literal(Value::Int32(3)))])),
+            parse("1 - (2 3)")
+        );
+        assert_eq!(Expr::new_literal(Value::Int32(1)), parse("((1))"));
+    }
+
+    #[test]
+    pub fn parse_paren_errors() {
+        let parse_err = |src: &str| Parser::new(Lexer::new(src.chars())).parse().unwrap_err().kind;
+        assert_eq!(ParseErrorKind::UnclosedParen, parse_err("(1 + 2"));
+        assert_eq!(ParseErrorKind::EmptyExpr, parse_err("()"));
+        assert_eq!(ParseErrorKind::InvalidPrefixExpressionTerm(TokenKind::CloseParen), parse_err("1)"));
+    }
+
+    #[test]
+    pub fn parse_spans_slice_source() {
+        let source = "foo /* \u{e9} */ +\n  (bar * 2)";
+        let expr = parse(source);
+        assert_eq!(source, expr.span.text(source));
+        match expr.kind {
+            ExprKind::Binary { ref right, .. } => assert_eq!("(bar * 2)", right.span.text(source)),
+            _ => panic!("Expected a binary expression")
+        }
+    }
+
+    fn parse_recovering(src: &str) -> (Expr, Vec<ParseErrorKind>) {
+        let (expr, errors) = Parser::new(Lexer::new(src.chars())).parse_recovering();
+        (expr, errors.into_iter().map(|e| e.kind).collect())
+    }
+
+    #[test]
+    pub fn parse_recovering_reports_every_error() {
+        let (expr, errors) = parse_recovering("1 + * 2 $ (3 -) )");
+        assert_eq!(
+            vec![ParseErrorKind::InvalidPrefixExpressionTerm(TokenKind::BinaryOperator(BinaryOp::Mul)),
+                 ParseErrorKind::LexerError(LexerErrorKind::InvalidCharacter('$')),
+                 ParseErrorKind::InvalidPrefixExpressionTerm(TokenKind::CloseParen),
+                 ParseErrorKind::InvalidPrefixExpressionTerm(TokenKind::CloseParen)],
+            errors);
+        assert_eq!(
+            Expr::new_compound_expr(vec![
+                Box::new(Expr::new_binary(BinaryOp::Add, Expr::new_literal(Value::Int32(1)), Expr::new_error_with_span(Span::unknown()))),
+                Box::new(Expr::new_literal(Value::Int32(2))),
+                Box::new(Expr::new_error_with_span(Span::unknown())),
+                Box::new(Expr::new_binary(BinaryOp::Sub, Expr::new_literal(Value::Int32(3)), Expr::new_error_with_span(Span::unknown()))),
+                Box::new(Expr::new_error_with_span(Span::unknown()))]),
+            expr);
+        assert!(expr.has_errors());
+    }
+
+    #[test]
+    pub fn parse_recovering_closes_parens_at_end_of_input() {
+        let (expr, errors) = parse_recovering("(1 (2 +");
+        assert_eq!(
+            vec![ParseErrorKind::UnexpectedEndOfInput, ParseErrorKind::UnclosedParen, ParseErrorKind::UnclosedParen],
+            errors);
+        assert_eq!(
+            Expr::new_compound_expr(vec![
+                Box::new(Expr::new_literal(Value::Int32(1))),
+                Box::new(Expr::new_binary(BinaryOp::Add, Expr::new_literal(Value::Int32(2)), Expr::new_error_with_span(Span::unknown())))]),
+            expr);
+
+        let (expr, errors) = parse_recovering("(");
+        assert_eq!(vec![ParseErrorKind::UnclosedParen], errors);
+        assert_eq!(ExprKind::Error, expr.kind);
+    }
+
+    #[test]
+    pub fn parse_recovering_without_errors() {
+        let (expr, errors) = parse_recovering("1 + (2 3)");
+        assert!(errors.is_empty());
+        assert!(!expr.has_errors());
+        assert_eq!(parse("1 + (2 3)"), expr);
+    }
+
     #[test]
     pub fn parse_identifier() {
         assert_eq!(Expr::new_variable_ref(String::from("abc")), parse("abc"));
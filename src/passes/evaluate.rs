@@ -1,21 +1,42 @@
 
+use std::fmt;
 use ast::*;
 use super::*;
 
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum EvaluateErrorKind {
-    IndexOutOfRange(u32)
+    IndexOutOfRange(u32),
+    DivisionByZero,
+    IntegerOverflow(BinaryOp)
 }
 
 impl ErrorKind for EvaluateErrorKind {
     fn message(&self) -> String {
-        match self {
-            &EvaluateErrorKind::IndexOutOfRange(index) => format!("Index {} was out of range.", index)
+        match *self {
+            EvaluateErrorKind::IndexOutOfRange(index) => format!("Index {} was out of range.", index),
+            EvaluateErrorKind::DivisionByZero => String::from("Division by zero"),
+            EvaluateErrorKind::IntegerOverflow(ref op) => format!("Integer overflow in {:?} operation", op)
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            EvaluateErrorKind::IndexOutOfRange(_) => "E0401",
+            EvaluateErrorKind::DivisionByZero => "E0402",
+            EvaluateErrorKind::IntegerOverflow(_) => "E0403"
         }
     }
 }
 
+impl fmt::Display for EvaluateErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ::std::error::Error for EvaluateErrorKind {}
+
 pub type EvaluateError = SourceError<EvaluateErrorKind>;
 pub type EvaluateResult = Result<Value, EvaluateError>;
 
@@ -32,6 +53,8 @@ pub fn evaluate(expr: &Expr, env: &Env) -> EvaluateResult {
         //This case indicates that the `resolve_variables` pass was not executed against `expr`
         //This would be a bug.
         ExprKind::VariableRef { ref name } => panic!("Unresolved variable reference: {:?}", name),
+        //Only expressions which parsed without errors can be evaluated.
+        ExprKind::Error => panic!("Syntax error at {:?}", expr.span),
         ExprKind::Binary{ ref op, ref left, ref right } => {
             let left_value = match evaluate(&left, env) {
                 Err(e) => return Err(e),
@@ -42,17 +65,12 @@ pub fn evaluate(expr: &Expr, env: &Env) -> EvaluateResult {
                 Ok(value) => value
             };
 
-            Ok(match (op, left_value, right_value) {
-                (&BinaryOp::Add, Value::Int32(l), Value::Int32(r)) => Value::Int32(l + r),
-                (&BinaryOp::Sub, Value::Int32(l), Value::Int32(r)) => Value::Int32(l - r),
-                (&BinaryOp::Mul, Value::Int32(l), Value::Int32(r)) => Value::Int32(l * r),
-                (&BinaryOp::Div, Value::Int32(l), Value::Int32(r)) => Value::Int32(l / r),
-                (&BinaryOp::Mod, Value::Int32(l), Value::Int32(r)) => Value::Int32(l % r),
-            })
+            apply_binary_op(op, left_value, right_value)
+                .map_err(|kind| EvaluateError::new_with_span(kind, expr.span))
         }
         ExprKind::CompoundExpr { ref exprs } => {
             //Iterate over all expressions except the last, discarding the result.
-            for expr in &exprs[0..exprs.len() - 2] {
+            for expr in &exprs[0..exprs.len() - 1] {
                 if let Err(e) = evaluate(expr, env) {
                     return Err(e)
                 }
@@ -63,6 +81,25 @@ pub fn evaluate(expr: &Expr, env: &Env) -> EvaluateResult {
     }
 }
 
+/// Applies a binary operator to two values.  Overflow and division by zero are reported as errors
+/// instead of panicking.  This is shared with the bytecode VM so that both agree on the semantics
+/// of every operator.
+pub fn apply_binary_op(op: &BinaryOp, left: Value, right: Value) -> Result<Value, EvaluateErrorKind> {
+    match (left, right) {
+        (Value::Int32(l), Value::Int32(r)) => {
+            let result = match *op {
+                BinaryOp::Add => l.checked_add(r),
+                BinaryOp::Sub => l.checked_sub(r),
+                BinaryOp::Mul => l.checked_mul(r),
+                BinaryOp::Div | BinaryOp::Mod if r == 0 => return Err(EvaluateErrorKind::DivisionByZero),
+                BinaryOp::Div => l.checked_div(r),
+                BinaryOp::Mod => l.checked_rem(r),
+            };
+            result.map(Value::Int32).ok_or_else(|| EvaluateErrorKind::IntegerOverflow(op.clone()))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,4 +138,30 @@ mod tests {
     fn test_mod() {
         assert_eq!(Value::Int32(1), eval(&Expr::new_binary(BinaryOp::Mod, lit_int32(7), lit_int32(3))));
     }
+
+    fn eval_err(expr: &Expr) -> EvaluateErrorKind {
+        let env = EnvDefBuilder::new().build();
+        let empty = env.create_with_default_values();
+        evaluate(expr, &empty).unwrap_err().kind
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(EvaluateErrorKind::DivisionByZero, eval_err(&Expr::new_binary(BinaryOp::Div, lit_int32(1), lit_int32(0))));
+        assert_eq!(EvaluateErrorKind::DivisionByZero, eval_err(&Expr::new_binary(BinaryOp::Mod, lit_int32(1), lit_int32(0))));
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(EvaluateErrorKind::IntegerOverflow(BinaryOp::Add), eval_err(&Expr::new_binary(BinaryOp::Add, lit_int32(i32::MAX), lit_int32(1))));
+        assert_eq!(EvaluateErrorKind::IntegerOverflow(BinaryOp::Div), eval_err(&Expr::new_binary(BinaryOp::Div, lit_int32(i32::MIN), lit_int32(-1))));
+    }
+
+    #[test]
+    fn test_compound_evaluates_every_expr() {
+        let expr = Expr::new_compound_expr(vec![
+            Box::new(Expr::new_binary(BinaryOp::Div, lit_int32(1), lit_int32(0))),
+            Box::new(lit_int32(2))]);
+        assert_eq!(EvaluateErrorKind::DivisionByZero, eval_err(&expr));
+    }
 }
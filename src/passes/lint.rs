@@ -0,0 +1,264 @@
+/*
+  Lints flag code which is valid but probably not what was intended.  Unlike the errors of the other
+  passes, lint warnings do not stop an expression from being executed.
+
+  Each `Lint` on an expression is checked by a `LintPass` which is invoked for every node of the expression.
+  The lints on `let` definitions, `UnusedLet` and `ShadowedGlobal`, concern several inputs, so they are
+  checked where the inputs are, by `repl::Session` and `analysis::analyze`.  Lints are enabled by default and
+  can be disabled individually with `LintConfig`.
+*/
+
+use std::fmt;
+use ast::*;
+use error::*;
+use common::*;
+use value::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// An expression of a `CompoundExpr` other than the last whose value is discarded, and which has no effect.
+    UnusedResult,
+    /// A division or modulus by the literal `0`, which always fails.
+    DivisionByZero,
+    /// A `let` definition whose value is not used before the name is defined again, or the inputs end.
+    UnusedLet,
+    /// A `let` definition of the name of a global.
+    ShadowedGlobal,
+}
+
+impl Lint {
+    pub fn all() -> &'static [Lint] {
+        &[Lint::UnusedResult, Lint::DivisionByZero, Lint::UnusedLet, Lint::ShadowedGlobal]
+    }
+
+    /// The name used to enable or disable the lint.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Lint::UnusedResult => "unused_result",
+            Lint::DivisionByZero => "division_by_zero",
+            Lint::UnusedLet => "unused_let",
+            Lint::ShadowedGlobal => "shadowed_global",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::all().iter().cloned().find(|l| l.name() == name)
+    }
+}
+
+impl ErrorKind for Lint {
+    fn message(&self) -> String {
+        match *self {
+            Lint::UnusedResult => String::from("The result of this expression is unused"),
+            Lint::DivisionByZero => String::from("This operation will always fail with a division by zero"),
+            Lint::UnusedLet => String::from("The value of this definition is never used"),
+            Lint::ShadowedGlobal => String::from("This definition hides the global of the same name"),
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            Lint::UnusedResult => "W0001",
+            Lint::DivisionByZero => "W0002",
+            Lint::UnusedLet => "W0003",
+            Lint::ShadowedGlobal => "W0004",
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ::std::error::Error for Lint {}
+
+pub type LintWarning = SourceError<Lint>;
+
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    disabled: Vec<Lint>
+}
+
+impl LintConfig {
+    pub fn new() -> LintConfig {
+        LintConfig { disabled: Vec::new() }
+    }
+
+    pub fn enable(mut self, lint: Lint) -> LintConfig {
+        self.disabled.retain(|l| *l != lint);
+        self
+    }
+
+    pub fn disable(mut self, lint: Lint) -> LintConfig {
+        if !self.disabled.contains(&lint) {
+            self.disabled.push(lint);
+        }
+        self
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        !self.disabled.contains(&lint)
+    }
+}
+
+/// Checks a single lint.
+trait LintPass {
+    fn lint(&self) -> Lint;
+
+    /// Invoked for each node of the expression, parents before their children.  `unused` is set for the nodes
+    /// within an expression whose result is unused, and for that expression.
+    fn check_expr(&self, expr: &Expr, unused: bool, warnings: &mut Vec<LintWarning>);
+}
+
+struct UnusedResult;
+
+impl LintPass for UnusedResult {
+    fn lint(&self) -> Lint {
+        Lint::UnusedResult
+    }
+
+    fn check_expr(&self, expr: &Expr, unused: bool, warnings: &mut Vec<LintWarning>) {
+        //The results within an unused expression are unused too, which has already been reported.
+        if unused {
+            return;
+        }
+        if let ExprKind::CompoundExpr { ref exprs } = expr.kind {
+            for (i, e) in exprs.iter().enumerate() {
+                if is_unused(exprs, i) {
+                    warnings.push(LintWarning::new_with_span(Lint::UnusedResult, e.span));
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if the result of the expression with index `index` of a `CompoundExpr`'s `exprs` is discarded,
+/// and the expression has no effect.
+fn is_unused(exprs: &[Box<Expr>], index: usize) -> bool {
+    index < exprs.len() - 1 && !has_effect(&exprs[index])
+}
+
+/// Returns true if evaluating `expr` can do anything besides produce a value.  Binary operators may fail.
+fn has_effect(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Binary { .. } | ExprKind::Error => true,
+        ExprKind::Literal { .. } | ExprKind::VariableRef { .. } | ExprKind::VariableIndex { .. } => false,
+        ExprKind::CompoundExpr { ref exprs } => exprs.iter().any(|e| has_effect(e))
+    }
+}
+
+struct DivisionByZero;
+
+impl LintPass for DivisionByZero {
+    fn lint(&self) -> Lint {
+        Lint::DivisionByZero
+    }
+
+    fn check_expr(&self, expr: &Expr, _unused: bool, warnings: &mut Vec<LintWarning>) {
+        if let ExprKind::Binary { op: BinaryOp::Div, ref right, .. } | ExprKind::Binary { op: BinaryOp::Mod, ref right, .. } = expr.kind {
+            if right.kind == (ExprKind::Literal { value: Value::Int32(0) }) {
+                warnings.push(LintWarning::new_with_span(Lint::DivisionByZero, expr.span));
+            }
+        }
+    }
+}
+
+const PASSES: &[&dyn LintPass] = &[&UnusedResult, &DivisionByZero];
+
+/// Returns the warnings of every lint enabled by `config`, in source order.
+pub fn lint(expr: &Expr, config: &LintConfig) -> Vec<LintWarning> {
+    let passes: Vec<&dyn LintPass> = PASSES.iter().cloned().filter(|p| config.is_enabled(p.lint())).collect();
+    let mut warnings = Vec::new();
+    walk(expr, false, &passes, &mut warnings);
+    warnings.sort_by_key(|w| w.span.start.offset);
+    warnings
+}
+
+fn walk(expr: &Expr, unused: bool, passes: &[&dyn LintPass], warnings: &mut Vec<LintWarning>) {
+    for pass in passes {
+        pass.check_expr(expr, unused, warnings);
+    }
+    match expr.kind {
+        ExprKind::Binary { ref left, ref right, .. } => {
+            walk(left, unused, passes, warnings);
+            walk(right, unused, passes, warnings);
+        },
+        ExprKind::CompoundExpr { ref exprs } => {
+            for (i, e) in exprs.iter().enumerate() {
+                walk(e, unused || is_unused(exprs, i), passes, warnings);
+            }
+        },
+        ExprKind::Literal { .. } | ExprKind::VariableRef { .. } | ExprKind::VariableIndex { .. } | ExprKind::Error => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse;
+
+    fn lints(source: &str, config: &LintConfig) -> Vec<(Lint, String)> {
+        let expr = parse(source).unwrap();
+        lint(&expr, config).into_iter().map(|w| (w.kind, String::from(w.span.text(source)))).collect()
+    }
+
+    #[test]
+    fn lint_unused_result() {
+        assert_eq!(
+            vec![(Lint::UnusedResult, String::from("1")), (Lint::UnusedResult, String::from("(a 2)"))],
+            lints("1 b + 1 (a 2) 3", &LintConfig::new()));
+        assert!(lints("1 + 2", &LintConfig::new()).is_empty());
+    }
+
+    #[test]
+    fn lint_unused_result_once_for_nested_compounds() {
+        assert_eq!(vec![(Lint::UnusedResult, String::from("(a 2)"))], lints("(a 2) 3", &LintConfig::new()));
+        assert_eq!(vec![(Lint::UnusedResult, String::from("((a 2) b)"))], lints("((a 2) b) 3", &LintConfig::new()));
+        //The result of the last expression is used, so the compounds within it are checked.
+        assert_eq!(vec![(Lint::UnusedResult, String::from("a"))], lints("1 + (a 2)", &LintConfig::new()));
+    }
+
+    #[test]
+    fn lint_unused_result_without_spans() {
+        //`((9) (1 (2 3) 4))`, built without spans, so that nesting cannot be told from them.
+        let literal = |value| Box::new(Expr::new_literal(Value::Int32(value)));
+        let compound = |exprs| Box::new(Expr::new_compound_expr(exprs));
+        let expr = Expr::new_compound_expr(vec![
+            compound(vec![literal(9)]),
+            compound(vec![literal(1), compound(vec![literal(2), literal(3)]), literal(4)])
+        ]);
+        let warnings = lint(&expr, &LintConfig::new());
+        assert_eq!(vec![Lint::UnusedResult; 3], warnings.iter().map(|w| w.kind).collect::<Vec<Lint>>());
+    }
+
+    #[test]
+    fn lint_unused_result_not_for_compounds_with_effects() {
+        //The division of the first compound always fails, so evaluating it is not without effect.
+        assert_eq!(vec![(Lint::DivisionByZero, String::from("1 / 0"))], lints("(1 / 0 2) 3", &LintConfig::new()));
+        assert_eq!(vec![(Lint::UnusedResult, String::from("a"))], lints("(a b + 1 2) 3", &LintConfig::new()));
+    }
+
+    #[test]
+    fn lint_division_by_zero() {
+        assert_eq!(
+            vec![(Lint::DivisionByZero, String::from("a / 0")), (Lint::DivisionByZero, String::from("(1 + 2) % (0)")),
+                 (Lint::DivisionByZero, String::from("1 / 00"))],
+            lints("a / 0 + (1 + 2) % (0) + 1 / 00 * 2", &LintConfig::new()));
+        assert!(lints("1 / 2 * 0", &LintConfig::new()).is_empty());
+    }
+
+    #[test]
+    fn lint_config_disables_lints() {
+        let config = LintConfig::new().disable(Lint::DivisionByZero);
+        assert_eq!(vec![(Lint::UnusedResult, String::from("1"))], lints("1 2 / 0", &config));
+        assert_eq!(2, lints("1 2 / 0", &config.enable(Lint::DivisionByZero)).len());
+        assert_eq!(Some(Lint::UnusedResult), Lint::from_name("unused_result"));
+        assert_eq!(None, Lint::from_name("unused"));
+    }
+}
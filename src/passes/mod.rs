@@ -1,7 +1,9 @@
 
 pub mod resolve_variables;
 pub mod evaluate;
+pub mod lint;
 
+use std::fmt;
 use ast::*;
 use value::*;
 use error::*;
@@ -14,16 +16,44 @@ use env::*;
 /// to reduce coupling.
 #[derive(Debug, Clone, PartialEq)]
 pub enum PassErrorKind {
-    VariableDoesNotExist { variable_name: String }
+    /// `suggestions` are the names of defined variables similar to `variable_name`.
+    VariableDoesNotExist { variable_name: String, suggestions: Vec<String> }
 }
 
 impl ErrorKind for PassErrorKind {
     fn message(&self) -> String {
         match self {
-            &PassErrorKind::VariableDoesNotExist{ref variable_name} => format!("Variable '{}' does not exist", variable_name)
+            &PassErrorKind::VariableDoesNotExist { ref variable_name, ref suggestions } => match suggestions.split_last() {
+                None => format!("Variable '{}' does not exist", variable_name),
+                Some((last, [])) => format!("Variable '{}' does not exist; did you mean '{}'?", variable_name, last),
+                Some((last, rest)) => {
+                    let rest: Vec<String> = rest.iter().map(|s| format!("'{}'", s)).collect();
+                    format!("Variable '{}' does not exist; did you mean {} or '{}'?", variable_name, rest.join(", "), last)
+                }
+            }
+        }
+    }
+
+    fn suggestions(&self) -> Vec<String> {
+        match *self {
+            PassErrorKind::VariableDoesNotExist { ref suggestions, .. } => suggestions.clone()
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            PassErrorKind::VariableDoesNotExist { .. } => "E0301"
         }
     }
 }
+
+impl fmt::Display for PassErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ::std::error::Error for PassErrorKind {}
 type PassError = SourceError<PassErrorKind>;
 pub type PassResult = Result<Expr, PassError>;
 
@@ -36,7 +66,8 @@ fn recurse_clone<TErrorKind: ErrorKind>(expr: &Expr, node_handler: &Fn(&Expr) ->
         None => match &expr.kind {
             &ExprKind::Literal{ value: _ } |
             &ExprKind::VariableRef { name: _ } |
-            &ExprKind::VariableIndex { index: _ }
+            &ExprKind::VariableIndex { index: _ } |
+            &ExprKind::Error
             => Ok((*expr).clone()),
 
             &ExprKind::Binary { ref op, ref left, ref right } => {
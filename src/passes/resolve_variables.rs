@@ -14,10 +14,94 @@ pub fn resolve_variables(expr: Expr, global_def: &EnvDef) -> PassResult {
                         Some(field) => Ok(Expr::new_variable_index_with_span(field.ordinal, expr.span)),
                         None => Err(
                             PassError::new_with_span(
-                                PassErrorKind::VariableDoesNotExist { variable_name: name.clone() },
+                                PassErrorKind::VariableDoesNotExist {
+                                    variable_name: name.clone(),
+                                    suggestions: similar_names(name, global_def.fields().iter().map(|f| &f.name[..]))
+                                },
                                 expr.span.clone()))
                     }),
                 _ => None
             }
         })
 }
+
+/// The most suggestions offered for a misspelt name.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the `candidates` which are most likely to be what was meant by the misspelt `name`:  those within an
+/// edit distance of a third of the length of `name`, closest first.  Names differing only by case are closest.
+pub fn similar_names<'a, I: Iterator<Item = &'a str>>(name: &str, candidates: I) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut similar: Vec<(usize, &str)> = candidates
+        .filter_map(|c| {
+            let distance = if c.to_lowercase() == name.to_lowercase() { 0 } else { edit_distance(name, c) };
+            if distance <= max_distance { Some((distance, c)) } else { None }
+        })
+        .collect();
+    similar.sort();
+    similar.into_iter().take(MAX_SUGGESTIONS).map(|(_, c)| String::from(c)).collect()
+}
+
+/// The Levenshtein distance between `a` and `b`:  the number of characters which must be inserted, deleted
+/// or substituted to turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    //The distances from the prefix of `a` read so far to each prefix of `b`.
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let substitution = diagonal + if ca == b[j] { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_recovering;
+    use value::*;
+
+    #[test]
+    fn resolve_variables_skips_error_nodes() {
+        let env_def = EnvDefBuilder::new().with_item("a", Value::Int32(1)).build();
+        let (expr, errors) = parse_recovering("a + ) a * $");
+        assert_eq!(2, errors.len());
+        let resolved = resolve_variables(expr, &env_def).unwrap();
+        assert!(resolved.has_errors());
+
+        let (expr, _) = parse_recovering("a + ) b");
+        let err = resolve_variables(expr, &env_def).unwrap_err();
+        assert_eq!(PassErrorKind::VariableDoesNotExist { variable_name: String::from("b"), suggestions: vec![String::from("a")] }, err.kind);
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(0, edit_distance("", ""));
+        assert_eq!(3, edit_distance("abc", ""));
+        assert_eq!(1, edit_distance("custmerId", "customerId"));
+        assert_eq!(2, edit_distance("ab", "ba"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+    }
+
+    #[test]
+    fn resolve_variables_suggests_similar_names() {
+        let env_def = EnvDefBuilder::new()
+            .with_item("customerId", Value::Int32(1))
+            .with_item("customerIds", Value::Int32(2))
+            .with_item("orderId", Value::Int32(3))
+            .with_item("CUSTMERID", Value::Int32(4))
+            .build();
+        let err = resolve_variables(::parse("1 + custmerId").unwrap(), &env_def).unwrap_err();
+        let expected = vec![String::from("CUSTMERID"), String::from("customerId"), String::from("customerIds")];
+        assert_eq!(expected, err.kind.suggestions());
+        assert_eq!("Variable 'custmerId' does not exist; did you mean 'CUSTMERID', 'customerId' or 'customerIds'?", err.kind.message());
+
+        let err = resolve_variables(::parse("total").unwrap(), &env_def).unwrap_err();
+        assert_eq!("Variable 'total' does not exist", err.kind.message());
+    }
+}
@@ -0,0 +1,617 @@
+/*
+  An interactive session, which evaluates one input at a time and remembers definitions between inputs.
+
+  Besides expressions, a session accepts definitions of the form:
+
+      let <name> = <expression>
+
+  which evaluate the expression and bind its value to the name for the inputs which follow.  Defining a
+  name again replaces its value.  The value of every expression is bound to `_`, so it can be used by the
+  next input.  The globals the session was created with are its first bindings, and `reset` returns to them.
+
+  `let` is only recognized at the start of an input;  the language itself has no definitions.
+
+  An input which ends part way through an expression, such as `(1 +`, is incomplete:  `is_incomplete`
+  tells the REPL to read another line and append it.  Inputs starting with `:` are the commands of
+  `COMMANDS`, parsed by `Command::parse`.
+*/
+
+use std::fmt;
+use std::error::Error as StdError;
+use diagnostic::*;
+use env::*;
+use error::*;
+use lexer::*;
+use parser::*;
+use passes::lint::*;
+use source::*;
+use source_map::*;
+use value::*;
+use ast::*;
+use passes::resolve_variables::*;
+use line_editor::Completion;
+use execute_expr;
+
+/// The name bound to the value of the last expression.
+pub const LAST_RESULT: &str = "_";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionErrorKind {
+    /// An input starting with `let` is not of the form `let <name> = <expression>`.
+    InvalidLet
+}
+
+impl ErrorKind for SessionErrorKind {
+    fn message(&self) -> String {
+        match *self {
+            SessionErrorKind::InvalidLet => String::from("Expected 'let <name> = <expression>'")
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            SessionErrorKind::InvalidLet => "E0901"
+        }
+    }
+}
+
+impl fmt::Display for SessionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl StdError for SessionErrorKind {}
+pub type SessionError = SourceError<SessionErrorKind>;
+
+/// The words with a meaning at the start of an input.
+pub const KEYWORDS: &[&str] = &["let", "exit"];
+
+/// The name, argument and description of every command.
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    (":ast", "<expr>", "Prints the expression with canonical parentheses"),
+    (":tokens", "<expr>", "Prints the tokens of the expression and their locations"),
+    (":type", "<expr>", "Prints the type of the expression without evaluating it"),
+    (":bytecode", "<expr>", "Prints the bytecode the expression compiles to"),
+    (":time", "<input>", "Evaluates the input and prints how long it took"),
+    (":load", "<file>", "Evaluates each input of a file"),
+    (":env", "", "Lists the variables and their values"),
+    (":reset", "", "Forgets every definition"),
+    (":help", "", "Prints this list"),
+    (":quit", "", "Exits, as does 'exit'"),
+];
+
+/// A command of the REPL.  The argument of each command taking one is its text with surrounding whitespace
+/// removed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Ast(String),
+    Tokens(String),
+    Type(String),
+    Bytecode(String),
+    Time(String),
+    Load(String),
+    Env,
+    Reset,
+    Help,
+    Quit
+}
+
+impl Command {
+    /// Parses `input` if it is a command, that is it starts with `:`.  Returns an error message if the command
+    /// is unknown or its argument is missing.
+    pub fn parse(input: &str) -> Option<Result<Command, String>> {
+        let input = input.trim();
+        if !input.starts_with(':') {
+            return None;
+        }
+        let (name, argument) = match input.find(char::is_whitespace) {
+            Some(index) => (&input[..index], input[index..].trim()),
+            None => (input, "")
+        };
+        let with_argument = |command: fn(String) -> Command| match argument {
+            "" => Err(format!("'{}' requires an argument; type ':help' for help", name)),
+            argument => Ok(command(String::from(argument)))
+        };
+        let without_argument = |command: Command| match argument {
+            "" => Ok(command),
+            _ => Err(format!("'{}' does not take an argument", name))
+        };
+        Some(match name {
+            ":ast" => with_argument(Command::Ast),
+            ":tokens" => with_argument(Command::Tokens),
+            ":type" => with_argument(Command::Type),
+            ":bytecode" => with_argument(Command::Bytecode),
+            ":time" => with_argument(Command::Time),
+            ":load" => with_argument(Command::Load),
+            ":env" => without_argument(Command::Env),
+            ":reset" => without_argument(Command::Reset),
+            ":help" => without_argument(Command::Help),
+            ":quit" => without_argument(Command::Quit),
+            _ => Err(format!("Unknown command '{}'; type ':help' for a list of commands", name))
+        })
+    }
+
+    /// The input the command evaluates or inspects, if any.
+    pub fn input(&self) -> Option<&str> {
+        match *self {
+            Command::Ast(ref input) | Command::Tokens(ref input) | Command::Type(ref input) |
+            Command::Bytecode(ref input) | Command::Time(ref input) => Some(input),
+            _ => None
+        }
+    }
+}
+
+/// Returns true if `input`, or the input of a command, ends before its expression does, so that more lines
+/// should be read.  Unbalanced parentheses, a trailing operator, an unterminated block comment and a `let`
+/// without an expression are incomplete.
+pub fn is_incomplete(input: &str) -> bool {
+    match Command::parse(input) {
+        Some(Ok(ref command)) => command.input().is_some_and(is_incomplete_input),
+        Some(Err(_)) => false,
+        None => is_incomplete_input(input)
+    }
+}
+
+fn is_incomplete_input(input: &str) -> bool {
+    let start = match parse_let(input, FileId::NONE, Location::start()) {
+        Ok(Some(ref header)) if !has_tokens(&input[header.expr_start().offset..]) => return true,
+        Ok(Some(header)) => header.expr_start(),
+        Ok(None) => Location::start(),
+        Err(_) => return false
+    };
+    let (_, errors) = Parser::new(Lexer::new(input[start.offset..].chars())).parse_recovering();
+    errors.iter().any(|e| matches!(e.kind,
+        ParseErrorKind::UnexpectedEndOfInput |
+        ParseErrorKind::UnclosedParen |
+        ParseErrorKind::LexerError(LexerErrorKind::UnterminatedBlockComment)))
+}
+
+/// Returns true unless `text` only contains whitespace and comments.
+fn has_tokens(text: &str) -> bool {
+    !matches!(Lexer::new(text.chars()).next(), LexResult::EndOfInput(_))
+}
+
+/// Splits `source`, a file of inputs, into inputs.  An input ends with the first line which completes it, and
+/// comments before an input are part of it.  Returns the start and the end byte offset of each input.  The last
+/// input may be incomplete.
+pub fn split_inputs(source: &str) -> Vec<(Location, usize)> {
+    let mut inputs = Vec::new();
+    let mut start = Location::start();
+    let mut loc = Location::start();
+    for c in source.chars() {
+        loc = loc.advance(c);
+        let input = &source[start.offset..loc.offset];
+        if c == '\n' && has_tokens(input) && !is_incomplete_input(input) {
+            inputs.push((start, loc.offset));
+            start = loc;
+        }
+    }
+    if has_tokens(&source[start.offset..]) {
+        inputs.push((start, source.len()));
+    }
+    inputs
+}
+
+/// The `let <name> =` which starts a definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LetHeader {
+    pub keyword_span: Span,
+    pub name: String,
+    pub name_span: Span,
+    pub equals_span: Span
+}
+
+impl LetHeader {
+    /// The location following the `=`, where the expression starts.
+    pub fn expr_start(&self) -> Location {
+        self.equals_span.end.advance('=')
+    }
+}
+
+/// An input which has been parsed, but not evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedInput {
+    /// The `let` of a definition, or `None` for an expression.
+    pub header: Option<LetHeader>,
+    /// The expression, with `ExprKind::Error` nodes in place of the parts in `errors`.
+    pub expr: Expr,
+    pub errors: Vec<ParseError>
+}
+
+/// Parses the input of `source`, the text of `file`, which starts at `start` and ends at the byte offset `end`.
+/// Fails if the input starts with `let` but is not a definition.
+pub fn parse_input(source: &str, file: FileId, start: Location, end: usize) -> Result<ParsedInput, SessionError> {
+    let source = &source[..end];
+    let header = parse_let(source, file, start)?;
+    let start = header.as_ref().map_or(start, LetHeader::expr_start);
+    let (expr, errors) = Parser::new(Lexer::new_at(source[start.offset..].chars(), file, start)).parse_recovering();
+    Ok(ParsedInput { header, expr, errors })
+}
+
+/// What evaluating an input did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// An expression was evaluated.
+    Value(Value),
+    /// A `let` bound the value to the name.
+    Defined(String, Value),
+    /// The input had errors, which are among the diagnostics.  No bindings were changed.
+    Failed
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub outcome: Outcome,
+    /// The errors and warnings, in the order they were found.
+    pub diagnostics: Vec<Diagnostic>
+}
+
+pub struct Session {
+    source_map: SourceMap,
+    globals: Vec<(String, Value)>,
+    bindings: Vec<(String, Value)>,
+    lint_config: LintConfig,
+    /// The names of the definitions whose values have not been used yet, and the spans of the names.
+    unused_definitions: Vec<(String, Span)>
+}
+
+impl Session {
+    /// Creates a session whose first bindings are the fields of `globals`.
+    pub fn new(globals: &EnvDef) -> Session {
+        let globals: Vec<(String, Value)> = globals.fields().iter().map(|f| (f.name.clone(), f.default_value.clone())).collect();
+        Session { source_map: SourceMap::new(), bindings: globals.clone(), globals, lint_config: LintConfig::new(), unused_definitions: Vec::new() }
+    }
+
+    /// Sets the lints which are checked for every input.
+    pub fn with_lint_config(mut self, config: LintConfig) -> Session {
+        self.lint_config = config;
+        self
+    }
+
+    /// Every input is added to the source map, so that the spans of diagnostics can be rendered.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    pub fn source_map_mut(&mut self) -> &mut SourceMap {
+        &mut self.source_map
+    }
+
+    /// The current bindings, in the order they were first defined.
+    pub fn bindings(&self) -> &[(String, Value)] {
+        &self.bindings
+    }
+
+    /// Forgets every definition, restoring the globals the session was created with.
+    pub fn reset(&mut self) {
+        self.bindings = self.globals.clone();
+        self.unused_definitions.clear();
+    }
+
+    /// The environment definition in which inputs are evaluated, with a field for each binding.
+    pub fn env_def(&self) -> EnvDef {
+        self.bindings.iter()
+            .fold(EnvDefBuilder::new(), |builder, b| builder.with_item(&b.0, b.1.clone()))
+            .build()
+    }
+
+    fn bind(&mut self, name: &str, value: Value) {
+        match self.bindings.iter_mut().find(|b| b.0 == name) {
+            Some(binding) => binding.1 = value,
+            None => self.bindings.push((String::from(name), value))
+        }
+    }
+
+    /// Evaluates `input`, which is either an expression or a `let` definition.  Besides the lints on the
+    /// expression, a definition of the name of a global is reported, as is a definition whose value was not
+    /// used when its name is defined again.
+    pub fn eval(&mut self, input: &str) -> Response {
+        let file = self.source_map.add("<input>", String::from(input));
+        self.eval_at(file, Location::start(), input.len())
+    }
+
+    /// Evaluates each input of `source`, the text of the file `name`, in order.  See `split_inputs`.
+    pub fn load(&mut self, name: &str, source: String) -> Vec<Response> {
+        let file = self.source_map.add(name, source.clone());
+        //The last input is evaluated even if it is incomplete, to report its errors.
+        split_inputs(&source).into_iter().map(|(start, end)| self.eval_at(file, start, end)).collect()
+    }
+
+    /// Evaluates the input of `file` which starts at `start` and ends at the byte offset `end`.
+    fn eval_at(&mut self, file: FileId, start: Location, end: usize) -> Response {
+        let ParsedInput { header, expr, errors } = match parse_input(&self.source_map.file(file).unwrap().source, file, start, end) {
+            Ok(input) => input,
+            Err(error) => return Response { outcome: Outcome::Failed, diagnostics: vec![Diagnostic::from_error(&error)] }
+        };
+        if !errors.is_empty() {
+            return Response { outcome: Outcome::Failed, diagnostics: errors.iter().map(Diagnostic::from_error).collect() };
+        }
+        let mut warnings = lint(&expr, &self.lint_config);
+        if let Some(ref header) = header {
+            if self.lint_config.is_enabled(Lint::ShadowedGlobal) && self.globals.iter().any(|g| g.0 == header.name) {
+                warnings.push(LintWarning::new_with_span(Lint::ShadowedGlobal, header.name_span));
+            }
+        }
+        let mut diagnostics: Vec<Diagnostic> = warnings.iter().map(Diagnostic::from_error).collect();
+
+        let used: Vec<String> = self.unused_definitions.iter().filter(|d| expr.uses_variable(&d.0)).map(|d| d.0.clone()).collect();
+        let outcome = match execute_expr(expr, &self.env_def()) {
+            Ok(value) => {
+                self.unused_definitions.retain(|d| !used.contains(&d.0));
+                match header {
+                    Some(header) => {
+                        self.define(&header, &mut diagnostics);
+                        self.bind(&header.name, value.clone());
+                        Outcome::Defined(header.name, value)
+                    },
+                    None => {
+                        self.bind(LAST_RESULT, value.clone());
+                        Outcome::Value(value)
+                    }
+                }
+            },
+            Err(error) => {
+                diagnostics.push(Diagnostic::from_error(&error));
+                Outcome::Failed
+            }
+        };
+        Response { outcome, diagnostics }
+    }
+
+    /// Records the definition `header`, reporting the previous definition of its name if its value was unused.
+    fn define(&mut self, header: &LetHeader, diagnostics: &mut Vec<Diagnostic>) {
+        if let Some(i) = self.unused_definitions.iter().position(|d| d.0 == header.name) {
+            let (_, span) = self.unused_definitions.remove(i);
+            if self.lint_config.is_enabled(Lint::UnusedLet) {
+                diagnostics.push(Diagnostic::from_error(&LintWarning::new_with_span(Lint::UnusedLet, span)));
+            }
+        }
+        self.unused_definitions.push((header.name.clone(), header.name_span));
+    }
+
+    /// Parses the expression `input` without evaluating it.
+    pub fn parse(&mut self, input: &str) -> Result<Expr, Vec<Diagnostic>> {
+        let file = self.source_map.add("<input>", String::from(input));
+        let (expr, errors) = Parser::new(Lexer::new_with_file(input.chars(), file)).parse_recovering();
+        if errors.is_empty() {
+            Ok(expr)
+        } else {
+            Err(errors.iter().map(Diagnostic::from_error).collect())
+        }
+    }
+
+    /// Parses the expression `input` and resolves its variables against the current bindings.
+    pub fn resolve(&mut self, input: &str) -> Result<Expr, Vec<Diagnostic>> {
+        let expr = self.parse(input)?;
+        resolve_variables(expr, &self.env_def()).map_err(|e| vec![Diagnostic::from_error(&e)])
+    }
+
+    /// Returns the name of the type of the expression `input`.  `Int32` is the only type, so this only checks
+    /// that the expression is valid.
+    pub fn type_of(&mut self, input: &str) -> Result<&'static str, Vec<Diagnostic>> {
+        self.resolve(input).map(|_| "Int32")
+    }
+
+    /// Returns the completions of the word before the byte offset `cursor` of `line`:  the names of commands when
+    /// `line` is a command, otherwise the names of the bindings, and keywords at the start of the line.
+    pub fn complete(&self, line: &str, cursor: usize) -> Completion {
+        let before = &line[..cursor];
+        let start = before.char_indices().rev()
+            .find(|&(_, c)| !is_identifier_char(c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+
+        let command = before.trim_start();
+        let candidates: Vec<&str> = if command.starts_with(':') && !command.contains(char::is_whitespace) {
+            let start = cursor - command.len();
+            let candidates = COMMANDS.iter().map(|c| c.0).filter(|name| name.starts_with(command)).map(String::from).collect();
+            return Completion { start, candidates };
+        } else if before[start..].starts_with(is_digit) {
+            Vec::new()
+        } else {
+            let keywords = if before[..start].trim().is_empty() { KEYWORDS } else { &[] };
+            self.bindings.iter().map(|b| &b.0[..]).chain(keywords.iter().cloned()).collect()
+        };
+        let mut candidates: Vec<String> = candidates.into_iter().filter(|c| c.starts_with(&before[start..])).map(String::from).collect();
+        candidates.sort();
+        candidates.dedup();
+        Completion { start, candidates }
+    }
+
+    /// Returns the tokens of `input`, or the first error.
+    pub fn tokens(&mut self, input: &str) -> Result<Vec<Token>, Vec<Diagnostic>> {
+        let file = self.source_map.add("<input>", String::from(input));
+        Lexer::new_with_file(input.chars(), file).tokens().map_err(|e| vec![Diagnostic::from_error(&e)])
+    }
+}
+
+/// Returns the header if the text of `input` from `start` starts with the keyword `let`.
+fn parse_let(input: &str, file: FileId, start: Location) -> Result<Option<LetHeader>, SessionError> {
+    let mut lexer = Lexer::new_at(input[start.offset..].chars(), file, start);
+    let keyword_span = match lexer.next() {
+        LexResult::Ok(Token { kind: TokenKind::Identifier(ref keyword), span }) if keyword == "let" => span,
+        _ => return Ok(None)
+    };
+    let error = match lexer.next() {
+        LexResult::Ok(Token { kind: TokenKind::Identifier(name), span: name_span }) => match lexer.next_char('=') {
+            Some(equals_span) => return Ok(Some(LetHeader { keyword_span, name, name_span, equals_span })),
+            None => lexer.next()
+        },
+        next => next
+    };
+    let loc = match error {
+        LexResult::Ok(token) => token.span.start,
+        LexResult::EndOfInput(loc) => loc,
+        LexResult::Err(error) => error.span.start
+    };
+    Err(SessionError::new_with_span(SessionErrorKind::InvalidLet, Span::new(file, loc, loc)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::*;
+    use ast::printer;
+
+    fn globals() -> EnvDef {
+        EnvDefBuilder::new().with_item("a", Value::Int32(2)).build()
+    }
+
+    fn codes(response: &Response) -> Vec<&'static str> {
+        response.diagnostics.iter().filter_map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn definitions_are_kept_between_inputs() {
+        let mut session = Session::new(&globals());
+        assert_eq!(Outcome::Defined(String::from("x"), Value::Int32(6)), session.eval("let x = a * 3").outcome);
+        assert_eq!(Outcome::Value(Value::Int32(8)), session.eval("x + a").outcome);
+        assert_eq!(Outcome::Defined(String::from("a"), Value::Int32(1)), session.eval(" let  a=1").outcome);
+        assert_eq!(Outcome::Defined(String::from("y"), Value::Int32(1)), session.eval("let y /* = */ = a").outcome);
+        assert_eq!(Outcome::Value(Value::Int32(7)), session.eval("x + a").outcome);
+        assert_eq!(
+            vec![(String::from("a"), Value::Int32(1)), (String::from("x"), Value::Int32(6)), (String::from("_"), Value::Int32(7)), (String::from("y"), Value::Int32(1))],
+            session.bindings());
+    }
+
+    #[test]
+    fn last_result_is_bound_to_underscore() {
+        let mut session = Session::new(&globals());
+        session.eval("a + 1");
+        assert_eq!(Outcome::Value(Value::Int32(6)), session.eval("_ * 2").outcome);
+        assert_eq!(Outcome::Value(Value::Int32(7)), session.eval("_ + 1").outcome);
+        //A variable whose name only starts with `let` is not a definition.
+        session.eval("let letter = 1");
+        assert_eq!(Outcome::Value(Value::Int32(2)), session.eval("letter + 1").outcome);
+    }
+
+    #[test]
+    fn reset_restores_globals() {
+        let mut session = Session::new(&globals());
+        session.eval("let a = 10");
+        session.eval("let b = a");
+        session.reset();
+        assert_eq!(vec![(String::from("a"), Value::Int32(2))], session.bindings());
+        assert_eq!(vec!["E0301"], codes(&session.eval("b")));
+    }
+
+    #[test]
+    fn errors_do_not_change_bindings() {
+        let mut session = Session::new(&globals());
+        for input in &["let", "let 1 = 2", "let x 2", "let x = 1 +", "let x = 1 / 0"] {
+            let response = session.eval(input);
+            assert_eq!(Outcome::Failed, response.outcome, "{}", input);
+        }
+        assert_eq!(vec!["E0901"], codes(&session.eval("let x 2")));
+        assert_eq!(1, session.bindings().len());
+
+        //The spans of errors in the expression are within the whole input.
+        let response = session.eval("let x =   1 + )");
+        assert_eq!(vec!["E0202"], codes(&response));
+        assert_eq!(15, response.diagnostics[0].span.start.col_offset);
+        let response = session.eval("let x = ");
+        assert_eq!(vec!["E0205"], codes(&response));
+        assert_eq!(8, response.diagnostics[0].span.start.col_offset);
+    }
+
+    #[test]
+    fn incomplete_inputs() {
+        for input in &["1 +", "(1 + 2", "((1)", "1 /* comment", "let x =", "let x = /* */", ":ast (1", ":time 2 *"] {
+            assert!(is_incomplete(input), "{}", input);
+        }
+        for input in &["", "1 + 2", "1 + )", "let x 1", "(1) 2", ":env", ":ast", ":load (", ":unknown (", "1 $"] {
+            assert!(!is_incomplete(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(None, Command::parse(" 1 + 2"));
+        assert_eq!(Some(Ok(Command::Ast(String::from("1 +\n 2")))), Command::parse(" :ast  1 +\n 2 \n"));
+        assert_eq!(Some(Ok(Command::Load(String::from("a b.expr")))), Command::parse(":load a b.expr"));
+        assert_eq!(Some(Ok(Command::Reset)), Command::parse(":reset "));
+        assert!(Command::parse(":type").unwrap().is_err());
+        assert!(Command::parse(":env x").unwrap().is_err());
+        assert!(Command::parse(":what").unwrap().is_err());
+        for &(name, argument, _) in COMMANDS {
+            let input = format!("{} 1", name);
+            assert_eq!(argument.is_empty(), Command::parse(&input).unwrap().is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn load_evaluates_each_input() {
+        let mut session = Session::new(&globals());
+        let responses = session.load("defs.expr", String::from("// rates\nlet x = (a\n  + 1)\n\nlet y = x *\n 2 /* trailing */\n\ny - $\nx +"));
+        let outcomes: Vec<Outcome> = responses.iter().map(|r| r.outcome.clone()).collect();
+        assert_eq!(
+            vec![Outcome::Defined(String::from("x"), Value::Int32(3)), Outcome::Defined(String::from("y"), Value::Int32(6)), Outcome::Failed, Outcome::Failed],
+            outcomes);
+        //Spans are within the file.
+        let span = responses[2].diagnostics[0].span;
+        assert_eq!((8, 5), (span.start.line, span.start.col_offset));
+        assert_eq!(Some("$"), session.source_map().snippet(&span));
+        assert_eq!(vec!["E0203"], codes(&responses[3]));
+    }
+
+    #[test]
+    fn inspect_expressions() {
+        let mut session = Session::new(&globals());
+        assert_eq!("a + 1 * 2", printer::print(&session.parse("a + 1 * 2").unwrap()));
+        assert_eq!(Ok("Int32"), session.type_of("a + 1"));
+        assert_eq!(vec![Some("E0301")], session.type_of("b").unwrap_err().iter().map(|d| d.code).collect::<Vec<_>>());
+        let tokens = session.tokens("a+ 10").unwrap();
+        assert_eq!(vec![TokenKind::Identifier(String::from("a")), TokenKind::BinaryOperator(BinaryOp::Add), TokenKind::LiteralInt32(10)],
+                   tokens.iter().map(|t| t.kind.clone()).collect::<Vec<_>>());
+        assert_eq!(Some("10"), session.source_map().snippet(&tokens[2].span));
+        assert_eq!(Some("E0101"), session.tokens("a $").unwrap_err()[0].code);
+    }
+
+    #[test]
+    fn complete_names() {
+        let mut session = Session::new(&EnvDefBuilder::new().with_item("total", Value::Int32(1)).with_item("tax", Value::Int32(2)).build());
+        session.eval("let taxRate = 3");
+        let complete = |line: &str| {
+            let completion = session.complete(line, line.len());
+            (completion.start, completion.candidates)
+        };
+        let strings = |names: &[&str]| names.iter().map(|n| String::from(*n)).collect::<Vec<_>>();
+        assert_eq!((4, strings(&["tax", "taxRate"])), complete("1 + ta"));
+        assert_eq!((0, strings(&["tax", "taxRate", "total"])), complete("t"));
+        assert_eq!((0, strings(&["let"])), complete("le"));
+        assert_eq!((2, Vec::new()), complete("1 le"));
+        assert_eq!((4, Vec::new()), complete("1 + 1"));
+        assert_eq!((1, strings(&[":reset"])), complete(" :r"));
+        assert_eq!((6, strings(&["total"])), complete(":ast (to"));
+        assert_eq!((4, strings(&["tax", "taxRate", "total"])), complete("1 + "));
+    }
+
+    #[test]
+    fn lint_warnings_are_reported() {
+        let mut session = Session::new(&globals());
+        let response = session.eval("let y = 1 a");
+        assert_eq!(Outcome::Defined(String::from("y"), Value::Int32(2)), response.outcome);
+        assert_eq!(vec!["W0001"], codes(&response));
+    }
+
+    #[test]
+    fn definitions_are_linted() {
+        let mut session = Session::new(&globals());
+        assert_eq!(vec!["W0004"], codes(&session.eval("let a = 1")));
+        assert!(codes(&session.eval("let x = a")).is_empty());
+        //The first `x` was never used when it is defined again.
+        let response = session.eval("let x = 2");
+        assert_eq!(vec!["W0003"], codes(&response));
+        assert_eq!(Some("x"), session.source_map().snippet(&response.diagnostics[0].span));
+        session.eval("x + 1");
+        assert!(codes(&session.eval("let x = 3")).is_empty());
+        //A definition which fails does not use the names it refers to.
+        session.eval("let y = x / 0");
+        assert_eq!(vec!["W0003"], codes(&session.eval("let x = 4")));
+
+        let mut session = Session::new(&globals()).with_lint_config(LintConfig::new().disable(Lint::UnusedLet).disable(Lint::ShadowedGlobal));
+        for input in &["let a = 1", "let x = 1", "let x = 2"] {
+            assert!(codes(&session.eval(input)).is_empty(), "{}", input);
+        }
+    }
+}
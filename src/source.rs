@@ -3,37 +3,42 @@
   This module contains types related to source code.
 */
 use std::fmt::*;
+use std::cmp;
 
-/// A location within a source file (line & column).
+/// A location within a source file (line, column & byte offset) of a character.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Location {
     /// The line of the location, starting at 1.
     pub line: u32,
-    /// The byte offset from the start of the line, starting at 1.
-    pub col_offset: u32
+    /// The number of characters from the start of the line, starting at 1.  A line ending is the
+    /// last character of its line.
+    pub col_offset: u32,
+    /// The byte offset from the start of the source, starting at 0.
+    pub offset: usize
 }
 
 impl Location {
     pub fn start() -> Location {
-        Location { line: 1, col_offset: 1,  }
+        Location { line: 1, col_offset: 1, offset: 0 }
     }
 
     #[cfg(test)]
     pub fn unknown() -> Location {
-        Location::new(0, 0)
+        Location::new(0, 0, 0)
     }
 
-    #[cfg(test)]
-    pub fn new(line: u32, col_offset: u32) -> Location {
-        Location { line, col_offset }
-    }
-
-    pub fn next(&self) -> Location {
-        Location { line: self.line, col_offset: self.col_offset + 1 }
+    pub fn new(line: u32, col_offset: u32, offset: usize) -> Location {
+        Location { line, col_offset, offset }
     }
 
-    pub fn next_line(&self) -> Location {
-        Location { line: self.line + 1, col_offset: 0 }
+    /// Returns the location of the character following `chr`, which is at this location.
+    pub fn advance(&self, chr: char) -> Location {
+        let offset = self.offset + chr.len_utf8();
+        if chr == '\n' {
+            Location { line: self.line + 1, col_offset: 1, offset }
+        } else {
+            Location { line: self.line, col_offset: self.col_offset + 1, offset }
+        }
     }
 }
 
@@ -43,26 +48,65 @@ impl Display for Location {
     }
 }
 
-/// A span within a source file indicated by a starting Location and ending Location.
+/// Identifies a source file within a `source_map::SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub u32);
+
+impl FileId {
+    /// The file of source which was not added to a `SourceMap`.
+    pub const NONE: FileId = FileId(0);
+}
+
+/// A span within a source file indicated by a starting Location and ending Location.  Both are the
+/// locations of characters within the span, so the end is inclusive.
 #[derive(Clone, Copy, PartialEq)]
 pub struct Span {
+    pub file: FileId,
     pub start: Location,
     pub end: Location
 }
 
 impl Span {
+    pub fn new(file: FileId, start: Location, end: Location) -> Span {
+        Span { file, start, end }
+    }
+
+    /// Creates a span of `FileId::NONE`.
     pub fn from_location(loc: Location) -> Span {
-        Span { start: loc.clone(), end: loc }
+        Span::from_locations(loc, loc)
     }
 
+    /// Creates a span of `FileId::NONE`.
     pub fn from_locations(start: Location, end: Location) -> Span {
-        Span { start, end }
+        Span::new(FileId::NONE, start, end)
+    }
+
+    /// Returns the span from the start of this span to the end of `end`, in the file of this span.
+    pub fn to(&self, end: Span) -> Span {
+        Span::new(self.file, self.start, end.end)
     }
 
     #[cfg(test)]
     pub fn unknown() -> Span {
         Span::from_locations(Location::unknown(), Location::unknown())
     }
+
+    /// Returns the byte range of the span within `source`, the text it was created from.  The range
+    /// is clamped to `source`, so the span of the end of input is empty.  A span whose end is not at a
+    /// character of `source` extends to the end of `source`.
+    pub fn byte_range(&self, source: &str) -> ::std::ops::Range<usize> {
+        let start = cmp::min(self.start.offset, source.len());
+        let end = match source.get(self.end.offset..).and_then(|rest| rest.chars().next()) {
+            Some(chr) => self.end.offset + chr.len_utf8(),
+            None => source.len()
+        };
+        start..cmp::max(start, end)
+    }
+
+    /// Returns the text of the span within `source`, the text it was created from.
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.byte_range(source)]
+    }
 }
 
 impl Debug for Span {
@@ -71,3 +115,26 @@ impl Debug for Span {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_advance() {
+        let loc = Location::start().advance('\u{e9}');
+        assert_eq!(Location::new(1, 2, 2), loc);
+        assert_eq!(Location::new(2, 1, 3), loc.advance('\n'));
+    }
+
+    #[test]
+    fn span_text() {
+        let source = "ab\u{e9}\ncd";
+        let span = |start: Location, end: Location| Span::from_locations(start, end);
+        assert_eq!("b\u{e9}", span(Location::new(1, 2, 1), Location::new(1, 3, 2)).text(source));
+        assert_eq!("\u{e9}\nc", span(Location::new(1, 3, 2), Location::new(2, 1, 5)).text(source));
+        assert_eq!("", Span::from_location(Location::new(2, 3, 7)).text(source));
+        //The end is within the `é`, as for a span of another text.
+        assert_eq!(1..7, span(Location::new(1, 2, 1), Location::new(1, 3, 3)).byte_range(source));
+    }
+}
@@ -0,0 +1,169 @@
+/*
+  Owns the text of every loaded source file and maps the `FileId` of a `Span` back to its file, so
+  that errors from any file can be reported with the file name, line and column and the source text.
+*/
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use source::*;
+
+/// A line and column, both starting at 1.  Columns are counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineCol {
+    pub line: u32,
+    pub col: u32
+}
+
+/// The file and line/column range of a `Span`.  `end` is the position of the last character of the span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanLocation<'a> {
+    pub file_name: &'a str,
+    pub start: LineCol,
+    pub end: LineCol
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub id: FileId,
+    pub name: String,
+    pub source: String,
+    /// The byte offset of the start of each line.
+    line_starts: Vec<usize>
+}
+
+impl SourceFile {
+    fn new(id: FileId, name: String, source: String) -> SourceFile {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        SourceFile { id, name, source, line_starts }
+    }
+
+    pub fn line_count(&self) -> u32 {
+        self.line_starts.len() as u32
+    }
+
+    /// Returns the text of `line` (starting at 1) without its line ending.
+    pub fn line_text(&self, line: u32) -> Option<&str> {
+        let index = (line as usize).checked_sub(1)?;
+        let start = *self.line_starts.get(index)?;
+        let end = self.line_starts.get(index + 1).map_or(self.source.len(), |next| next - 1);
+        let text = &self.source[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Returns the line and column of the character at byte `offset`.  Offsets past the end of the
+    /// source are clamped to the end of the source.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let index = match self.line_starts.binary_search(&offset) {
+            Ok(index) => index,
+            Err(index) => index - 1
+        };
+        let col = self.source[self.line_starts[index]..offset].chars().count() + 1;
+        LineCol { line: index as u32 + 1, col: col as u32 }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    /// Adds a source file and returns its id.  `name` is used when reporting errors and need not be a path.
+    pub fn add(&mut self, name: &str, source: String) -> FileId {
+        //FileId(0) is FileId::NONE.
+        let id = FileId(self.files.len() as u32 + 1);
+        self.files.push(SourceFile::new(id, String::from(name), source));
+        id
+    }
+
+    /// Reads the file at `path` and adds it, named after `path`.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<FileId> {
+        let source = fs::read_to_string(path.as_ref())?;
+        Ok(self.add(&path.as_ref().to_string_lossy(), source))
+    }
+
+    pub fn file(&self, id: FileId) -> Option<&SourceFile> {
+        (id.0 as usize).checked_sub(1).and_then(|index| self.files.get(index))
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    pub fn file_name(&self, span: &Span) -> Option<&str> {
+        self.file(span.file).map(|f| &f.name[..])
+    }
+
+    /// Returns the source text of `span`.
+    pub fn snippet(&self, span: &Span) -> Option<&str> {
+        self.file(span.file).map(|f| span.text(&f.source))
+    }
+
+    /// Returns the file name and line/column range of `span`, computed from its byte offsets.
+    pub fn lookup(&self, span: &Span) -> Option<SpanLocation<'_>> {
+        self.file(span.file).map(|f| SpanLocation {
+            file_name: &f.name,
+            start: f.line_col(span.start.offset),
+            end: f.line_col(span.end.offset)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_with_file;
+
+    #[test]
+    fn source_map_assigns_ids() {
+        let mut map = SourceMap::new();
+        let a = map.add("a.rule", String::from("1"));
+        let b = map.add("b.rule", String::from("2"));
+        assert!(a != b && a != FileId::NONE && b != FileId::NONE);
+        assert_eq!("b.rule", map.file(b).unwrap().name);
+        assert!(map.file(FileId::NONE).is_none());
+        assert!(map.file(FileId(3)).is_none());
+    }
+
+    #[test]
+    fn source_map_lookup() {
+        let mut map = SourceMap::new();
+        map.add("first.rule", String::from("1 + 1"));
+        let file = map.add("second.rule", String::from("foo +\r\n  (bar * 2)\n"));
+
+        let expr = parse_with_file(&map.file(file).unwrap().source, file).unwrap();
+        assert_eq!(Some("second.rule"), map.file_name(&expr.span));
+        assert_eq!(Some("foo +\r\n  (bar * 2)"), map.snippet(&expr.span));
+        assert_eq!(
+            Some(SpanLocation { file_name: "second.rule", start: LineCol { line: 1, col: 1 }, end: LineCol { line: 2, col: 11 } }),
+            map.lookup(&expr.span));
+
+        let err = parse_with_file("1 +", file).unwrap_err();
+        assert_eq!(file, err.span.file);
+    }
+
+    #[test]
+    fn source_file_lines() {
+        let mut map = SourceMap::new();
+        let file = map.add("lines", String::from("ab\r\n\u{e9}c\n"));
+        let file = map.file(file).unwrap();
+        assert_eq!(3, file.line_count());
+        assert_eq!(Some("ab"), file.line_text(1));
+        assert_eq!(Some("\u{e9}c"), file.line_text(2));
+        assert_eq!(Some(""), file.line_text(3));
+        assert_eq!(None, file.line_text(0));
+        assert_eq!(None, file.line_text(4));
+        assert_eq!(LineCol { line: 2, col: 2 }, file.line_col(6));
+        assert_eq!(LineCol { line: 3, col: 1 }, file.line_col(100));
+    }
+}
@@ -0,0 +1,207 @@
+/*
+  A concrete syntax tree built from the tokens of `syntax::tokenize`.
+
+  The tree has the same shape as the `Expr` the parser would produce, but every token, including its
+  trivia, is kept in source order so `SyntaxNode::text` reproduces the input byte-for-byte.  Parsing
+  never fails:  unexpected tokens are wrapped in `NodeKind::Error` nodes and anything missing, such as
+  the right operand of `1 +`, is represented by an empty `NodeKind::Error` node.
+*/
+
+use common::*;
+use parser::get_binary_op_precedence;
+use syntax::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeKind {
+    /// The whole source:  a sequence of expressions followed by the `EndOfInput` token.
+    Root,
+    Literal,
+    Name,
+    /// The left operand, the operator token and the right operand.
+    Binary,
+    /// An open paren, a sequence of expressions and a close paren.
+    Paren,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    fn new(kind: NodeKind, children: Vec<SyntaxElement>) -> SyntaxNode {
+        SyntaxNode { kind, children }
+    }
+
+    /// Returns the source text of the node, including all trivia.
+    pub fn text(&self) -> String {
+        self.tokens().iter().map(|t| t.full_text()).collect()
+    }
+
+    /// Returns the tokens of the node in source order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SyntaxToken>) {
+        for child in &self.children {
+            match *child {
+                SyntaxElement::Node(ref node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(ref token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// Returns the child nodes, skipping tokens.
+    pub fn child_nodes(&self) -> Vec<&SyntaxNode> {
+        self.children.iter().filter_map(|c| match *c {
+            SyntaxElement::Node(ref node) => Some(node),
+            SyntaxElement::Token(_) => None
+        }).collect()
+    }
+
+    /// Returns the first child which is a token.
+    pub fn first_child_token(&self) -> Option<&SyntaxToken> {
+        self.children.iter().filter_map(|c| match *c {
+            SyntaxElement::Token(ref token) => Some(token),
+            SyntaxElement::Node(_) => None
+        }).next()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.kind == NodeKind::Error || self.child_nodes().iter().any(|n| n.has_errors())
+    }
+}
+
+/// Returns the operator represented by a token of kind `kind`, if any.
+pub fn binary_op(kind: TokenKind) -> Option<BinaryOp> {
+    match kind {
+        TokenKind::Plus => Some(BinaryOp::Add),
+        TokenKind::Minus => Some(BinaryOp::Sub),
+        TokenKind::Star => Some(BinaryOp::Mul),
+        TokenKind::Slash => Some(BinaryOp::Div),
+        TokenKind::Percent => Some(BinaryOp::Mod),
+        _ => None
+    }
+}
+
+/// Builds the concrete syntax tree of `source`.  The result is always a `NodeKind::Root` node.
+pub fn parse(source: &str) -> SyntaxNode {
+    let mut parser = CstParser { tokens: tokenize(source).into_iter().rev().collect() };
+    let mut children = Vec::new();
+    while parser.peek() != TokenKind::EndOfInput {
+        children.push(SyntaxElement::Node(parser.parse_expr(0)));
+    }
+    children.push(parser.bump());
+    SyntaxNode::new(NodeKind::Root, children)
+}
+
+/// Mirrors the Pratt parser in `parser.rs`.
+struct CstParser {
+    /// The remaining tokens, in reverse order.
+    tokens: Vec<SyntaxToken>,
+}
+
+impl CstParser {
+    fn peek(&self) -> TokenKind {
+        //The EndOfInput token is never consumed except by `parse`.
+        self.tokens.last().unwrap().kind
+    }
+
+    fn bump(&mut self) -> SyntaxElement {
+        SyntaxElement::Token(self.tokens.pop().unwrap())
+    }
+
+    fn missing() -> SyntaxElement {
+        SyntaxElement::Node(SyntaxNode::new(NodeKind::Error, Vec::new()))
+    }
+
+    fn parse_expr(&mut self, precedence: u32) -> SyntaxNode {
+        let mut expr = self.parse_prefix();
+        while let Some(op) = binary_op(self.peek()) {
+            let next_precedence = get_binary_op_precedence(&op);
+            if precedence >= next_precedence {
+                break;
+            }
+            let op_token = self.bump();
+            let right = match self.peek() {
+                TokenKind::EndOfInput => CstParser::missing(),
+                _ => SyntaxElement::Node(self.parse_expr(next_precedence))
+            };
+            expr = SyntaxNode::new(NodeKind::Binary, vec![SyntaxElement::Node(expr), op_token, right]);
+        }
+        expr
+    }
+
+    fn parse_prefix(&mut self) -> SyntaxNode {
+        match self.peek() {
+            TokenKind::Int => SyntaxNode::new(NodeKind::Literal, vec![self.bump()]),
+            TokenKind::Identifier => SyntaxNode::new(NodeKind::Name, vec![self.bump()]),
+            TokenKind::OpenParen => {
+                let mut children = vec![self.bump()];
+                loop {
+                    match self.peek() {
+                        TokenKind::CloseParen => {
+                            children.push(self.bump());
+                            break;
+                        },
+                        TokenKind::EndOfInput => {
+                            children.push(CstParser::missing());
+                            break;
+                        },
+                        _ => children.push(SyntaxElement::Node(self.parse_expr(0)))
+                    }
+                }
+                SyntaxNode::new(NodeKind::Paren, children)
+            },
+            TokenKind::EndOfInput => SyntaxNode::new(NodeKind::Error, Vec::new()),
+            _ => SyntaxNode::new(NodeKind::Error, vec![self.bump()])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(node: &SyntaxNode) -> String {
+        let children: Vec<String> = node.children.iter().map(|c| match *c {
+            SyntaxElement::Node(ref n) => shape(n),
+            SyntaxElement::Token(ref t) => t.text.clone(),
+        }).collect();
+        format!("{:?}[{}]", node.kind, children.join(" "))
+    }
+
+    #[test]
+    fn parse_reproduces_source() {
+        for source in &["", " 1 +\n 2 * ( a  b ) ", "1 + ", "(1", ") 2 $", "1 2\n\n3\n"] {
+            assert_eq!(*source, parse(source).text());
+        }
+    }
+
+    #[test]
+    fn parse_shape() {
+        assert_eq!(
+            "Root[Binary[Literal[1] + Binary[Paren[( Name[a] Name[b] )] * Literal[2]]] Literal[3] ]",
+            shape(&parse("1 + (a b) * 2 3")));
+        assert!(!parse("1 + (a b) * 2 3").has_errors());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("Root[Binary[Literal[1] + Error[]] ]", shape(&parse("1 +")));
+        assert_eq!("Root[Paren[( Literal[1] Error[]] ]", shape(&parse("(1")));
+        assert_eq!("Root[Error[)] Literal[2] ]", shape(&parse(") 2")));
+        assert!(parse("(1").has_errors());
+    }
+}
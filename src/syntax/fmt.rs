@@ -0,0 +1,315 @@
+/*
+  Reformats source code.
+
+  Each top-level expression is placed on its own line and single blank lines between them are kept.
+  Binary operators are surrounded by single spaces and parentheses hug their contents.  Parentheses
+  written in the source are kept, even when they are redundant.  Comments are kept next to the token
+  they were attached to; the token following a `//` comment is always placed on a new line.
+
+  When an expression does not fit in `FormatOptions::line_width` it is broken before the operators of
+  its outermost chain of binary operators of equal precedence, and inside its outermost parentheses, with
+  continuation lines indented by `FormatOptions::indent` spaces.  Nested expressions are only broken if
+  they still do not fit.  This is the algorithm from Wadler's "A prettier printer":  the source is
+  first translated to a `Doc` describing where lines may be broken, which is then laid out.
+*/
+
+use parser::*;
+use syntax::*;
+use syntax::cst::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    /// The number of spaces continuation lines are indented by.
+    pub indent: usize,
+    /// The width lines should fit in.  Lines may still be longer if they cannot be broken.
+    pub line_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions { indent: 4, line_width: 100 }
+    }
+}
+
+/// Reformats `source`.  Source which does not parse is not formatted and its first error is returned.
+pub fn fmt(source: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    let root = parse(source);
+    if let Err(e) = ::parse(source) {
+        //Source with nothing but trivia is valid, it's just empty.
+        if e.kind != ParseErrorKind::EmptyExpr || !root.child_nodes().is_empty() {
+            return Err(e);
+        }
+    }
+
+    let mut out = String::new();
+    for (i, expr) in root.child_nodes().iter().enumerate() {
+        if i > 0 && blank_line_before(expr) {
+            out.push('\n');
+        }
+        //Comments before an expression are placed on the lines before it, rather than in its first group.
+        let mut expr = (*expr).clone();
+        for trivia in take_leading_comments(&mut expr) {
+            out.push_str(&trivia.text);
+            out.push('\n');
+        }
+        layout(&to_doc(&expr), options, &mut out);
+        out.push('\n');
+    }
+    //Comments after the last expression are attached to the end of input token.
+    let tokens = root.tokens();
+    for trivia in tokens[tokens.len() - 1].leading.iter().filter(|t| is_comment(t)) {
+        out.push_str(&trivia.text);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Removes the comments from the leading trivia of the first token of `node` and returns them.
+fn take_leading_comments(node: &mut SyntaxNode) -> Vec<Trivia> {
+    match node.children.first_mut() {
+        Some(&mut SyntaxElement::Node(ref mut child)) => take_leading_comments(child),
+        Some(&mut SyntaxElement::Token(ref mut token)) => {
+            let (comments, rest) = token.leading.drain(..).partition(is_comment);
+            token.leading = rest;
+            comments
+        },
+        None => Vec::new()
+    }
+}
+
+fn is_comment(trivia: &Trivia) -> bool {
+    trivia.kind == TriviaKind::LineComment || trivia.kind == TriviaKind::BlockComment
+}
+
+/// Returns true if there is at least one empty line before `node`:  two line endings with nothing but
+/// whitespace between them.  The line ending of a comment does not start an empty line.
+fn blank_line_before(node: &SyntaxNode) -> bool {
+    let mut after_newline = false;
+    for trivia in &node.tokens()[0].leading {
+        match trivia.kind {
+            TriviaKind::Newline if after_newline => return true,
+            TriviaKind::Newline => after_newline = true,
+            TriviaKind::Whitespace => {},
+            TriviaKind::LineComment | TriviaKind::BlockComment => after_newline = false
+        }
+    }
+    false
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Doc {
+    Text(String),
+    /// A space, or a line break when the enclosing group is broken.
+    Line,
+    /// Nothing, or a line break when the enclosing group is broken.
+    SoftLine,
+    /// A space, unless at the start of a line.
+    Space,
+    /// A `//` comment.  Whatever follows is placed on a new line and the enclosing groups are broken.
+    LineComment(String),
+    Concat(Vec<Doc>),
+    /// Laid out on a single line if it fits, otherwise its lines are broken.
+    Group(Vec<Doc>),
+    /// Increases the indentation of lines broken within.
+    Nest(Vec<Doc>),
+}
+
+fn comment_doc(trivia: &Trivia) -> Doc {
+    match trivia.kind {
+        TriviaKind::LineComment => Doc::LineComment(trivia.text.clone()),
+        _ => Doc::Text(trivia.text.clone())
+    }
+}
+
+/// Returns the doc for `token`, with its text replaced by `text`, surrounded by its comments.
+fn token_doc(token: &SyntaxToken, text: &str) -> Doc {
+    let mut docs = Vec::new();
+    for trivia in token.leading.iter().filter(|t| is_comment(t)) {
+        docs.push(comment_doc(trivia));
+        docs.push(Doc::Space);
+    }
+    docs.push(Doc::Text(String::from(text)));
+    for trivia in token.trailing.iter().filter(|t| is_comment(t)) {
+        docs.push(Doc::Space);
+        docs.push(comment_doc(trivia));
+    }
+    Doc::Concat(docs)
+}
+
+/// The doc of the first token of `node`, which must have one.
+fn first_token_doc(node: &SyntaxNode, text: &str) -> Doc {
+    token_doc(node.first_child_token().unwrap(), text)
+}
+
+fn to_doc(node: &SyntaxNode) -> Doc {
+    match node.kind {
+        NodeKind::Literal | NodeKind::Name => {
+            let token = node.first_child_token().unwrap();
+            token_doc(token, &token.text)
+        },
+        NodeKind::Binary => {
+            //Left associative chains of the same precedence are broken together.
+            let mut rest = Vec::new();
+            let mut first = node;
+            let precedence = binary_precedence(node);
+            while first.kind == NodeKind::Binary && binary_precedence(first) == precedence {
+                let operands = first.child_nodes();
+                let op = first.first_child_token().unwrap();
+                rest.push(Doc::Line);
+                rest.push(Doc::Concat(vec![token_doc(op, &op.text), Doc::Space]));
+                rest.push(to_doc(operands[1]));
+                first = operands[0];
+            }
+            //Each operator and right operand was pushed in reverse order.
+            let mut nest: Vec<Doc> = Vec::new();
+            for chunk in rest.chunks(3).rev() {
+                nest.extend_from_slice(chunk);
+            }
+            Doc::Group(vec![to_doc(first), Doc::Nest(nest)])
+        },
+        NodeKind::Paren => {
+            let close = match node.children[node.children.len() - 1] {
+                SyntaxElement::Token(ref token) => token_doc(token, ")"),
+                SyntaxElement::Node(_) => Doc::Text(String::from(")"))
+            };
+            let mut inner = vec![Doc::SoftLine];
+            for (i, expr) in node.child_nodes().iter().enumerate() {
+                if i > 0 {
+                    inner.push(Doc::Line);
+                }
+                inner.push(to_doc(expr));
+            }
+            Doc::Group(vec![first_token_doc(node, "("), Doc::Nest(inner), Doc::SoftLine, close])
+        },
+        //The source parsed, so there should be no errors, but if there are they're kept verbatim.
+        NodeKind::Root | NodeKind::Error => Doc::Text(String::from(node.text().trim()))
+    }
+}
+
+fn binary_precedence(node: &SyntaxNode) -> u32 {
+    let op = node.first_child_token().and_then(|t| binary_op(t.kind)).unwrap();
+    get_binary_op_precedence(&op)
+}
+
+/// A `Doc` waiting to be laid out with the indentation and mode of its enclosing group.
+type Command<'a> = (usize, bool, &'a Doc);
+
+fn layout(doc: &Doc, options: &FormatOptions, out: &mut String) {
+    let mut col = 0;
+    //Set after a line comment, until the next line break.
+    let mut pending_newline = false;
+    let mut stack: Vec<Command> = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        let newline = match *doc {
+            Doc::Line | Doc::SoftLine => !flat || pending_newline,
+            Doc::Text(_) | Doc::Space | Doc::LineComment(_) => pending_newline,
+            _ => false
+        };
+        if newline {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+            col = indent;
+            pending_newline = false;
+        }
+        match *doc {
+            Doc::Text(ref text) | Doc::LineComment(ref text) => {
+                out.push_str(text);
+                col += text.chars().count();
+                pending_newline = matches!(*doc, Doc::LineComment(_));
+            },
+            Doc::Line | Doc::Space if !newline => {
+                out.push(' ');
+                col += 1;
+            },
+            Doc::Line | Doc::SoftLine | Doc::Space => {},
+            Doc::Concat(ref docs) => {
+                stack.extend(docs.iter().rev().map(|d| (indent, flat, d)));
+            },
+            Doc::Group(ref docs) => {
+                let flat = flat || fits(options.line_width as isize - col as isize, (indent, true, doc), &stack);
+                stack.extend(docs.iter().rev().map(|d| (indent, flat, d)));
+            },
+            Doc::Nest(ref docs) => {
+                stack.extend(docs.iter().rev().map(|d| (indent + options.indent, flat, d)));
+            }
+        }
+    }
+}
+
+/// Returns true if `next`, followed by `rest` up to its first line break, fits in `width` columns.
+fn fits(mut width: isize, next: Command, rest: &[Command]) -> bool {
+    let mut rest = rest.iter().rev();
+    let mut stack = vec![next];
+    while width >= 0 {
+        let (indent, flat, doc) = match stack.pop() {
+            Some(command) => command,
+            None => match rest.next() {
+                Some(&command) => command,
+                None => return true
+            }
+        };
+        match *doc {
+            Doc::Text(ref text) => width -= text.chars().count() as isize,
+            //Anything after a line comment is on another line, so the group must be broken if it continues.
+            Doc::LineComment(_) => return stack.is_empty(),
+            Doc::Line | Doc::SoftLine if !flat => return true,
+            Doc::Line | Doc::Space => width -= 1,
+            Doc::SoftLine => {},
+            Doc::Group(ref docs) | Doc::Nest(ref docs) | Doc::Concat(ref docs) =>
+                stack.extend(docs.iter().rev().map(|d| (indent, flat, d)))
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt_width(source: &str, line_width: usize) -> String {
+        fmt(source, &FormatOptions { indent: 2, line_width }).unwrap()
+    }
+
+    #[test]
+    fn fmt_normalizes_spacing() {
+        assert_eq!("1 + 2 * (a - b)\n", fmt_width("  1+2 *( a-b )  ", 80));
+        assert_eq!("1\n2\n\n3\n", fmt_width("1 2\n\n\n\n  3", 80));
+        assert_eq!("", fmt_width("  \n ", 80));
+    }
+
+    #[test]
+    fn fmt_breaks_long_lines() {
+        assert_eq!("aaaa\n  + bbbb * cccc\n  - dddd\n", fmt_width("aaaa + bbbb * cccc - dddd", 20));
+        assert_eq!("(\n  aaaa\n    + bbbb\n  cccc\n)\n", fmt_width("(aaaa + bbbb cccc)", 10));
+        assert_eq!("(aaaa + bbbb)\n  * cccc\n", fmt_width("(aaaa + bbbb) * cccc", 15));
+    }
+
+    #[test]
+    fn fmt_keeps_comments() {
+        assert_eq!("// Rule\n1 + /* two */ 2 // end\n", fmt_width("// Rule\n1+/* two */2   // end", 80));
+        assert_eq!("1\n  + // one\n  2\n  - 3\n", fmt_width("1 + // one\n 2 - 3", 80));
+        assert_eq!("(\n  a // x\n)\n", fmt_width("(a // x\n)", 80));
+        assert_eq!("1 // a\n/* b */\n", fmt_width("1 // a\n\n   /* b */", 80));
+        assert_eq!("// only\n", fmt_width("  // only", 80));
+        assert_eq!("1 + 2 // c\n// d\n3\n", fmt("1 + 2 // c\n// d\n3", &FormatOptions::default()).unwrap());
+        assert_eq!("1\n\n// d\n3\n", fmt_width("1\n \t\n// d\n3", 80));
+    }
+
+    #[test]
+    fn fmt_is_idempotent() {
+        let source = "foo * (bar + 1000 % baz) - 7 (a b (c d e)) x1 / (y1 - z1 * (w1 + 2) - 3)\n\n\n1 + 2 \
+                      // c1\n(/* c2 */ a // c3\n - /* c4 */ b) /* c5 */ // c6";
+        for width in 1..60 {
+            let once = fmt_width(source, width);
+            assert_eq!(once, fmt_width(&once, width));
+            assert_eq!(::parse(source).unwrap(), ::parse(&once).unwrap());
+        }
+    }
+
+    #[test]
+    fn fmt_rejects_invalid_source() {
+        let options = FormatOptions::default();
+        assert_eq!(ParseErrorKind::UnclosedParen, fmt("(1 + 2", &options).unwrap_err().kind);
+        assert_eq!(ParseErrorKind::EmptyExpr, fmt("()", &options).unwrap_err().kind);
+    }
+}
@@ -0,0 +1,261 @@
+/*
+  A lossless view of source code for tooling which rewrites source, such as the formatter.
+
+  Unlike `lexer::Lexer`, which discards whitespace and comments, `tokenize` keeps every byte of the
+  input:  the whitespace and comments around each token are recorded as trivia attached to the token.
+  `cst::parse` arranges the tokens into a concrete syntax tree which reproduces the input exactly, even when
+  it contains errors.
+
+  The rules for where tokens and comments start and end are those of the lexer (`lexer::token_start`,
+  `lexer::read_line_comment` and `lexer::read_block_comment`), so the two always agree.
+*/
+
+pub mod cst;
+pub mod fmt;
+
+use common::BinaryOp;
+use lexer::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Int,
+    Identifier,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    OpenParen,
+    CloseParen,
+    /// A character which does not start any token.
+    Error,
+    /// An empty token at the end of input which holds the trivia following the last token.
+    EndOfInput,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    /// A run of spaces and tabs.
+    Whitespace,
+    /// A single line ending, either "\n" or "\r\n".
+    Newline,
+    /// A `//` comment, not including the line ending.
+    LineComment,
+    /// A `/* */` comment, including any nested comments.  Unterminated comments extend to the end of input.
+    BlockComment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    pub kind: TokenKind,
+    pub text: String,
+    /// The byte offset of `text` within the source.
+    pub offset: usize,
+    /// Trivia between the previous token's trailing trivia and this token.
+    pub leading: Vec<Trivia>,
+    /// Trivia following this token on the same line.  Does not include the line ending.
+    pub trailing: Vec<Trivia>,
+}
+
+impl SyntaxToken {
+    /// Returns the source text of the token including its trivia.
+    pub fn full_text(&self) -> String {
+        let mut text = String::new();
+        for t in &self.leading {
+            text.push_str(&t.text);
+        }
+        text.push_str(&self.text);
+        for t in &self.trailing {
+            text.push_str(&t.text);
+        }
+        text
+    }
+}
+
+/// Splits `source` into tokens.  The last token is always `TokenKind::EndOfInput` and concatenating the
+/// `full_text` of every token gives back `source`.
+pub fn tokenize(source: &str) -> Vec<SyntaxToken> {
+    let mut cursor = Cursor { source, pos: 0 };
+    let mut tokens = Vec::new();
+    loop {
+        let leading = cursor.read_trivia(true);
+        let offset = cursor.pos;
+        let kind = match cursor.peek() {
+            None => {
+                tokens.push(SyntaxToken { kind: TokenKind::EndOfInput, text: String::new(), offset, leading, trailing: Vec::new() });
+                return tokens;
+            },
+            Some(c) => cursor.read_token(c)
+        };
+        let text = String::from(&source[offset..cursor.pos]);
+        let trailing = cursor.read_trivia(false);
+        tokens.push(SyntaxToken { kind, text, offset, leading, trailing });
+    }
+}
+
+struct Cursor<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl <'a> CharSource for Cursor<'a> {
+    fn peek_n(&mut self, n: u32) -> Option<char> {
+        self.source[self.pos..].chars().nth(n as usize)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+}
+
+impl <'a> Cursor<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn eat_while(&mut self, cond: fn(char) -> bool) {
+        while let Some(c) = self.peek() {
+            if !cond(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// Reads the token starting with `c` and returns its kind.
+    fn read_token(&mut self, c: char) -> TokenKind {
+        self.pos += c.len_utf8();
+        match token_start(c) {
+            TokenStart::Operator(BinaryOp::Add) => TokenKind::Plus,
+            TokenStart::Operator(BinaryOp::Sub) => TokenKind::Minus,
+            TokenStart::Operator(BinaryOp::Mul) => TokenKind::Star,
+            TokenStart::Operator(BinaryOp::Div) => TokenKind::Slash,
+            TokenStart::Operator(BinaryOp::Mod) => TokenKind::Percent,
+            TokenStart::OpenParen => TokenKind::OpenParen,
+            TokenStart::CloseParen => TokenKind::CloseParen,
+            TokenStart::Integer => {
+                self.eat_while(is_digit);
+                TokenKind::Int
+            },
+            TokenStart::Identifier => {
+                self.eat_while(is_identifier_char);
+                TokenKind::Identifier
+            },
+            TokenStart::Invalid => TokenKind::Error
+        }
+    }
+
+    /// Reads trivia up to the next token.  Unless `multiline` is set, stops before the first line ending.
+    fn read_trivia(&mut self, multiline: bool) -> Vec<Trivia> {
+        let mut trivia = Vec::new();
+        loop {
+            let start = self.pos;
+            let crlf = self.source[self.pos..].starts_with("\r\n");
+            let kind = match (comment_start(self), self.peek()) {
+                (Some(CommentKind::Line), _) => {
+                    read_line_comment(self);
+                    TriviaKind::LineComment
+                },
+                //An unterminated comment extends to the end of input.
+                (Some(CommentKind::Block), _) => {
+                    read_block_comment(self);
+                    TriviaKind::BlockComment
+                },
+                (None, Some('\n')) if multiline => {
+                    self.pos += 1;
+                    TriviaKind::Newline
+                },
+                (None, Some('\r')) if multiline && crlf => {
+                    self.pos += 2;
+                    TriviaKind::Newline
+                },
+                (None, Some(' ')) | (None, Some('\t')) => {
+                    self.eat_while(|c| c == ' ' || c == '\t');
+                    TriviaKind::Whitespace
+                },
+                //A carriage return which does not start a line ending is treated as whitespace.
+                (None, Some('\r')) if !crlf => {
+                    self.pos += 1;
+                    TriviaKind::Whitespace
+                },
+                _ => return trivia
+            };
+            trivia.push(Trivia { kind, text: String::from(&self.source[start..self.pos]) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source).iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn tokenize_is_lossless() {
+        for source in &["", "  ", "1 + foo2\n", "\r\n(a)*  b\t%\n\n 3 -4/ x", "1 $ 2", "a\rb", "1 // c\r\n/* /* */ \n */2 /* x"] {
+            let text: String = tokenize(source).iter().map(|t| t.full_text()).collect();
+            assert_eq!(*source, text);
+        }
+    }
+
+    #[test]
+    fn tokenize_kinds() {
+        assert_eq!(
+            vec![TokenKind::OpenParen, TokenKind::Int, TokenKind::Plus, TokenKind::Identifier, TokenKind::CloseParen,
+                 TokenKind::Minus, TokenKind::Star, TokenKind::Slash, TokenKind::Percent, TokenKind::Error, TokenKind::EndOfInput],
+            kinds("(12+ab3) - * / % ?"));
+    }
+
+    #[test]
+    fn tokenize_comments() {
+        let tokens = tokenize("// a\r\n1 /* b /* c */ */ // d\n/ /* e");
+        let trivia = |t: &SyntaxToken| t.leading.iter().chain(t.trailing.iter()).map(|t| (t.kind, t.text.clone())).collect::<Vec<_>>();
+        assert_eq!(
+            vec![(TriviaKind::LineComment, String::from("// a")), (TriviaKind::Newline, String::from("\r\n")),
+                 (TriviaKind::Whitespace, String::from(" ")), (TriviaKind::BlockComment, String::from("/* b /* c */ */")),
+                 (TriviaKind::Whitespace, String::from(" ")), (TriviaKind::LineComment, String::from("// d"))],
+            trivia(&tokens[0]));
+        assert_eq!(TokenKind::Slash, tokens[1].kind);
+        assert_eq!(
+            vec![(TriviaKind::Newline, String::from("\n")), (TriviaKind::Whitespace, String::from(" ")),
+                 (TriviaKind::BlockComment, String::from("/* e"))],
+            trivia(&tokens[1]));
+        assert_eq!(TokenKind::EndOfInput, tokens[2].kind);
+    }
+
+    #[test]
+    fn tokenize_agrees_with_lexer() {
+        for source in &["(12+ab_3) - x % 0 / 7", "1 // c\r\n/* /* */ \n */2 /* x */", "a/**/b//\n/ /*/ */c"] {
+            let lexed: Vec<(usize, usize)> = Lexer::new(source.chars()).tokens().unwrap().iter()
+                .map(|t| (t.span.start.offset, t.span.end.offset + 1))
+                .collect();
+            let tokenized: Vec<(usize, usize)> = tokenize(source).iter()
+                .filter(|t| t.kind != TokenKind::EndOfInput)
+                .map(|t| (t.offset, t.offset + t.text.len()))
+                .collect();
+            assert_eq!(lexed, tokenized, "{}", source);
+        }
+    }
+
+    #[test]
+    fn tokenize_attaches_trivia() {
+        let tokens = tokenize("1  \n  + 2");
+        assert_eq!(vec![Trivia { kind: TriviaKind::Whitespace, text: String::from("  ") }], tokens[0].trailing);
+        assert_eq!(
+            vec![Trivia { kind: TriviaKind::Newline, text: String::from("\n") }, Trivia { kind: TriviaKind::Whitespace, text: String::from("  ") }],
+            tokens[1].leading);
+        assert_eq!(6, tokens[1].offset);
+        assert_eq!("+", tokens[1].text);
+    }
+}
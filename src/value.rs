@@ -1,6 +1,52 @@
+use std::fmt;
+
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
     Int32(i32),
 }
 
+
+impl Value {
+    /// Parses the literal form of a value, inferring its type from the form:  integers, which may be negative,
+    /// are `Int32`.  Other forms are not supported as there are no other types.
+    pub fn parse(text: &str) -> Result<Value, String> {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("'{}' is not a supported value; values must be integers", text));
+        }
+        text.parse::<i32>()
+            .map(Value::Int32)
+            .map_err(|_| format!("'{}' does not fit in a 32-bit integer", text))
+    }
+
+    /// The name of the type of the value, as written in messages.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Int32(_) => "Int32"
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Int32(v) => write!(f, "{}", v)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(Ok(Value::Int32(42)), Value::parse("42"));
+        assert_eq!(Ok(Value::Int32(-2147483648)), Value::parse("-2147483648"));
+        assert!(Value::parse("2147483648").unwrap_err().contains("32-bit"));
+        for text in &["", "-", "1.5", "+1", "true", "\"a\"", " 1"] {
+            assert!(Value::parse(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+
+extern crate compiler_in_rust_lib;
+
+mod support;
+
+use compiler_in_rust_lib::*;
+use compiler_in_rust_lib::ast::*;
+use compiler_in_rust_lib::value::*;
+use compiler_in_rust_lib::env::*;
+use compiler_in_rust_lib::passes::resolve_variables::*;
+use compiler_in_rust_lib::passes::evaluate::*;
+
+use support::*;
+
+fn env_def() -> EnvDef {
+    EnvDefBuilder::new()
+        .with_item("foo", Value::Int32(100))
+        .with_item("bar", Value::Int32(-7))
+        .with_item("big", Value::Int32(i32::MAX))
+        .with_item("while", Value::Int32(3))
+        .build()
+}
+
+fn resolve(source: &str, env_def: &EnvDef) -> Expr {
+    resolve_variables(parse(source).unwrap(), env_def).unwrap()
+}
+
+#[test]
+fn c_backend_matches_interpreter() {
+    if !cc_available() {
+        println!("Skipping test:  cc is not available");
+        return;
+    }
+    let env_def = env_def();
+    let sources = [
+        "1", "foo", "while", "1 + 2 * 3", "foo - bar * 2", "foo / bar", "bar / 2", "foo 2 bar",
+        "big + 1", "big * 2", "0 - big - 2", "foo / 0", "1 foo / 0 2", "bar * big", "big / bar * while"];
+    let exprs: Vec<Expr> = sources.iter().map(|s| resolve(s, &env_def)).collect();
+
+    let results = run_with_c_backend("matches_interpreter", &exprs, &env_def);
+    assert_eq!(sources.len(), results.len());
+
+    let env = env_def.create_with_default_values();
+    for ((source, expr), result) in sources.iter().zip(exprs.iter()).zip(results) {
+        assert_eq!(evaluate(expr, &env).map_err(|e| e.kind), result, "{}", source);
+    }
+}
+
+#[test]
+fn c_backend_without_globals() {
+    if !cc_available() {
+        println!("Skipping test:  cc is not available");
+        return;
+    }
+    let env_def = EnvDefBuilder::new().build();
+    let results = run_with_c_backend("without_globals", &[resolve("2 * 21", &env_def)], &env_def);
+    assert_eq!(vec![Ok(Value::Int32(42))], results);
+}
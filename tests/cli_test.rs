@@ -0,0 +1,141 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the binary with `args`, writing `stdin` to its standard input.
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_compiler_in_rust"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn evaluates_inline_expression() {
+    let output = run(&["-e", "1 + 2 * 3"], "");
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("7\n", stdout(&output));
+    assert_eq!("", stderr(&output));
+}
+
+#[test]
+fn evaluates_stdin_and_files() {
+    let output = run(&["-"], "(4 + 5) // comment\n* 2\n");
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("18\n", stdout(&output));
+
+    let path = std::env::temp_dir().join(format!("cli_test_{}.expr", std::process::id()));
+    std::fs::write(&path, "/* rule */\n10 % 4\n").unwrap();
+    let output = run(&[path.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("2\n", stdout(&output));
+}
+
+#[test]
+fn exit_codes_distinguish_errors() {
+    let output = run(&["-e", "1 + ) $"], "");
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("", stdout(&output));
+    assert_eq!(2, stderr(&output).matches("error[").count());
+
+    assert_eq!(Some(2), run(&["-e", "1 + x"], "").status.code());
+    assert_eq!(Some(3), run(&["-e", "2147483647 + 1"], "").status.code());
+    assert_eq!(Some(64), run(&["-e"], "").status.code());
+    assert_eq!(Some(64), run(&["-e", "1", "-"], "").status.code());
+    assert_eq!(Some(66), run(&["/does/not/exist.expr"], "").status.code());
+}
+
+#[test]
+fn json_errors() {
+    let output = run(&["--error-format=json", "-e", "1 +"], "");
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).starts_with("{\"code\":\"E0203\",\"severity\":\"error\""));
+}
+
+#[test]
+fn repl_stops_at_end_of_input() {
+    let output = run(&[], "1 + 1\n:ast 1+2*3\n");
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).contains(">Result: Int32(2)\n>1 + 2 * 3\n>"));
+}
+
+#[test]
+fn globals_from_definitions_and_files() {
+    let dir = std::env::temp_dir();
+    let json = dir.join(format!("cli_test_globals_{}.json", std::process::id()));
+    let lines = dir.join(format!("cli_test_globals_{}.txt", std::process::id()));
+    std::fs::write(&json, "{\"a\": 5, \"b\": 7}").unwrap();
+    std::fs::write(&lines, "# defaults\na = 1\nc = 10\n").unwrap();
+    let output = run(
+        &["--globals", json.to_str().unwrap(), "--globals", lines.to_str().unwrap(), "--define", "b=-1", "-e", "a * b + c"], "");
+    let bad = run(&["--globals", json.to_str().unwrap(), "--define=b=1.5", "-e", "b"], "");
+    std::fs::remove_file(&json).unwrap();
+    std::fs::remove_file(&lines).unwrap();
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("9\n", stdout(&output));
+    assert_eq!(Some(64), bad.status.code());
+    assert!(stderr(&bad).contains("invalid --define"));
+}
+
+#[test]
+fn repl_keeps_definitions() {
+    let output = run(&["--define", "a=2"], "let x = a * 3\nx + 1\n_ * 2\n:env\n:reset\n:env\nx\nlet 1\n");
+    let stdout = stdout(&output);
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout.contains(">x = 6\n>Result: Int32(7)\n>Result: Int32(14)\n>a = 2\nx = 6\n_ = 14\n"), "{}", stdout);
+    assert!(stdout.contains(">Definitions cleared.\n>a = 2\n>error[E0301]: Variable 'x' does not exist"), "{}", stdout);
+    assert!(stdout.contains("error[E0901]"), "{}", stdout);
+}
+
+#[test]
+fn repl_continues_incomplete_input() {
+    let output = run(&[], "(1 +\n 2) *\n\n3\nlet x = 2 *\n /* comment */ 5\n:type x +\n1\n:nope\n:quit\n1\n");
+    let stdout = stdout(&output);
+    assert!(stdout.contains(">..error[E0203]"), "{}", stdout);
+    assert!(stdout.contains(">Result: Int32(3)\n>.x = 10\n>.Int32\n>error: Unknown command ':nope'"), "{}", stdout);
+    assert!(stdout.ends_with("commands\n>"), "{}", stdout);
+}
+
+#[test]
+fn repl_loads_files() {
+    let path = std::env::temp_dir().join(format!("cli_test_load_{}.expr", std::process::id()));
+    std::fs::write(&path, "let a = 4\nlet b = a *\n  a\n").unwrap();
+    let output = run(&[], &format!(":load {}\n:bytecode b + 1\n:tokens b+1\n", path.to_str().unwrap()));
+    std::fs::remove_file(&path).unwrap();
+    let stdout = stdout(&output);
+    assert!(stdout.contains(">a = 4\nb = 16\n>constants:\n"), "{}", stdout);
+    assert!(stdout.contains("LOAD_GLOBAL  1"), "{}", stdout);
+    assert!(stdout.contains(">1:1      identifier \"b\"\n1:2      operator +\n1:3      literal integer 1\n>"), "{}", stdout);
+}
+
+#[test]
+fn emits_tokens_and_trees() {
+    let output = run(&["--emit=tokens", "-e", "a +\n 2"], "");
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("1:1-1:1    Identifier(\"a\")\n1:3-1:3    BinaryOperator(Add)\n2:2-2:2    LiteralInt32(2)\n", stdout(&output));
+
+    let output = run(&["--emit=ast", "-"], "(1 - 2) * x");
+    assert_eq!("Binary Mul 1:1-1:11\n  Binary Sub 1:1-1:7\n    Literal 1 1:2-1:2\n    Literal 2 1:6-1:6\n  VariableRef x 1:11-1:11\n", stdout(&output));
+
+    let output = run(&["--emit=dot", "-e", "1"], "");
+    assert_eq!("digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n    n0 [label=\"1\\n1:1-1:1\"];\n}\n", stdout(&output));
+
+    assert_eq!(Some(1), run(&["--emit=tokens", "-e", "1 $"], "").status.code());
+    assert_eq!(Some(1), run(&["--emit=ast", "-e", "1 +"], "").status.code());
+    assert_eq!(Some(64), run(&["--emit=ast"], "").status.code());
+    assert_eq!(Some(64), run(&["--emit=wasm", "-e", "1"], "").status.code());
+}
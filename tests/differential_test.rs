@@ -0,0 +1,212 @@
+
+//! Differential testing of every evaluation engine against `passes::evaluate`.
+//!
+//! Random expressions are printed to source with `ast::printer`, parsed and resolved, and then evaluated by every engine.  All engines must produce the same value or
+//! the same `EvaluateErrorKind`.  When they don't, the expression is shrunk to a minimal failing source string.
+
+extern crate compiler_in_rust_lib;
+
+mod support;
+
+use compiler_in_rust_lib::*;
+use compiler_in_rust_lib::ast::*;
+use compiler_in_rust_lib::ast::printer::print;
+use compiler_in_rust_lib::source::*;
+use compiler_in_rust_lib::value::*;
+use compiler_in_rust_lib::env::*;
+use compiler_in_rust_lib::bytecode;
+use compiler_in_rust_lib::codegen::*;
+use compiler_in_rust_lib::codegen::wasm::*;
+use compiler_in_rust_lib::codegen::wasm::interp::*;
+use compiler_in_rust_lib::passes::resolve_variables::*;
+use compiler_in_rust_lib::passes::evaluate::*;
+
+use support::*;
+
+const GLOBALS: [(&str, i32); 5] = [("foo", 100), ("bar", -7), ("big", i32::MAX), ("small", i32::MIN), ("zero", 0)];
+/// The lexer has no negative literals.
+const LITERALS: [i32; 9] = [0, 1, 2, 3, 7, 10, 1000, 65536, i32::MAX];
+
+fn env_def() -> EnvDef {
+    GLOBALS.iter().fold(EnvDefBuilder::new(), |b, &(name, value)| b.with_item(name, Value::Int32(value))).build()
+}
+
+fn span() -> Span {
+    Span::from_location(Location::start())
+}
+
+fn random_program(rng: &mut Rng, depth: u32) -> Expr {
+    let names: Vec<&str> = GLOBALS.iter().map(|g| g.0).collect();
+    random_source_expr(rng, depth, &names, &LITERALS)
+}
+
+/// Parses the printed form of `expr` and resolves it.  Panics if the source does not parse back to `expr`.
+fn compile_source(expr: &Expr, env_def: &EnvDef) -> Expr {
+    let source = print(expr);
+    let parsed = parse(&source).unwrap_or_else(|e| panic!("Failed to parse `{}`: {:?}", source, e));
+    assert_eq!(*expr, parsed, "`{}` did not parse to the generated expression", source);
+    resolve_variables(parsed, env_def).unwrap()
+}
+
+fn evaluate_engine(expr: &Expr, env_def: &EnvDef) -> EngineResult {
+    evaluate(expr, &env_def.create_with_default_values()).map_err(|e| e.kind)
+}
+
+fn bytecode_engine(expr: &Expr, env_def: &EnvDef) -> EngineResult {
+    let bytes = bytecode::serialize::serialize(&bytecode::compile::compile(expr));
+    let chunk = bytecode::serialize::load(&bytes).unwrap();
+    bytecode::vm::run(&chunk, &env_def.create_with_default_values()).map_err(|e| e.kind)
+}
+
+fn wasm_engine(expr: &Expr, env_def: &EnvDef, mode: GlobalMode) -> EngineResult {
+    let module = wasm::compile(expr, env_def, &WasmOptions { globals: mode }).unwrap();
+    let module = decode::decode(&encode::encode(&module)).unwrap();
+    validate::validate(&module).unwrap();
+
+    let values: Vec<WasmValue> = GLOBALS.iter().map(|&(_, v)| WasmValue::I32(v)).collect();
+    let (imports, args) = match mode {
+        GlobalMode::Imports => (values, vec![]),
+        GlobalMode::Parameters => (vec![], values),
+    };
+    let mut instance = Instance::new(&module, &imports).unwrap();
+    match instance.invoke(EVALUATE_EXPORT, &args) {
+        Ok(results) => match results[..] {
+            [WasmValue::I32(v)] => Ok(Value::Int32(v)),
+            _ => panic!("Unexpected results {:?}", results)
+        },
+        Err(Trap::Unreachable) => match instance.exported_global(STATUS_EXPORT) {
+            Some(WasmValue::I32(code)) => Err(status_error(code).expect("Unknown status code")),
+            other => panic!("Unexpected status {:?}", other)
+        },
+        Err(trap) => panic!("Unexpected trap {:?}", trap)
+    }
+}
+
+/// The results of every engine which runs inside the test process.
+fn in_process_results(expr: &Expr, env_def: &EnvDef) -> Vec<(&'static str, EngineResult)> {
+    vec![
+        ("evaluate", evaluate_engine(expr, env_def)),
+        ("bytecode", bytecode_engine(expr, env_def)),
+        ("wasm (imports)", wasm_engine(expr, env_def, GlobalMode::Imports)),
+        ("wasm (parameters)", wasm_engine(expr, env_def, GlobalMode::Parameters)),
+    ]
+}
+
+fn all_agree(results: &[(&'static str, EngineResult)]) -> bool {
+    results.iter().all(|r| r.1 == results[0].1)
+}
+
+/// Returns smaller variants of `expr` that are still valid according to the grammar.
+fn shrink_candidates(expr: &Expr) -> Vec<Expr> {
+    let mut candidates = Vec::new();
+    match expr.kind {
+        ExprKind::Literal { value: Value::Int32(v) } if v != 0 => {
+            candidates.push(Expr::new_literal_with_span(Value::Int32(0), span()));
+            candidates.push(Expr::new_literal_with_span(Value::Int32(v / 2), span()));
+        },
+        ExprKind::Binary { ref op, ref left, ref right } => {
+            candidates.push((**left).clone());
+            candidates.push((**right).clone());
+            for l in shrink_candidates(left) {
+                candidates.push(Expr::new_binary_with_span(op.clone(), l, (**right).clone(), span()));
+            }
+            for r in shrink_candidates(right) {
+                candidates.push(Expr::new_binary_with_span(op.clone(), (**left).clone(), r, span()));
+            }
+        },
+        ExprKind::CompoundExpr { ref exprs } => {
+            for i in 0..exprs.len() {
+                let mut rest = exprs.clone();
+                rest.remove(i);
+                candidates.push(if rest.len() == 1 { *rest.pop().unwrap() } else { Expr::new_compound_expr_with_span(rest, span()) });
+            }
+            for i in 0..exprs.len() {
+                for e in shrink_candidates(&exprs[i]) {
+                    let mut exprs = exprs.clone();
+                    *exprs[i] = e;
+                    candidates.push(Expr::new_compound_expr_with_span(exprs, span()));
+                }
+            }
+        },
+        _ => {}
+    }
+    candidates
+}
+
+/// Repeatedly replaces `expr` with the first smaller candidate for which `fails` still holds.
+fn shrink<F: Fn(&Expr) -> bool>(expr: &Expr, fails: F) -> Expr {
+    let mut current = expr.clone();
+    while let Some(smaller) = shrink_candidates(&current).into_iter().find(|c| fails(c)) {
+        current = smaller;
+    }
+    current
+}
+
+#[test]
+fn in_process_engines_agree() {
+    let env_def = env_def();
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for i in 0..2000 {
+        let expr = random_program(&mut rng, 1 + i % 7);
+        let results = in_process_results(&compile_source(&expr, &env_def), &env_def);
+        if !all_agree(&results) {
+            let minimal = shrink(&expr, |e| !all_agree(&in_process_results(&compile_source(e, &env_def), &env_def)));
+            panic!("Engines disagree on `{}` (shrunk from `{}`): {:?}",
+                   print(&minimal), print(&expr), in_process_results(&compile_source(&minimal, &env_def), &env_def));
+        }
+    }
+}
+
+/// Compiles all of `exprs` at once with a native backend, since invoking `cc` is slow, and checks each
+/// result against `evaluate`.  A failing expression is shrunk by compiling candidates one at a time.
+fn check_native_engine(name: &str, run: fn(&str, &[Expr], &EnvDef) -> Vec<EngineResult>) {
+    let env_def = env_def();
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let exprs: Vec<Expr> = (0..400).map(|i| random_program(&mut rng, 1 + i % 7)).collect();
+    let resolved: Vec<Expr> = exprs.iter().map(|e| compile_source(e, &env_def)).collect();
+
+    let results = run(name, &resolved, &env_def);
+    assert_eq!(exprs.len(), results.len());
+
+    let fails = |e: &Expr| {
+        let resolved = compile_source(e, &env_def);
+        let expected = evaluate_engine(&resolved, &env_def);
+        run(name, &[resolved], &env_def).remove(0) != expected
+    };
+    for ((expr, resolved), result) in exprs.iter().zip(resolved.iter()).zip(results) {
+        let expected = evaluate_engine(resolved, &env_def);
+        if result != expected {
+            let minimal = shrink(expr, fails);
+            panic!("{} disagrees with evaluate on `{}` (shrunk from `{}`)", name, print(&minimal), print(expr));
+        }
+    }
+}
+
+#[test]
+fn c_engine_agrees() {
+    if !cc_available() {
+        println!("Skipping test:  cc is not available");
+        return;
+    }
+    check_native_engine("differential", run_with_c_backend);
+}
+
+#[test]
+fn x86_64_engine_agrees() {
+    if !x86_64_toolchain_available() {
+        println!("Skipping test:  an x86-64 Linux toolchain is not available");
+        return;
+    }
+    check_native_engine("differential", run_with_x86_64_backend);
+}
+
+#[test]
+fn shrinking_finds_minimal_source() {
+    //Only the division by `zero` fails.  Literals shrink to `0` and variables are never replaced, so the
+    //divisor stays `zero`.
+    let expr = parse("foo 7 * 3 - 10 / zero 2").unwrap();
+    let fails = |e: &Expr| evaluate_engine(&compile_source(e, &env_def()), &env_def()) == Err(EvaluateErrorKind::DivisionByZero);
+    assert!(fails(&expr));
+    assert_eq!("0 / zero", print(&shrink(&expr, fails)));
+}
@@ -44,3 +44,8 @@ fn compount_expr() {
     //Expressions "1 * 4", "2", "3"
     assert_eq!(Ok(Value::Int32(3)), execute("1 * 4 2 3"));
 }
+
+#[test]
+fn expression_with_comments() {
+    assert_eq!(Ok(Value::Int32(7)), execute("// Explains the rule\n1 + /* two */ 2 * 3 // trailing"))
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the language server with `args`, sending it each of `messages` with its header.
+fn run(args: &[&str], messages: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lsp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for message in messages {
+        write!(stdin, "Content-Length: {}\r\n\r\n{}", message.len(), message).unwrap();
+    }
+    drop(stdin);
+    child.wait_with_output().unwrap()
+}
+
+/// The bodies of the messages the server wrote.
+fn replies(output: &Output) -> Vec<String> {
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    stdout.split("Content-Length: ").skip(1)
+        .map(|message| String::from(message.split_once("\r\n\r\n").unwrap().1))
+        .collect()
+}
+
+const INITIALIZE: &str = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{}}}"#;
+const SHUTDOWN: &str = r#"{"jsonrpc":"2.0","id":9,"method":"shutdown"}"#;
+const EXIT: &str = r#"{"jsonrpc":"2.0","method":"exit"}"#;
+
+#[test]
+fn serves_a_session() {
+    let output = run(&["--define", "total=5", "--stdio"], &[
+        INITIALIZE,
+        r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///r.expr","languageId":"expr","version":1,"text":"let a = total\na + b"}}}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///r.expr"},"position":{"line":0,"character":9}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///r.expr"},"position":{"line":1,"character":0}}}"#,
+        r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///r.expr","version":2},"contentChanges":[{"text":"a"}]}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"textDocument/completion","params":{"textDocument":{"uri":"file:///r.expr"},"position":{"line":0,"character":1}}}"#,
+        "{",
+        SHUTDOWN,
+        EXIT
+    ]);
+    assert_eq!(Some(0), output.status.code());
+    let replies = replies(&output);
+    assert_eq!(8, replies.len());
+    assert!(replies[0].starts_with(r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities":"#), "{}", replies[0]);
+    assert_eq!(
+        r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///r.expr","diagnostics":[{"range":{"start":{"line":1,"character":4},"end":{"line":1,"character":5}},"severity":1,"code":"E0301","source":"compiler_in_rust","message":"Variable 'b' does not exist; did you mean 'a'?"}]}}"#,
+        replies[1]);
+    assert!(replies[2].contains("Global, with the default value `5`."), "{}", replies[2]);
+    assert_eq!(
+        r#"{"jsonrpc":"2.0","id":3,"result":{"uri":"file:///r.expr","range":{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}}}"#,
+        replies[3]);
+    assert!(replies[4].contains("Variable 'a' does not exist"), "{}", replies[4]);
+    assert!(replies[5].contains(r#""label":"total""#), "{}", replies[5]);
+    assert!(replies[6].starts_with(r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"#), "{}", replies[6]);
+    assert_eq!(r#"{"jsonrpc":"2.0","id":9,"result":null}"#, replies[7]);
+}
+
+#[test]
+fn exits_without_shutdown() {
+    let output = run(&[], &[INITIALIZE, EXIT]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(1, replies(&output).len());
+    assert_eq!(Some(1), run(&[], &[INITIALIZE]).status.code());
+}
+
+#[test]
+fn rejects_invalid_arguments() {
+    assert_eq!(Some(64), run(&["--define", "1=2"], &[]).status.code());
+    assert_eq!(Some(64), run(&["--verbose"], &[]).status.code());
+    assert_eq!(Some(66), run(&["--globals", "/nonexistent/globals"], &[]).status.code());
+}
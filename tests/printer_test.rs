@@ -0,0 +1,34 @@
+
+//! Round trip property test for `ast::printer`:  printing any expression and parsing the result must
+//! give back the same expression.
+
+extern crate compiler_in_rust_lib;
+
+mod support;
+
+use compiler_in_rust_lib::*;
+use compiler_in_rust_lib::ast::printer::print;
+
+use support::*;
+
+#[test]
+fn print_then_parse_round_trips() {
+    let names = ["a", "b", "foo", "x1"];
+    let literals = [0, 1, 2, 42, 1000, i32::MAX];
+    let mut rng = Rng(0x853c_49e6_748f_ea9b);
+
+    for i in 0..5000 {
+        let expr = random_source_expr(&mut rng, 1 + i % 8, &names, &literals);
+        let source = print(&expr);
+        let parsed = parse(&source).unwrap_or_else(|e| panic!("Failed to parse `{}`: {:?}", source, e));
+        assert_eq!(expr, parsed, "`{}` did not parse to the printed expression", source);
+        //The printed form is canonical.
+        assert_eq!(source, print(&parsed));
+    }
+}
+
+#[test]
+fn print_normalizes_source() {
+    let source = "((1+2))*foo  (a) ((b c) - 3 % (4 / 5))";
+    assert_eq!("(1 + 2) * foo a (b c) - 3 % (4 / 5)", print(&parse(source).unwrap()));
+}
@@ -0,0 +1,151 @@
+
+//! Helpers shared by the integration tests:  random expressions and building and running native code.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::process::Command;
+use std::env;
+
+use compiler_in_rust_lib::ast::*;
+use compiler_in_rust_lib::common::*;
+use compiler_in_rust_lib::source::*;
+use compiler_in_rust_lib::value::*;
+use compiler_in_rust_lib::env::*;
+use compiler_in_rust_lib::codegen::*;
+use compiler_in_rust_lib::codegen::c::*;
+use compiler_in_rust_lib::codegen::x86_64::*;
+use compiler_in_rust_lib::passes::evaluate::*;
+
+pub type EngineResult = Result<Value, EvaluateErrorKind>;
+
+/// A xorshift generator so the tests need no dependencies and are reproducible.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+/// Generates an unresolved expression of at most `depth` levels of arbitrary shape.  Parentheses make
+/// any tree expressible in source, so compound expressions may appear anywhere except directly in a
+/// compound expression of one element.  Literals must be non-negative as the lexer has no negative literals.
+pub fn random_source_expr(rng: &mut Rng, depth: u32, names: &[&str], literals: &[i32]) -> Expr {
+    let span = Span::from_location(Location::start());
+    if depth == 0 || rng.below(4) == 0 {
+        return if rng.below(2) == 0 {
+            Expr::new_literal_with_span(Value::Int32(*rng.choose(literals)), span)
+        } else {
+            Expr::new_variable_ref_with_span(String::from(*rng.choose(names)), span)
+        };
+    }
+    if rng.below(8) == 0 {
+        let exprs = (0..2 + rng.below(3)).map(|_| Box::new(random_source_expr(rng, depth - 1, names, literals))).collect();
+        return Expr::new_compound_expr_with_span(exprs, span);
+    }
+    let op = rng.choose(&[BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div, BinaryOp::Mod]).clone();
+    let left = random_source_expr(rng, depth - 1, names, literals);
+    let right = random_source_expr(rng, depth - 1, names, literals);
+    Expr::new_binary_with_span(op, left, right, span)
+}
+
+pub fn cc_available() -> bool {
+    Command::new("cc").arg("--version").output().map(|o| o.status.success()).unwrap_or(false)
+}
+
+/// The generated assembly can only be linked on x86-64 Linux.
+pub fn x86_64_toolchain_available() -> bool {
+    cfg!(all(target_arch = "x86_64", target_os = "linux")) && cc_available()
+}
+
+fn int32_values(env_def: &EnvDef) -> Vec<String> {
+    env_def.fields().iter().map(|f| match f.default_value {
+        Value::Int32(v) => format!("(int32_t){}", v as i64)
+    }).collect()
+}
+
+/// Compiles `files` (name, contents) in a fresh temporary directory with `cc`, runs the executable and
+/// parses each line of its output as "<status> <result>".
+fn build_and_run(name: &str, files: &[(&str, String)], cc_args: &[&str]) -> Vec<EngineResult> {
+    let dir = env::temp_dir().join(format!("compiler_in_rust_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let exe_file = dir.join("rules");
+
+    let mut cc = Command::new("cc");
+    cc.args(cc_args).arg("-o").arg(&exe_file);
+    for &(file_name, ref contents) in files {
+        let path = dir.join(file_name);
+        fs::write(&path, contents).unwrap();
+        cc.arg(&path);
+    }
+    let output = cc.output().unwrap();
+    assert!(output.status.success(), "cc failed:\n{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe_file).output().unwrap();
+    assert!(run.status.success());
+    fs::remove_dir_all(&dir).unwrap();
+
+    String::from_utf8(run.stdout).unwrap().lines().map(|line| {
+        let parts: Vec<i32> = line.split(' ').map(|p| p.parse().unwrap()).collect();
+        match parts[0] {
+            STATUS_OK => Ok(Value::Int32(parts[1])),
+            code => Err(status_error(code).expect("Unknown status code"))
+        }
+    }).collect()
+}
+
+/// Compiles each of the resolved `exprs` with the C backend and returns the result of running them
+/// with the default values of `env_def`.
+pub fn run_with_c_backend(name: &str, exprs: &[Expr], env_def: &EnvDef) -> Vec<EngineResult> {
+    let mut program = String::from("#include <stdio.h>\n");
+    let mut main = String::from("int main(void) {\n    int32_t result;\n    int status;\n");
+    let initializers = int32_values(env_def);
+
+    for (i, expr) in exprs.iter().enumerate() {
+        let function_name = format!("rule{}", i);
+        program.push_str(&c::compile(expr, env_def, &COptions { function_name: function_name.clone() }).unwrap());
+        main.push_str(&format!("    {{\n        {}_globals g = {{ {} }};\n", function_name, initializers.join(", ")));
+        main.push_str(&format!("        result = 0;\n        status = {}(&g, &result);\n", function_name));
+        main.push_str("        printf(\"%d %d\\n\", status, (int)result);\n    }\n");
+    }
+    main.push_str("    return 0;\n}\n");
+    program.push_str(&main);
+
+    build_and_run(&format!("c_{}", name), &[("rules.c", program)], &["-std=c99", "-Wall", "-Werror"])
+}
+
+/// Compiles each of the resolved `exprs` with the x86-64 backend and returns the result of running them
+/// with the default values of `env_def`.
+pub fn run_with_x86_64_backend(name: &str, exprs: &[Expr], env_def: &EnvDef) -> Vec<EngineResult> {
+    let mut asm = String::new();
+    let mut driver = String::from("#include <stdio.h>\n#include <stdint.h>\n");
+    let mut calls = String::new();
+
+    for (i, expr) in exprs.iter().enumerate() {
+        let function_name = format!("rule{}", i);
+        asm.push_str(&x86_64::compile(expr, env_def, &AsmOptions { function_name: function_name.clone() }).unwrap());
+        driver.push_str(&format!("int {}(const int32_t *globals, int32_t *result);\n", function_name));
+        calls.push_str(&format!("    result = 0;\n    status = {}(globals, &result);\n    printf(\"%d %d\\n\", status, (int)result);\n", function_name));
+    }
+    let mut globals = int32_values(env_def);
+    if globals.is_empty() {
+        globals.push(String::from("0"));
+    }
+    driver.push_str(&format!("int main(void) {{\n    const int32_t globals[] = {{ {} }};\n    int32_t result;\n    int status;\n", globals.join(", ")));
+    driver.push_str(&calls);
+    driver.push_str("    return 0;\n}\n");
+
+    build_and_run(&format!("x86_64_{}", name), &[("driver.c", driver), ("rules.s", asm)], &[])
+}
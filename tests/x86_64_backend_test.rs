@@ -0,0 +1,65 @@
+
+extern crate compiler_in_rust_lib;
+
+mod support;
+
+use compiler_in_rust_lib::ast::*;
+use compiler_in_rust_lib::common::*;
+use compiler_in_rust_lib::source::*;
+use compiler_in_rust_lib::value::*;
+use compiler_in_rust_lib::env::*;
+use compiler_in_rust_lib::passes::evaluate::*;
+
+use support::*;
+
+const GLOBALS: [i32; 5] = [100, -7, i32::MAX, i32::MIN, 0];
+const LITERALS: [i32; 9] = [0, 1, -1, 2, 3, 7, 1000, i32::MAX, i32::MIN];
+
+fn env_def() -> EnvDef {
+    let mut builder = EnvDefBuilder::new();
+    for (i, value) in GLOBALS.iter().enumerate() {
+        builder = builder.with_item(&format!("g{}", i), Value::Int32(*value));
+    }
+    builder.build()
+}
+
+fn span() -> Span {
+    Span::from_location(Location::start())
+}
+
+fn random_expr(rng: &mut Rng, depth: u32) -> Expr {
+    if depth == 0 || rng.below(4) == 0 {
+        return if rng.below(2) == 0 {
+            Expr::new_literal_with_span(Value::Int32(*rng.choose(&LITERALS)), span())
+        } else {
+            Expr::new_variable_index_with_span(rng.below(GLOBALS.len() as u64) as u32, span())
+        };
+    }
+    if rng.below(10) == 0 {
+        let exprs = (0..2 + rng.below(2)).map(|_| Box::new(random_expr(rng, depth - 1))).collect();
+        return Expr::new_compound_expr_with_span(exprs, span());
+    }
+    let op = rng.choose(&[BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div, BinaryOp::Mod]).clone();
+    let left = random_expr(rng, depth - 1);
+    let right = random_expr(rng, depth - 1);
+    Expr::new_binary_with_span(op, left, right, span())
+}
+
+#[test]
+fn x86_64_backend_matches_interpreter_on_random_exprs() {
+    if !x86_64_toolchain_available() {
+        println!("Skipping test:  an x86-64 Linux toolchain is not available");
+        return;
+    }
+    let env_def = env_def();
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let exprs: Vec<Expr> = (0..300).map(|i| random_expr(&mut rng, 1 + i % 8)).collect();
+
+    let results = run_with_x86_64_backend("random_exprs", &exprs, &env_def);
+    assert_eq!(exprs.len(), results.len());
+
+    let env = env_def.create_with_default_values();
+    for (expr, result) in exprs.iter().zip(results) {
+        assert_eq!(evaluate(expr, &env).map_err(|e| e.kind), result, "{:?}", expr);
+    }
+}